@@ -40,6 +40,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use super::{OsType, PlatformInfo};
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GameDataLocation {
     pub root: PathBuf,
@@ -50,6 +52,7 @@ pub struct GameDataLocation {
     pub saves: PathBuf,
     pub options: PathBuf,
     pub screenshots: PathBuf,
+    pub crash_reports: PathBuf,
 }
 
 impl GameDataLocation {
@@ -64,6 +67,7 @@ impl GameDataLocation {
             saves: root.join("resourcepacks"),
             options: root.join("options.txt"),
             screenshots: root.join("screenshots"),
+            crash_reports: root.join("crash-reports"),
         }
     }
 
@@ -79,9 +83,34 @@ impl GameDataLocation {
         self.logs.join(file_name)
     }
 
+    /// Read a log in the `logs` folder, the gzipped logs are decompressed
+    pub fn read_log<P: AsRef<Path>>(&self, file_name: P) -> std::io::Result<String> {
+        crate::utils::gzip::read_to_string(self.get_log(file_name))
+    }
+
+    pub fn read_latest_log(&self) -> std::io::Result<String> {
+        crate::utils::gzip::read_to_string(&self.latest_log)
+    }
+
     pub fn get_level_file<P: AsRef<Path>>(&self, world_name: P) -> PathBuf {
         self.saves.join(world_name).join("level.dat")
     }
+
+    /// List the crash reports, the newest first, see [`list_crash_reports`]
+    ///
+    /// [`list_crash_reports`]: crate::game_data::crash_report::list_crash_reports
+    pub fn list_crash_reports(
+        &self,
+    ) -> anyhow::Result<Vec<crate::game_data::crash_report::CrashReportInfo>> {
+        crate::game_data::crash_report::list_crash_reports(&self.crash_reports)
+    }
+
+    /// Count the mods in the `mods` folder, see [`summarize_folder`]
+    ///
+    /// [`summarize_folder`]: crate::game_data::mods::summarize_folder
+    pub fn mod_summary(&self) -> anyhow::Result<crate::game_data::mods::ModSummary> {
+        crate::game_data::mods::summarize_folder(&self.mods)
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -91,6 +120,9 @@ pub struct MinecraftLocation {
     pub libraries: PathBuf,
     pub assets: PathBuf,
     pub versions: PathBuf,
+
+    /// The Java runtimes provided by Mojang, in `runtime/<component>/<os>/`
+    pub runtime: PathBuf,
 }
 
 impl MinecraftLocation {
@@ -101,13 +133,52 @@ impl MinecraftLocation {
             assets: root.join("assets"),
             libraries: root.join("libraries"),
             versions: root.join("versions"),
+            runtime: root.join("runtime"),
         }
     }
 
+    /// Keep the assets of the version in its own folder, `versions/<version>/assets`, instead of
+    /// the shared one, for fully isolated instances. The installs and launches with this
+    /// location read and write the assets there.
+    pub fn with_isolated_assets<P: AsRef<Path>>(mut self, version: P) -> Self {
+        self.assets = self.get_version_root(version).join("assets");
+        self
+    }
+
     pub fn get_natives_root<P: AsRef<Path>>(&self, version: P) -> PathBuf {
         self.get_version_root(version).join("cvl-natives")
     }
 
+    /// List all natives folders of a version, the `cvl-natives` one the launch extracts into and
+    /// the `natives-*` ones
+    pub fn list_natives<P: AsRef<Path>>(&self, version: P) -> std::io::Result<Vec<PathBuf>> {
+        let mut natives = Vec::new();
+        for entry in self.get_version_root(version).read_dir()? {
+            let path = entry?.path();
+            let is_natives = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.starts_with("natives-") || name == "cvl-natives",
+                None => false,
+            };
+            if path.is_dir() && is_natives {
+                natives.push(path);
+            }
+        }
+        Ok(natives)
+    }
+
+    /// Remove the stale natives folders of a version, like the `natives-*` ones of other
+    /// platforms or launchers, except the one the launch extracts into, see
+    /// [`get_natives_root`](Self::get_natives_root)
+    pub fn clean_natives<P: AsRef<Path>>(&self, version: P) -> std::io::Result<()> {
+        let current = self.get_natives_root(&version);
+        for natives in self.list_natives(&version)? {
+            if natives != current {
+                std::fs::remove_dir_all(natives)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_version_root<P: AsRef<Path>>(&self, version: P) -> PathBuf {
         self.versions.join(version)
     }
@@ -149,7 +220,62 @@ impl MinecraftLocation {
             .join(format!("{version_assets}.json"))
     }
 
+    /// The folder legacy versions read their assets from, by their names instead of hashes
+    pub fn get_assets_virtual(&self, version_assets: &str) -> PathBuf {
+        self.assets.join("virtual").join(version_assets)
+    }
+
     pub fn get_log_config<P: AsRef<Path>>(&self, file: P) -> PathBuf {
         self.assets.join("log_configs").join(file)
     }
+
+    /// The root of a Mojang Java runtime component, like `java-runtime-gamma`
+    pub fn get_runtime_root(&self, component: &str, platform: &PlatformInfo) -> PathBuf {
+        let os = runtime_os_name(platform);
+        self.runtime.join(component).join(os).join(component)
+    }
+}
+
+/// The os name used by Mojang's Java runtime manifest, like `windows-x64`, `mac-os-arm64`
+pub(crate) fn runtime_os_name(platform: &PlatformInfo) -> &'static str {
+    match (&platform.os_type, platform.arch.as_str()) {
+        (OsType::Windows, "x86") => "windows-x86",
+        (OsType::Windows, "aarch64") => "windows-arm64",
+        (OsType::Windows, _) => "windows-x64",
+        (OsType::Linux, "x86") => "linux-i386",
+        (OsType::Linux, _) => "linux",
+        (OsType::Osx, "aarch64") => "mac-os-arm64",
+        (OsType::Osx, _) => "mac-os",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clean_natives() {
+        let root = std::env::temp_dir().join(format!("cvl-test-{}", uuid::Uuid::new_v4()));
+        let minecraft = MinecraftLocation::new(&root);
+        let current = minecraft.get_natives_root("1.20.1");
+        std::fs::create_dir_all(&current).unwrap();
+        std::fs::write(current.join("lwjgl.dll"), "").unwrap();
+        for stale in ["natives-windows-x64", "natives-linux-x64"] {
+            std::fs::create_dir_all(minecraft.get_version_root("1.20.1").join(stale)).unwrap();
+        }
+        assert_eq!(minecraft.list_natives("1.20.1").unwrap().len(), 3);
+
+        minecraft.clean_natives("1.20.1").unwrap();
+        assert_eq!(minecraft.list_natives("1.20.1").unwrap(), vec![current]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_assets_virtual() {
+        let minecraft = MinecraftLocation::new(".minecraft");
+        assert_eq!(
+            minecraft.get_assets_virtual("legacy"),
+            Path::new(".minecraft/assets/virtual/legacy")
+        );
+    }
 }
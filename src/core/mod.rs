@@ -37,10 +37,7 @@
 //! use aml_core::core::version::Version;
 //!
 //!  async fn fn_name() {
-//!     let version = reqwest::get("https://piston-meta.mojang.com/v1/packages/715ccf3330885e75b205124f09f8712542cbe7e0/1.20.1.json")
-//!         .await
-//!         .unwrap()
-//!         .json::<Version>()
+//!     let version = Version::from_url("https://piston-meta.mojang.com/v1/packages/715ccf3330885e75b205124f09f8712542cbe7e0/1.20.1.json")
 //!         .await
 //!         .unwrap();
 //!     let resolved = version.parse(&MinecraftLocation::new(".minecraft"), &PlatformInfo::new().await).await.unwrap();
@@ -60,6 +57,7 @@
 
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
 use once_cell::sync::Lazy;
 use reqwest::Client;
@@ -67,10 +65,26 @@ use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 
 pub mod folder;
-// pub mod task;
+pub mod task;
 pub mod version;
 
-pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| Client::new());
+static CLIENT: Lazy<RwLock<Client>> = Lazy::new(|| RwLock::new(Client::new()));
+
+/// The http client of this crate when it's first used. A client set later by
+/// [`set_http_client`] is not seen here.
+#[deprecated(note = "use http_client, which follows set_http_client")]
+pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(http_client);
+
+/// The http client used by all downloads and fetchers of this crate
+pub fn http_client() -> Client {
+    CLIENT.read().unwrap().clone()
+}
+
+/// Replace the http client used by all downloads and fetchers of this crate, so the proxy, TLS
+/// and default headers of the app can be shared. The requests already started are not affected.
+pub fn set_http_client(client: Client) {
+    *CLIENT.write().unwrap() = client;
+}
 /// May not actually be used
 pub static DEFAULT_LAUNCHER_PROFILE: &[u8] = include_bytes!("./launcher_profile.json");
 
@@ -116,8 +130,6 @@ impl PlatformInfo {
             version: {
                 #[cfg(windows)]
                 {
-                    use regex::Regex;
-
                     let mut command = Command::new("C:\\Windows\\System32\\cmd.exe");
                     command.creation_flags(0x08000000);
                     command.args(&[
@@ -126,9 +138,7 @@ impl PlatformInfo {
                     ]);
                     let output = command.output().await.unwrap();
                     let stdout = String::from_utf8(output.stdout).unwrap();
-
-                    let regex = Regex::new(r"\s+").unwrap();
-                    regex.replace_all(&stdout, ".").to_string()
+                    normalize_windows_version(&stdout)
                 }
                 #[cfg(not(windows))]
                 {
@@ -160,6 +170,31 @@ impl PlatformInfo {
     }
 }
 
+/// Turn the output of `[System.Environment]::OSVersion.Version`, a table like
+///
+/// ```text
+/// Major  Minor  Build  Revision
+/// -----  -----  -----  --------
+/// 10     0      19045  0
+/// ```
+///
+/// into `10.0.19045`, the form the `os.version` rules of version jsons match against.
+pub fn normalize_windows_version(output: &str) -> String {
+    output
+        .lines()
+        .map(|line| {
+            line.split(|c: char| c.is_whitespace() || c == '.')
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .find(|parts| parts.len() >= 3 && parts.iter().all(|p| p.parse::<u32>().is_ok()))
+        .map(|parts| parts[..3].join("."))
+        .unwrap_or(output.trim().to_string())
+}
+
+/// The largest `-Xmx`, in MB, a 32-bit JVM can reliably reserve
+pub const MAX_32BIT_MEMORY: u32 = 1536;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct JavaExec {
     pub binary: PathBuf,
@@ -189,11 +224,308 @@ impl JavaExec {
             // version,
         }
     }
+
+    /// Find the Java runtime provided by Mojang for the component in `java_version`, like
+    /// `java-runtime-gamma`. Return None if the runtime is not installed.
+    pub fn for_component(
+        component: &str,
+        minecraft: &folder::MinecraftLocation,
+        platform: &PlatformInfo,
+    ) -> Option<Self> {
+        let binary = Self::component_binary(component, minecraft, platform);
+        if binary.is_file() {
+            Some(Self { binary })
+        } else {
+            None
+        }
+    }
+
+    /// Read the properties of the JVM and check that a 32-bit one can address `max_memory` MB of
+    /// heap.
+    ///
+    /// Return a warning if it cannot, the game would fail to start with such `-Xmx`.
+    pub async fn check_max_memory(&self, max_memory: u32) -> anyhow::Result<Option<String>> {
+        let output = Command::new(&self.binary)
+            .args(["-XshowSettings:properties", "-version"])
+            .output()
+            .await?;
+        // the settings are printed to stderr, like the version
+        let settings = String::from_utf8_lossy(&output.stderr);
+        Ok(max_memory_warning(&settings, max_memory))
+    }
+
+    /// Run `java -version` and read the major version, like `8` for `1.8.0_381` or `17`
+    pub async fn major_version(&self) -> anyhow::Result<u32> {
+        let version = self.version_output().await?;
+        parse_java_major_version(&version).ok_or(anyhow::anyhow!(
+            "Cannot read the version of {}",
+            self.binary.display()
+        ))
+    }
+
+    /// Run `java -version` and check it is at least the major version required by the game.
+    ///
+    /// The error is a [`JavaMismatch`] when the java is too old, so that it can be told apart
+    /// from a java that cannot run with `downcast_ref`.
+    pub async fn check_version(&self, required: &version::JavaVersion) -> anyhow::Result<()> {
+        let found_major = self.major_version().await?;
+        if (found_major as i32) < required.major_version {
+            return Err(JavaMismatch {
+                required_major: required.major_version as u32,
+                found_major,
+                binary: self.binary.clone(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn version_output(&self) -> anyhow::Result<String> {
+        let output = Command::new(&self.binary).arg("-version").output().await?;
+        // `java -version` prints to stderr
+        Ok(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+
+    fn component_binary(
+        component: &str,
+        minecraft: &folder::MinecraftLocation,
+        platform: &PlatformInfo,
+    ) -> PathBuf {
+        let root = minecraft.get_runtime_root(component, platform);
+        match platform.os_type {
+            OsType::Windows => root.join("bin").join("java.exe"),
+            OsType::Linux => root.join("bin").join("java"),
+            OsType::Osx => root
+                .join("jre.bundle")
+                .join("Contents")
+                .join("Home")
+                .join("bin")
+                .join("java"),
+        }
+    }
 }
 
+/// The java configured is older than the one required by the version
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavaMismatch {
+    pub required_major: u32,
+    pub found_major: u32,
+    pub binary: PathBuf,
+}
+
+impl std::fmt::Display for JavaMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Java {} is required, but {} is Java {}",
+            self.required_major,
+            self.binary.display(),
+            self.found_major
+        )
+    }
+}
+
+impl std::error::Error for JavaMismatch {}
+
+/// Check the output of `java -XshowSettings:properties -version` against the requested
+/// `max_memory` in MB. No warning is given if the bitness of the JVM is unknown.
+pub fn max_memory_warning(settings_output: &str, max_memory: u32) -> Option<String> {
+    if max_memory <= MAX_32BIT_MEMORY {
+        return None;
+    }
+    // `sun.arch.data.model` is missing from some non-HotSpot VMs, their `os.arch` tells it too
+    let is_32bit = match java_property(settings_output, "sun.arch.data.model") {
+        Some(data_model) => data_model == "32",
+        None => java_property(settings_output, "os.arch")
+            .is_some_and(|arch| matches!(arch, "x86" | "i386" | "i486" | "i586" | "i686" | "arm")),
+    };
+    if !is_32bit {
+        return None;
+    }
+    Some(format!(
+        "A 32-bit Java cannot allocate {max_memory} MB of memory, use at most {MAX_32BIT_MEMORY} MB or a 64-bit Java"
+    ))
+}
+
+/// Read a property like `os.arch = amd64` from the output of `-XshowSettings:properties`
+fn java_property<'a>(settings_output: &'a str, name: &str) -> Option<&'a str> {
+    settings_output.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == name).then(|| value.trim())
+    })
+}
+
+/// Read the major version from the output of `java -version`, whose first line is like
+/// `openjdk version "17.0.8" 2023-07-18` or `java version "1.8.0_381"`
+pub fn parse_java_major_version(version_output: &str) -> Option<u32> {
+    let version = version_output.split('"').nth(1)?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// A file to download, the task type of every install, like the libraries, the assets and the
+/// java runtimes. See [`download_files`](crate::utils::download::download_files).
+///
+/// More fields may be added, so it's built with [`Download::new`] outside of this crate.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct Download {
     pub url: String,
     pub file: PathBuf,
+
+    /// The expected sha1 in lowercase hex, used to check the file when it's known
     pub sha1: Option<String>,
+
+    /// The expected size in bytes, a cheap check before the sha1 when it's known
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+impl Download {
+    /// A download of `url` to `file`, without a sha1 or size to check it
+    pub fn new(url: impl Into<String>, file: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            file: file.into(),
+            sha1: None,
+            size: None,
+        }
+    }
+
+    pub fn with_sha1(mut self, sha1: impl Into<String>) -> Self {
+        self.sha1 = Some(sha1.into());
+        self
+    }
+
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_java_for_component() {
+        let minecraft = folder::MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let platform = |os_type, arch: &str| PlatformInfo {
+            arch: arch.to_string(),
+            name: String::new(),
+            os_type,
+            version: String::new(),
+        };
+        let component = "java-runtime-gamma";
+        let cases = [
+            (
+                platform(OsType::Windows, "x64"),
+                "windows-x64/java-runtime-gamma/bin/java.exe",
+            ),
+            (
+                platform(OsType::Linux, "x64"),
+                "linux/java-runtime-gamma/bin/java",
+            ),
+            (
+                platform(OsType::Osx, "aarch64"),
+                "mac-os-arm64/java-runtime-gamma/jre.bundle/Contents/Home/bin/java",
+            ),
+        ];
+        for (platform, path) in cases {
+            let binary = minecraft.runtime.join(component).join(path);
+            assert_eq!(
+                JavaExec::component_binary(component, &minecraft, &platform),
+                binary
+            );
+            assert_eq!(
+                JavaExec::for_component(component, &minecraft, &platform),
+                None
+            );
+            std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+            std::fs::write(&binary, "").unwrap();
+            assert_eq!(
+                JavaExec::for_component(component, &minecraft, &platform),
+                Some(JavaExec { binary })
+            );
+        }
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(home.join("bin")).unwrap();
+        let binary = home.join("bin").join("java");
+        std::fs::write(
+            &binary,
+            "#!/bin/sh\necho 'java version \"1.8.0_381\"' >&2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let java = JavaExec::new(&home).await;
+        let required = |major_version| version::JavaVersion {
+            component: "java-runtime-gamma".to_string(),
+            major_version,
+        };
+
+        let error = java.check_version(&required(17)).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<JavaMismatch>(),
+            Some(&JavaMismatch {
+                required_major: 17,
+                found_major: 8,
+                binary: binary.clone(),
+            })
+        );
+        assert!(java.check_version(&required(8)).await.is_ok());
+        std::fs::remove_dir_all(home).unwrap();
+    }
+
+    #[test]
+    fn test_max_memory_warning() {
+        let java_32bit = "Property settings:\n    \
+            java.version = 1.8.0_381\n    \
+            os.arch = x86\n    \
+            sun.arch.data.model = 32\n\n\
+            java version \"1.8.0_381\"\n";
+        // the banner of this VM doesn't say 64-Bit
+        let java_64bit = "Property settings:\n    \
+            java.version = 17.0.8\n    \
+            java.vm.name = Eclipse OpenJ9 VM\n    \
+            os.arch = amd64\n\n\
+            openjdk version \"17.0.8\" 2023-07-18\n\
+            Eclipse OpenJ9 VM (build openj9-0.40.0, JRE 17 Linux amd64, mixed mode)\n";
+        let warning = max_memory_warning(java_32bit, 4096).unwrap();
+        assert!(warning.contains("4096"));
+        assert_eq!(max_memory_warning(java_32bit, 1024), None);
+        assert_eq!(max_memory_warning(java_64bit, 4096), None);
+        assert!(max_memory_warning("    os.arch = i386\n", 4096).is_some());
+        assert_eq!(
+            max_memory_warning("java version \"1.8.0_381\"\n", 4096),
+            None
+        );
+    }
+
+    #[test]
+    fn test_normalize_windows_version() {
+        let output = "\r\nMajor  Minor  Build  Revision\r\n-----  -----  -----  --------\r\n10     0      19045  0       \r\n\r\n";
+        assert_eq!(normalize_windows_version(output), "10.0.19045");
+        assert_eq!(normalize_windows_version("6.1.7601.65536"), "6.1.7601");
+    }
+
+    #[test]
+    fn test_parse_java_major_version() {
+        let version =
+            |v: &str| parse_java_major_version(&format!("openjdk version \"{v}\" 2023-07-18\n"));
+        assert_eq!(version("1.8.0_381"), Some(8));
+        assert_eq!(version("17.0.8"), Some(17));
+        assert_eq!(version("21"), Some(21));
+        assert_eq!(parse_java_major_version("command not found"), None);
+    }
 }
@@ -34,12 +34,85 @@
 //! }
 //! ```
 
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::{future, stream, FutureExt, Stream, StreamExt};
+use tokio::sync::Notify;
+
+use super::Download;
+
+/// The progress of downloads by files and by bytes
+///
+/// Each file weighs its size, so a large jar moves the byte progress more than a small asset.
+/// The files without a known size weigh the average known size.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WeightedProgress {
+    pub completed_files: usize,
+    pub total_files: usize,
+    pub completed_bytes: u64,
+    pub total_bytes: u64,
+
+    /// The weight of the files without a known size
+    unknown_size: u64,
+}
+
+impl WeightedProgress {
+    pub fn new(downloads: &[Download]) -> Self {
+        let known = downloads.iter().filter_map(|download| download.size);
+        let (count, sum) = known.fold((0, 0), |(count, sum), size| (count + 1, sum + size));
+        let unknown_size = sum.checked_div(count).unwrap_or(1).max(1);
+        let mut progress = Self {
+            total_files: downloads.len(),
+            unknown_size,
+            ..Self::default()
+        };
+        progress.total_bytes = downloads
+            .iter()
+            .map(|download| progress.weight(download))
+            .sum();
+        progress
+    }
+
+    fn weight(&self, download: &Download) -> u64 {
+        download.size.unwrap_or(self.unknown_size)
+    }
+
+    /// Count a download as done
+    pub fn complete(&mut self, download: &Download) {
+        self.completed_files += 1;
+        self.completed_bytes += self.weight(download);
+    }
+
+    /// The percentage of files done, from 0 to 100
+    pub fn file_percentage(&self) -> f64 {
+        percentage(self.completed_files as f64, self.total_files as f64)
+    }
+
+    /// The percentage of bytes done, from 0 to 100
+    pub fn byte_percentage(&self) -> f64 {
+        percentage(self.completed_bytes as f64, self.total_bytes as f64)
+    }
+}
+
+fn percentage(completed: f64, total: f64) -> f64 {
+    if total == 0.0 {
+        100.0
+    } else {
+        completed / total * 100.0
+    }
+}
+
 /// Execute the corresponding function when the installation event occurs
 ///
 /// please use `TaskEventListeners::new()` to create a new instance, and use
 /// `TaskEventListeners::on_start()` `EventListeners::on_progress()`
 /// `TaskEventListeners::on_succeed()` `EventListeners::on_failed()`
-/// to register the event
+/// `TaskEventListeners::on_file()` to register the event
 ///
 /// # Examples
 ///
@@ -62,6 +135,8 @@ pub struct TaskEventListeners {
     on_progress: Box<dyn Fn(usize, usize, usize)>,
     on_succeed: Box<dyn Fn()>,
     on_failed: Box<dyn Fn()>,
+    on_file: Box<dyn Fn(String, bool)>,
+    on_weighted_progress: Box<dyn Fn(WeightedProgress)>,
 }
 
 impl Default for TaskEventListeners {
@@ -73,11 +148,24 @@ impl Default for TaskEventListeners {
             }),
             on_succeed: Box::new(|| println!("Done!")),
             on_failed: Box::new(|| println!("Error!")),
+            on_file: Box::new(|_, _| ()),
+            on_weighted_progress: Box::new(|_| ()),
         }
     }
 }
 
 impl TaskEventListeners {
+    /// The listeners that ignore all events, unlike the default ones that print them
+    pub fn silent() -> Self {
+        Self {
+            on_start: Box::new(|| ()),
+            on_progress: Box::new(|_, _, _| ()),
+            on_succeed: Box::new(|| ()),
+            on_failed: Box::new(|| ()),
+            on_file: Box::new(|_, _| ()),
+            on_weighted_progress: Box::new(|_| ()),
+        }
+    }
     /// Register the start event listener, when the task start, the event will be triggered
     pub fn on_start(self, on_start: Box<dyn Fn()>) -> Self {
         Self { on_start, ..self }
@@ -97,6 +185,19 @@ impl TaskEventListeners {
     pub fn on_failed(self, on_failed: Box<dyn Fn()>) -> Self {
         Self { on_failed, ..self }
     }
+    /// Register the file event listener, when a file is downloaded or failed to download, the
+    /// event will be triggered with the path of the file and whether it succeeded
+    pub fn on_file(self, on_file: Box<dyn Fn(String, bool)>) -> Self {
+        Self { on_file, ..self }
+    }
+    /// Register the weighted progress event listener, triggered with the progress by files and
+    /// by bytes after each file of a download
+    pub fn on_weighted_progress(self, on_weighted_progress: Box<dyn Fn(WeightedProgress)>) -> Self {
+        Self {
+            on_weighted_progress,
+            ..self
+        }
+    }
     pub(crate) fn start(&self) {
         (self.on_start)();
     }
@@ -109,4 +210,254 @@ impl TaskEventListeners {
     pub(crate) fn failed(&self) {
         (self.on_failed)();
     }
+    pub(crate) fn file(&self, path: String, success: bool) {
+        (self.on_file)(path, success);
+    }
+    pub(crate) fn weighted_progress(&self, progress: WeightedProgress) {
+        (self.on_weighted_progress)(progress);
+    }
+}
+
+/// The task is cancelled by its [`CancellationToken`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The task is cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Cancel a task from another task or thread.
+///
+/// The clones share the state, so keep one to cancel and pass another to the task.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<CancelState>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the task, it stops at its next check
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Fail with [`Cancelled`] if the token is cancelled
+    pub fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Cancelled.into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Wait until the token is cancelled
+    pub async fn cancelled(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        // registered before the check, so a cancel in between still wakes it
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+
+    /// Run `future` until it is done or the token is cancelled, the future is dropped then
+    pub async fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(Cancelled.into()),
+            result = future => result,
+        }
+    }
+}
+
+/// The events of an install task, see `install_streamed`
+#[derive(Debug)]
+pub enum InstallEvent {
+    Started,
+
+    /// The same as the arguments of `TaskEventListeners::on_progress()`
+    Progress {
+        completed: usize,
+        total: usize,
+        step: usize,
+    },
+
+    /// A file is downloaded successfully
+    FileDone(PathBuf),
+
+    /// The task succeeded, this is always the last event
+    Finished,
+
+    /// The task failed, this is always the last event
+    Failed(anyhow::Error),
+}
+
+/// Run an install task that reports by `TaskEventListeners`, and receive its events as a stream.
+///
+/// The task runs while the stream is polled, and the stream ends after `Finished` or `Failed`.
+///
+/// ```
+/// use futures::StreamExt;
+/// use aml_core::core::task::{install_streamed, InstallEvent};
+/// use aml_core::utils::download::download_files;
+///
+/// async fn fn_name() {
+///     let mut events = Box::pin(install_streamed(|listeners| async move {
+///         download_files(vec![], &listeners, 16).await
+///     }));
+///     while let Some(event) = events.next().await {
+///         println!("{event:?}");
+///     }
+/// }
+/// ```
+pub fn install_streamed<F, Fut>(install: F) -> impl Stream<Item = InstallEvent>
+where
+    F: FnOnce(TaskEventListeners) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let (sender, receiver) = mpsc::unbounded();
+    let send = |sender: &UnboundedSender<InstallEvent>, event| {
+        // the receiver is only dropped with the stream, then nobody cares about the events
+        sender.unbounded_send(event).ok();
+    };
+    let (on_start, on_progress, on_file) = (sender.clone(), sender.clone(), sender.clone());
+    let listeners = TaskEventListeners {
+        on_start: Box::new(move || send(&on_start, InstallEvent::Started)),
+        on_progress: Box::new(move |completed, total, step| {
+            send(
+                &on_progress,
+                InstallEvent::Progress {
+                    completed,
+                    total,
+                    step,
+                },
+            )
+        }),
+        on_succeed: Box::new(|| ()),
+        on_failed: Box::new(|| ()),
+        on_file: Box::new(move |path, success| {
+            if success {
+                send(&on_file, InstallEvent::FileDone(PathBuf::from(path)));
+            }
+        }),
+        on_weighted_progress: Box::new(|_| ()),
+    };
+    let task = async move {
+        let event = match install(listeners).await {
+            Ok(()) => InstallEvent::Finished,
+            Err(err) => InstallEvent::Failed(err),
+        };
+        send(&sender, event);
+    };
+    // the receiver ends once the task is done and has dropped every sender
+    stream::select(receiver.map(Some), task.into_stream().map(|_| None)).filter_map(future::ready)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::download::{
+        download_files,
+        test::{serve, temp_dir},
+    };
+
+    #[tokio::test]
+    async fn test_install_streamed() {
+        let url = serve("200 OK", b"content".to_vec()).await;
+        let root = temp_dir();
+        let downloads = (0..3)
+            .map(|i| Download {
+                url: format!("{url}/{i}"),
+                file: root.join(format!("{i}.txt")),
+                sha1: None,
+                size: None,
+            })
+            .collect::<Vec<_>>();
+        let events =
+            install_streamed(
+                |listeners| async move { download_files(downloads, &listeners, 1).await },
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(events.first(), Some(InstallEvent::Started)));
+        assert!(matches!(events.last(), Some(InstallEvent::Finished)));
+        let files = events
+            .iter()
+            .filter(|event| matches!(event, InstallEvent::FileDone(_)))
+            .count();
+        assert_eq!(files, 3);
+        std::fs::remove_dir_all(root).unwrap();
+
+        let events = install_streamed(|_| async { Err(anyhow::anyhow!("broken")) })
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(events.as_slice(), [InstallEvent::Failed(_)]));
+    }
+
+    #[test]
+    fn test_weighted_progress() {
+        let download = |name: &str, size| Download {
+            url: format!("https://example.com/{name}"),
+            file: PathBuf::from(name),
+            sha1: None,
+            size,
+        };
+        let client = download("client.jar", Some(300_000_000));
+        let assets = (0..3)
+            .map(|i| download(&format!("asset-{i}"), Some(2_000)))
+            .collect::<Vec<_>>();
+        let mut downloads = vec![client.clone()];
+        downloads.extend(assets.clone());
+
+        let mut progress = WeightedProgress::new(&downloads);
+        assert_eq!(progress.total_bytes, 300_006_000);
+        progress.complete(&client);
+        assert_eq!(progress.file_percentage(), 25.0);
+        assert!(progress.byte_percentage() > 99.0);
+        for asset in &assets {
+            progress.complete(asset);
+        }
+        assert_eq!(progress.byte_percentage(), 100.0);
+
+        // the unknown sizes weigh the average
+        let mut progress = WeightedProgress::new(&[
+            download("a", Some(10)),
+            download("b", Some(30)),
+            download("c", None),
+        ]);
+        assert_eq!(progress.total_bytes, 60);
+        progress.complete(&download("c", None));
+        assert_eq!(progress.completed_bytes, 20);
+        assert_eq!(WeightedProgress::new(&[]).byte_percentage(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_token() {
+        let cancel = CancellationToken::new();
+        assert!(cancel.check().is_ok());
+        let canceller = cancel.clone();
+        tokio::spawn(async move { canceller.cancel() });
+        let err = cancel
+            .run(future::pending::<Result<()>>())
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+        assert!(cancel.check().is_err());
+    }
 }
@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Mutex, time::SystemTime};
 
 use anyhow::Result;
 use once_cell::sync::Lazy;
@@ -25,9 +25,47 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::core::folder::MinecraftLocation;
+use crate::install::DownloadSource;
+
+use crate::utils::cache::fetch_json;
+use crate::utils::download::{fetch_verified, Downloader};
+use crate::utils::gzip::{decompress_if_gzip, fetch};
 
 use super::PlatformInfo;
 
+/// How many times [`Version::from_url`] tries before giving up on a transient failure
+const FETCH_ATTEMPTS: usize = 3;
+
+/// The wait of [`Version::from_url`] before the first retry, doubled for each next one
+const FETCH_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The longest `inheritsFrom` chain [`Version::parse`] follows
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+/// How many resolved versions [`Version::parse_cached`] keeps
+const RESOLVED_CACHE_SIZE: usize = 16;
+
+struct CachedVersion {
+    versions_folder: PathBuf,
+    version: Version,
+    platform: PlatformInfo,
+    /// The modified time of every json in the inheritance chain when it was resolved
+    modified: Vec<Option<SystemTime>>,
+    resolved: ResolvedVersion,
+}
+
+/// The versions resolved by [`Version::parse_cached`], the most recently used last
+static RESOLVED_CACHE: Lazy<Mutex<Vec<CachedVersion>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+async fn modified_times(path_chain: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    let mut modified = Vec::with_capacity(path_chain.len());
+    for path in path_chain {
+        let metadata = tokio::fs::metadata(path).await;
+        modified.push(metadata.and_then(|m| m.modified()).ok());
+    }
+    modified
+}
+
 static DEFAULT_GAME_ARGS: Lazy<Vec<String>> = Lazy::new(|| {
     vec![
         "--username".to_string(),
@@ -65,8 +103,6 @@ static DEFAULT_JVM_ARGS: Lazy<Vec<String>> = Lazy::new(|| {
         // "\"-Djna.tmpdir=${natives_directory}\"".to_string(),
         // "\"-Dorg.lwjgl.system.SharedLibraryExtractPath=${natives_directory}\"".to_string(),
         // "\"-Dio.netty.native.workdir=${natives_directory}\"".to_string(),
-        "\"-Dminecraft.launcher.brand=${launcher_name}\"".to_string(),
-        "\"-Dminecraft.launcher.version=${launcher_version}\"".to_string(),
         "\"-Dfile.encoding=UTF-8\"".to_string(),
         "\"-Dsun.stdout.encoding=UTF-8\"".to_string(),
         "\"-Dsun.stderr.encoding=UTF-8\"".to_string(),
@@ -97,8 +133,12 @@ pub struct VersionInfo {
     pub url: String,
     pub time: String,
     pub release_time: String,
-    pub sha1: String,
-    pub compliance_level: u8,
+
+    /// The sha1 of the version json, only in the v2 manifest
+    pub sha1: Option<String>,
+
+    /// Only in the v2 manifest
+    pub compliance_level: Option<u8>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -107,11 +147,42 @@ pub struct VersionManifest {
     pub versions: Vec<VersionInfo>,
 }
 
+const VERSION_MANIFEST_HOST: &str = "https://piston-meta.mojang.com";
+
+fn version_manifest_url(base_url: &str) -> String {
+    format!(
+        "{}/mc/game/version_manifest_v2.json",
+        base_url.trim_end_matches('/')
+    )
+}
+
+/// The manifest without the version list, which is skipped while deserializing
+#[derive(Deserialize)]
+struct LatestOnly {
+    latest: LatestVersion,
+}
+
 impl VersionManifest {
     pub async fn new() -> Result<VersionManifest> {
-        let response =
-            reqwest::get("https://piston-meta.mojang.com/mc/game/version_manifest_v2.json").await?;
-        Ok(response.json::<VersionManifest>().await?)
+        Self::from_mirror(VERSION_MANIFEST_HOST).await
+    }
+
+    /// Fetch the manifest from `{base_url}/mc/game/version_manifest_v2.json`, for the regions
+    /// where Mojang is blocked, like `https://bmclapi2.bangbang93.com`
+    pub async fn from_mirror(base_url: &str) -> Result<VersionManifest> {
+        fetch_json(&version_manifest_url(base_url)).await
+    }
+
+    /// Get the latest release and snapshot, without building the whole version list.
+    ///
+    /// Mojang doesn't serve `latest` alone, so the manifest is still downloaded, or revalidated
+    /// if the cache is enabled with `set_cache_dir`.
+    pub async fn latest() -> Result<LatestVersion> {
+        Self::latest_from_url(&version_manifest_url(VERSION_MANIFEST_HOST)).await
+    }
+
+    async fn latest_from_url(url: &str) -> Result<LatestVersion> {
+        Ok(fetch_json::<LatestOnly>(url).await?.latest)
     }
 }
 
@@ -249,6 +320,61 @@ pub struct JavaVersion {
     pub major_version: i32,
 }
 
+/// Guess the java version required by a Minecraft version, for the version jsons without
+/// `javaVersion`
+pub fn infer_java_version(minecraft_version: &str) -> JavaVersion {
+    let (component, major_version) = match MinecraftVersion::from_str(minecraft_version) {
+        Ok(MinecraftVersion::Release(1, minor, patch)) => match (minor, patch.unwrap_or(0)) {
+            (21.., _) | (20, 5..) => ("java-runtime-delta", 21),
+            (18.., _) => ("java-runtime-gamma", 17),
+            (17, _) => ("java-runtime-alpha", 16),
+            _ => ("jre-legacy", 8),
+        },
+        Ok(MinecraftVersion::Snapshot(year, week, _)) => match (year, week) {
+            (25.., _) | (24, 14..) => ("java-runtime-delta", 21),
+            (22.., _) | (21, 44..) => ("java-runtime-gamma", 17),
+            (21, 19..) => ("java-runtime-alpha", 16),
+            _ => ("jre-legacy", 8),
+        },
+        _ => ("jre-legacy", 8),
+    };
+    JavaVersion {
+        component: component.to_string(),
+        major_version,
+    }
+}
+
+/// The java major version an installed version needs, without resolving the version.
+///
+/// The `javaVersion` of the nearest json in the `inheritsFrom` chain is used. If no json has one,
+/// it's inferred from the Minecraft version, see [`infer_java_version`].
+pub async fn required_java(version_id: &str, minecraft: &MinecraftLocation) -> Result<u32> {
+    let mut id = version_id.to_string();
+    let mut client_version = None;
+    for _ in 0..=MAX_INHERITANCE_DEPTH {
+        let path = minecraft.get_version_json(&id);
+        let version_json = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|err| anyhow::anyhow!("Cannot read version {id}: {err}"))?;
+        let version: Version = serde_json::from_str(&version_json)
+            .map_err(|err| anyhow::anyhow!("Malformed version json {}: {err}", path.display()))?;
+        if let Some(java_version) = version.java_version {
+            return Ok(u32::try_from(java_version.major_version)?);
+        }
+        client_version = client_version.or(version.client_version);
+        match version.inherits_from {
+            Some(parent) => id = parent,
+            None => {
+                let minecraft_version = client_version.unwrap_or(version.id);
+                return Ok(infer_java_version(&minecraft_version).major_version as u32);
+            }
+        }
+    }
+    Err(anyhow::anyhow!(
+        "The inheritsFrom chain of {version_id} is deeper than {MAX_INHERITANCE_DEPTH}"
+    ))
+}
+
 /// Minecraft Version
 ///
 /// It used to compare the version of the game
@@ -305,6 +431,9 @@ pub struct ResolvedVersion {
     pub asset_index: Option<AssetIndex>,
 
     /// The asset index id of this version. Should be something like `1.14`, `1.12`.
+    ///
+    /// It's always the id of `asset_index` when there is one, so the install and the launch use
+    /// the same index.
     pub assets: String,
     pub downloads: Option<HashMap<String, Download>>,
     pub libraries: Vec<ResolvedLibrary>,
@@ -328,6 +457,90 @@ pub struct ResolvedVersion {
     /// It's the chain of inherits json path. The root json will be the last element of the array.
     /// The first element is the user provided version.
     pub path_chain: Vec<PathBuf>,
+
+    /// The Minecraft version of the root vanilla version in the inheritance chain, like `1.19.4`
+    /// for a fabric version `1.19.4-fabric-0.14.21`.
+    pub minecraft_version: String,
+
+    /// The compliance level of the version, `1` means the version supports the player safety
+    /// features and the log4j fixes.
+    pub compliance_level: Option<i32>,
+}
+
+impl ResolvedVersion {
+    /// Whether the version is safe from the log4j vulnerability (CVE-2021-44228).
+    ///
+    /// A version is considered safe if Mojang marks it compliant, or it has a logging config,
+    /// which is the patched one served by Mojang.
+    pub fn is_log4j_safe(&self) -> bool {
+        let has_logging_config = match &self.logging {
+            Some(logging) => logging.contains_key("client"),
+            None => false,
+        };
+        self.compliance_level.unwrap_or(0) >= 1 || has_logging_config
+    }
+
+    /// The paths of the non-native libraries and the client jar, in the classpath order
+    pub fn library_paths(&self, minecraft: &MinecraftLocation) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(self.libraries.len() + 1);
+        for library in self.libraries.iter().filter(|l| !l.is_native_library) {
+            let path = minecraft.get_library_by_path(&library.download_info.path);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths.push(minecraft.get_version_jar(&self.id, None));
+        paths
+    }
+
+    /// Download a named entry of `downloads`, like `client` or `client_mappings`, into the
+    /// version folder.
+    ///
+    /// The file is verified against the sha1 in the version json, and is not downloaded again if
+    /// it's already there. A corrupt download is removed with a
+    /// [`ChecksumMismatch`](crate::utils::download::ChecksumMismatch). Return None if the version
+    /// has no such download.
+    pub async fn download_artifact(
+        &self,
+        name: &str,
+        minecraft: &MinecraftLocation,
+        source: &DownloadSource,
+        downloader: &dyn Downloader,
+    ) -> Result<Option<PathBuf>> {
+        let artifact = match self.downloads.as_ref().and_then(|d| d.get(name)) {
+            Some(artifact) => artifact,
+            None => return Ok(None),
+        };
+        let extension = artifact
+            .url
+            .rsplit('/')
+            .next()
+            .and_then(|file_name| file_name.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+            .unwrap_or("jar");
+        let file = match name {
+            "client" => minecraft.get_version_jar(&self.id, None),
+            _ => minecraft
+                .get_version_root(&self.id)
+                .join(format!("{}-{name}.{extension}", self.id)),
+        };
+        let url = match source.version_base() {
+            Some(base) => format!(
+                "{}/{}/{name}",
+                base.trim_end_matches('/'),
+                self.minecraft_version
+            ),
+            None => artifact.url.clone(),
+        };
+        let download = super::Download {
+            url,
+            file: file.clone(),
+            sha1: Some(artifact.sha1.clone()),
+            size: Some(artifact.size),
+        };
+        fetch_verified(downloader, &download, true).await?;
+        Ok(Some(file))
+    }
 }
 
 /// The raw json format provided by Minecraft.
@@ -344,10 +557,7 @@ pub struct ResolvedVersion {
 /// use aml_core::core::version::Version;
 ///
 /// async fn fn_name() {
-///     let version = reqwest::get("https://piston-meta.mojang.com/v1/packages/715ccf3330885e75b205124f09f8712542cbe7e0/1.20.1.json")
-///         .await
-///         .unwrap()
-///         .json::<Version>()
+///     let version = Version::from_url("https://piston-meta.mojang.com/v1/packages/715ccf3330885e75b205124f09f8712542cbe7e0/1.20.1.json")
 ///         .await
 ///         .unwrap();
 ///     println!("{:#?}", version);
@@ -386,27 +596,109 @@ pub struct ResolvedVersion {
 /// }
 /// ```
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
-#[serde(rename_all = "camelCase")]
+#[serde(remote = "Self", rename_all = "camelCase")]
+#[non_exhaustive]
 pub struct Version {
     pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub r#type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub release_time: Option<String>,
+    #[serde(default)]
     pub inherits_from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub minimum_launcher_version: Option<i32>,
+    #[serde(default)]
     pub minecraft_arguments: Option<String>,
+    #[serde(default)]
     pub arguments: Option<Arguments>,
+    #[serde(default)]
     pub main_class: Option<String>,
+    #[serde(default)]
     pub libraries: Option<Vec<Value>>,
+    #[serde(default)]
     pub jar: Option<String>,
+    #[serde(default)]
     pub asset_index: Option<AssetIndex>,
+    #[serde(default)]
     pub assets: Option<String>,
+    #[serde(default)]
     pub downloads: Option<HashMap<String, Download>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub server: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub logging: Option<HashMap<String, Logging>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub java_version: Option<JavaVersion>,
+    /// The vanilla version of a version json in a renamed folder, written by some launchers
+    #[serde(
+        default,
+        alias = "client_version",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub client_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compliance_level: Option<i32>,
+
+    /// The fields this crate doesn't know, and the non-essential ones with an unexpected type,
+    /// kept to write them back
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// Whether a json value has the type of `T`
+fn is_a<T: serde::de::DeserializeOwned>(value: &Value) -> bool {
+    T::deserialize(value).is_ok()
+}
+
+/// Tell whether a json value has the type of a field
+type FieldCheck = fn(&Value) -> bool;
+
+/// The fields a version can launch without, which some third-party jsons write with another
+/// type. They are read as None and kept in `extra` instead of failing the whole version.
+const LENIENT_FIELDS: &[(&str, FieldCheck)] = &[
+    ("time", is_a::<String>),
+    ("type", is_a::<String>),
+    ("releaseTime", is_a::<String>),
+    ("minimumLauncherVersion", is_a::<i32>),
+    ("client", is_a::<String>),
+    ("server", is_a::<String>),
+    ("logging", is_a::<HashMap<String, Logging>>),
+    ("javaVersion", is_a::<JavaVersion>),
+    ("clientVersion", is_a::<String>),
+    ("client_version", is_a::<String>),
+    ("complianceLevel", is_a::<i32>),
+];
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = serde_json::Map::deserialize(deserializer)?;
+        let mut mistyped = Vec::new();
+        for (key, is_valid) in LENIENT_FIELDS {
+            if let Some(value) = fields.get(*key) {
+                if !value.is_null() && !is_valid(value) {
+                    mistyped.extend(fields.remove_entry(*key));
+                }
+            }
+        }
+        let mut version =
+            Version::deserialize(Value::Object(fields)).map_err(serde::de::Error::custom)?;
+        for (key, value) in mistyped {
+            log::warn!("ignore the field {key} of version {}: {value}", version.id);
+            version.extra.insert(key, value);
+        }
+        Ok(version)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Version::serialize(self, serializer)
+    }
 }
 
 impl FromStr for Version {
@@ -417,11 +709,94 @@ impl FromStr for Version {
 }
 
 impl Version {
+    /// A version json with only the id, to fill the other fields of
+    pub fn new(id: impl Into<String>) -> Version {
+        Version {
+            id: id.into(),
+            time: None,
+            r#type: None,
+            release_time: None,
+            inherits_from: None,
+            minimum_launcher_version: None,
+            minecraft_arguments: None,
+            arguments: None,
+            main_class: None,
+            libraries: None,
+            jar: None,
+            asset_index: None,
+            assets: None,
+            downloads: None,
+            client: None,
+            server: None,
+            logging: None,
+            java_version: None,
+            client_version: None,
+            compliance_level: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
     pub fn from_value(raw: Value) -> Result<Version, serde_json::Error> {
         serde_json::from_value(raw)
     }
 
-    pub fn from_versions_folder(
+    /// Write the version json back, without the absent fields and with the unknown ones
+    pub fn to_version_json(&self) -> Result<String> {
+        let mut json = serde_json::to_value(self)?;
+        if let Some(fields) = json.as_object_mut() {
+            fields.retain(|_, value| !value.is_null());
+        }
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Fetch a version json with the shared http client.
+    ///
+    /// Connection errors and `5xx` responses are retried with an increasing delay, other failures
+    /// are returned at once.
+    pub async fn from_url(url: &str) -> Result<Version> {
+        let mut attempt = 1;
+        let mut delay = FETCH_RETRY_DELAY;
+        let body = loop {
+            let err = match fetch(url).await {
+                Ok(body) => break body,
+                Err(err) => err,
+            };
+            let transient = match err.downcast_ref::<reqwest::Error>() {
+                Some(err) => err.status().is_none_or(|status| status.is_server_error()),
+                None => false,
+            };
+            if !transient || attempt >= FETCH_ATTEMPTS {
+                return Err(anyhow::anyhow!("Failed to fetch version json {url}: {err}"));
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        };
+        serde_json::from_slice(&body)
+            .map_err(|err| anyhow::anyhow!("Malformed version json {url}: {err}"))
+    }
+
+    /// Like [`Version::from_url`], but fetch the version json with the downloader
+    pub async fn from_url_with(url: &str, downloader: &dyn Downloader) -> Result<Version> {
+        let file = std::env::temp_dir().join(format!("cvl-{}.json", uuid::Uuid::new_v4()));
+        let download = super::Download {
+            url: url.to_string(),
+            file: file.clone(),
+            sha1: None,
+            size: None,
+        };
+        let fetched = downloader.fetch(&download).await;
+        let body = match fetched {
+            Ok(()) => tokio::fs::read(&file).await,
+            Err(err) => return Err(anyhow::anyhow!("Failed to fetch version json {url}: {err}")),
+        };
+        tokio::fs::remove_file(&file).await.ok();
+        serde_json::from_slice(&decompress_if_gzip(body?)?)
+            .map_err(|err| anyhow::anyhow!("Malformed version json {url}: {err}"))
+    }
+
+    /// Read the version json of an installed version from the versions folder
+    pub async fn from_versions_folder(
         minecraft: MinecraftLocation,
         version_name: &str,
     ) -> Result<Version, std::io::Error> {
@@ -430,57 +805,147 @@ impl Version {
             .join(version_name)
             .join(format!("{}.json", version_name));
 
-        let raw = read_to_string(path)?;
-        let version: Version = serde_json::from_str((&raw).as_ref())?;
+        let raw = tokio::fs::read_to_string(path).await?;
+        let version: Version = serde_json::from_str(&raw)?;
         Ok(version)
     }
 
+    /// Like [`Version::parse`], but reuse the result of an earlier call for the same version
+    /// if none of the jsons it inherits from has been modified since.
+    ///
+    /// This saves reading the inheritance chain again when the same version is launched repeatedly.
+    pub async fn parse_cached(
+        &self,
+        minecraft: &MinecraftLocation,
+        platform: &PlatformInfo,
+    ) -> Result<ResolvedVersion> {
+        let is_same = |cached: &CachedVersion| {
+            cached.versions_folder == minecraft.versions
+                && &cached.version == self
+                && &cached.platform == platform
+        };
+        // the lock is only held to look up and update the cache, never across the file reads
+        let candidate = RESOLVED_CACHE
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|cached| is_same(cached))
+            .map(|cached| (cached.modified.clone(), cached.resolved.clone()));
+        if let Some((modified, resolved)) = candidate {
+            if modified_times(&resolved.path_chain).await == modified {
+                let mut cache = RESOLVED_CACHE.lock().unwrap();
+                if let Some(index) = cache.iter().position(is_same) {
+                    let cached = cache.remove(index);
+                    cache.push(cached);
+                }
+                return Ok(resolved);
+            }
+        }
+        let resolved = self.parse(minecraft, platform).await?;
+        let modified = modified_times(&resolved.path_chain).await;
+        let mut cache = RESOLVED_CACHE.lock().unwrap();
+        cache.retain(|cached| {
+            cached.versions_folder != minecraft.versions || cached.version.id != self.id
+        });
+        if cache.len() >= RESOLVED_CACHE_SIZE {
+            cache.remove(0);
+        }
+        cache.push(CachedVersion {
+            versions_folder: minecraft.versions.clone(),
+            version: self.clone(),
+            platform: platform.clone(),
+            modified,
+            resolved: resolved.clone(),
+        });
+        Ok(resolved)
+    }
+
     /// parse a Minecraft version json
     pub async fn parse(
         &self,
         minecraft: &MinecraftLocation,
         platform: &PlatformInfo,
+    ) -> Result<ResolvedVersion> {
+        self.parse_for_side(minecraft, platform, Side::Client).await
+    }
+
+    /// Like [`Version::parse`], but resolve the libraries and arguments for the given side
+    pub async fn parse_for_side(
+        &self,
+        minecraft: &MinecraftLocation,
+        platform: &PlatformInfo,
+        side: Side,
     ) -> Result<ResolvedVersion> {
         let mut inherits_from = self.inherits_from.clone();
         let versions_folder = &minecraft.versions;
         let mut versions = Vec::new();
         let mut inheritances = Vec::new();
         let mut path_chain = Vec::new();
+        let mut visited = vec![self.id.clone()];
         versions.push(self.clone());
         while let Some(inherits_from_unwrap) = inherits_from {
+            if visited.contains(&inherits_from_unwrap) {
+                visited.push(inherits_from_unwrap);
+                return Err(anyhow::anyhow!(
+                    "Cyclic inheritsFrom chain: {}",
+                    visited.join(" -> ")
+                ));
+            }
+            if inheritances.len() >= MAX_INHERITANCE_DEPTH {
+                return Err(anyhow::anyhow!(
+                    "The inheritsFrom chain of {} is deeper than {MAX_INHERITANCE_DEPTH}",
+                    self.id
+                ));
+            }
+            visited.push(inherits_from_unwrap.clone());
             inheritances.push(inherits_from_unwrap.clone());
 
             let path = versions_folder
                 .join(inherits_from_unwrap.clone())
                 .join(format!("{}.json", inherits_from_unwrap.clone()));
             path_chain.push(path.clone());
-            let version_json = read_to_string(path)?;
-            let version_json: Version = serde_json::from_str((&version_json).as_ref())?;
+            let version_json = tokio::fs::read_to_string(&path).await.map_err(|err| {
+                anyhow::anyhow!("Cannot read parent version {inherits_from_unwrap}: {err}")
+            })?;
+            let version_json: Version = serde_json::from_str(&version_json).map_err(|err| {
+                anyhow::anyhow!("Malformed version json {}: {err}", path.display())
+            })?;
 
             versions.push(version_json.clone());
             inherits_from = version_json.inherits_from;
         }
 
+        let root_id = match versions.last() {
+            Some(root) => root.id.clone(),
+            None => self.id.clone(),
+        };
+        let mut client_version = None;
+
         let mut assets = "".to_string();
         let mut minimum_launcher_version = 0;
 
-        let game_args = DEFAULT_GAME_ARGS.clone();
-        let jvm_args = DEFAULT_JVM_ARGS.clone();
+        let (mut game_args, jvm_args) = resolve_arguments(side);
         let mut release_time = "".to_string();
         let mut time = "".to_string();
         let mut version_type = "".to_string();
         let mut logging = HashMap::new();
         let mut main_class = "".to_string();
         let mut asset_index = None;
-        let mut java_version = JavaVersion {
-            component: "jre-legacy".to_string(),
-            major_version: 8,
-        };
+        let mut java_version = None;
         let mut libraries_raw = Vec::new();
         let mut downloads = HashMap::new();
+        let mut compliance_level = None;
+        let mut feature_jvm_args = Vec::new();
+        let mut tweak_classes: Vec<String> = Vec::new();
 
         while versions.len() != 0 {
             let version = versions.pop().unwrap();
+            // the child keeps its own order, the parents only add the tweakers it doesn't list
+            let own = version_tweak_classes(&version);
+            let inherited = tweak_classes
+                .into_iter()
+                .filter(|tweak_class| !own.contains(tweak_class));
+            tweak_classes = own.iter().cloned().chain(inherited).collect();
             minimum_launcher_version = std::cmp::max(
                 version.minimum_launcher_version.unwrap_or(0),
                 minimum_launcher_version,
@@ -504,8 +969,18 @@ impl Version {
                 Some(asset_index) => Some(asset_index),
                 None => asset_index,
             };
-            java_version = version.java_version.unwrap_or(java_version);
+            java_version = version.java_version.or(java_version);
+            client_version = version.client_version.or(client_version);
+            compliance_level = version.compliance_level.or(compliance_level);
 
+            let jvm = version.arguments.and_then(|arguments| arguments.jvm);
+            if let Some(jvm) = jvm.filter(|_| side == Side::Client) {
+                feature_jvm_args.extend(jvm.into_iter().filter(|arg| {
+                    arg["rules"]
+                        .as_array()
+                        .is_some_and(|rules| rules.iter().any(|rule| rule["features"].is_object()))
+                }));
+            }
             if let Some(libraries) = version.libraries {
                 libraries_raw.splice(0..0, libraries);
             }
@@ -514,6 +989,15 @@ impl Version {
                 None => (),
             };
         }
+        let minecraft_version = client_version.unwrap_or(root_id);
+        // launchwrapper only runs on the client, the server takes no game arguments
+        if side == Side::Client {
+            game_args.extend(
+                tweak_classes
+                    .into_iter()
+                    .flat_map(|tweak_class| ["--tweakClass".to_string(), tweak_class]),
+            );
+        }
         let main_class_is_empty = main_class.is_empty();
         let assets_index_is_empty = asset_index
             == Some(AssetIndex {
@@ -526,28 +1010,32 @@ impl Version {
         if main_class_is_empty || assets_index_is_empty || downloads_is_empty {
             return Err(anyhow::anyhow!("Bad Version JSON"));
         }
+        // an inheriting version may override only one of them, the index that is installed wins
+        if let Some(asset_index) = asset_index.as_ref().filter(|index| !index.id.is_empty()) {
+            assets = asset_index.id.clone();
+        }
         Ok(ResolvedVersion {
             id: self.id.clone(),
             arguments: Some(ResolvedArguments {
                 game: game_args,
                 jvm: jvm_args,
+                feature_jvm: feature_jvm_args,
             }),
             main_class,
             asset_index,
             assets,
             downloads: Some(downloads),
-            libraries: resolve_libraries(libraries_raw, platform).await,
+            libraries: resolve_libraries(libraries_raw, platform, side).await,
             minimum_launcher_version,
             release_time,
             time,
             version_type,
             logging: Some(logging),
-            java_version: self.java_version.clone().unwrap_or(JavaVersion {
-                component: "jre-legacy".to_string(),
-                major_version: 8,
-            }),
+            java_version: java_version.unwrap_or(infer_java_version(&minecraft_version)),
             inheritances,
             path_chain,
+            minecraft_version,
+            compliance_level,
         })
     }
 }
@@ -556,6 +1044,10 @@ impl Version {
 pub struct ResolvedArguments {
     pub game: Vec<String>,
     pub jvm: Vec<String>,
+
+    /// The jvm arguments of the version json gated by `features` rules, like
+    /// `{ "rules": [...], "value": [...] }`. They are picked at launch by the enabled features.
+    pub feature_jvm: Vec<Value>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -564,7 +1056,53 @@ pub struct ResolvedLibrary {
     pub is_native_library: bool,
 }
 
-async fn resolve_libraries(libraries: Vec<Value>, platform: &PlatformInfo) -> Vec<ResolvedLibrary> {
+/// The side a version is resolved for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Side {
+    #[default]
+    Client,
+    Server,
+}
+
+/// The `--tweakClass` values of launchwrapper in the `minecraftArguments` and the game
+/// `arguments` of a version json, in order
+fn version_tweak_classes(version: &Version) -> Vec<String> {
+    let legacy = version
+        .minecraft_arguments
+        .iter()
+        .flat_map(|arguments| arguments.split_whitespace().map(str::to_string));
+    let game = version
+        .arguments
+        .iter()
+        .flat_map(|arguments| arguments.game.iter().flatten())
+        .filter_map(|arg| arg.as_str().map(str::to_string));
+    let args = legacy.chain(game).collect::<Vec<_>>();
+    args.windows(2)
+        .filter(|pair| pair[0] == "--tweakClass")
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+/// The game and jvm argument templates. The server takes no game arguments and has no natives.
+fn resolve_arguments(side: Side) -> (Vec<String>, Vec<String>) {
+    match side {
+        Side::Client => (DEFAULT_GAME_ARGS.clone(), DEFAULT_JVM_ARGS.clone()),
+        Side::Server => (
+            vec![],
+            DEFAULT_JVM_ARGS
+                .iter()
+                .filter(|arg| !arg.contains("${natives_directory}"))
+                .cloned()
+                .collect(),
+        ),
+    }
+}
+
+pub(crate) async fn resolve_libraries(
+    libraries: Vec<Value>,
+    platform: &PlatformInfo,
+    side: Side,
+) -> Vec<ResolvedLibrary> {
     let mut result = Vec::new();
     for library in libraries {
         let rules = library["rules"].as_array();
@@ -574,9 +1112,20 @@ async fn resolve_libraries(libraries: Vec<Value>, platform: &PlatformInfo) -> Ve
                 continue;
             }
         }
+        // the libraries of legacy forge marked for the other side only, included if absent
+        let required = match side {
+            Side::Client => &library["clientreq"],
+            Side::Server => &library["serverreq"],
+        };
+        if required.as_bool() == Some(false) {
+            continue;
+        }
         // resolve native lib
         let classifiers = library["downloads"]["classifiers"].as_object();
         let natives = library["natives"].as_object();
+        if side == Side::Server && natives.is_some() {
+            continue;
+        }
         if classifiers.is_some() && natives.is_some() {
             let classifiers = classifiers.unwrap();
             let natives = natives.unwrap();
@@ -641,7 +1190,7 @@ async fn resolve_libraries(libraries: Vec<Value>, platform: &PlatformInfo) -> Ve
             download_info: LibraryDownload {
                 sha1: None,
                 size: None,
-                url: format!("{url}{path}"),
+                url: join_url(url, &path),
                 path,
             },
             is_native_library: false,
@@ -650,8 +1199,30 @@ async fn resolve_libraries(libraries: Vec<Value>, platform: &PlatformInfo) -> Ve
     result
 }
 
-/// Check if all the rules in Rule[] are acceptable in certain OS platform and features.
+/// Join a maven base url and a path with exactly one slash, the `url` of some libraries has no
+/// trailing slash
+pub(crate) fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Check if all the rules in Rule[] are acceptable in certain OS platform.
 fn check_allowed(rules: Vec<Value>, platform: &PlatformInfo) -> bool {
+    check_allowed_with_features(rules, platform, &HashMap::new())
+}
+
+/// Check if all the rules in Rule[] are acceptable in certain OS platform and features.
+///
+/// A rule with `features` only applies if every listed feature has the same value in
+/// `features`, the missing ones are `false`.
+pub(crate) fn check_allowed_with_features(
+    rules: Vec<Value>,
+    platform: &PlatformInfo,
+    features: &HashMap<String, Value>,
+) -> bool {
     // by default it's allowed
     if rules.is_empty() {
         return true;
@@ -660,6 +1231,14 @@ fn check_allowed(rules: Vec<Value>, platform: &PlatformInfo) -> bool {
     let mut allow = false;
     for rule in rules {
         let action = rule["action"].as_str().unwrap() == "allow";
+        if let Some(required) = rule["features"].as_object() {
+            let enabled = required
+                .iter()
+                .all(|(name, value)| features.get(name).unwrap_or(&Value::Bool(false)) == value);
+            if !enabled {
+                continue;
+            }
+        }
         let os = rule["os"].clone();
         if !os.is_object() {
             allow = action;
@@ -728,7 +1307,10 @@ impl LibraryInfo {
             Some(classifier) => format!("{base}-{classifier}"),
             None => "".to_string(),
         };
-        let path = format!("{base}.{type}");
+        let path = match classifier.as_str() {
+            "" => format!("{base}.{type}"),
+            classifier => format!("{classifier}.{type}"),
+        };
         Self {
             group_id,
             artifact_id,
@@ -741,3 +1323,1000 @@ impl LibraryInfo {
         }
     }
 }
+
+/// A mod loader, of an installed version or of a mod
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Loader {
+    Forge,
+    NeoForge,
+    Fabric,
+    Quilt,
+    Optifine,
+
+    /// A mod whose loader can't be told from its metadata files
+    Unknown,
+}
+
+/// The information inferred from an installed version id
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct InstalledIdInfo {
+    pub minecraft_version: Option<String>,
+
+    /// None if the version is vanilla or the loader is unknown
+    pub loader: Option<Loader>,
+    pub loader_version: Option<String>,
+}
+
+static INSTALLED_ID_PATTERNS: Lazy<Vec<(Regex, Loader, bool)>> = Lazy::new(|| {
+    // (pattern, loader, whether the minecraft version is the first capture)
+    vec![
+        (r"^fabric-loader-([^-]+)-(.+)$", Loader::Fabric, false),
+        (r"^quilt-loader-([^-]+)-(.+)$", Loader::Quilt, false),
+        (r"^(.+?)-fabric-?(.+)$", Loader::Fabric, true),
+        (r"^(.+?)-loader(.+)$", Loader::Fabric, true),
+        (r"^(.+?)-quilt-?(.+)$", Loader::Quilt, true),
+        (r"^(.+?)-[Ff]orge-?(.+)$", Loader::Forge, true),
+        (r"^(.+?)-OptiFine_(.+)$", Loader::Optifine, true),
+    ]
+    .into_iter()
+    .map(|(pattern, loader, minecraft_first)| {
+        (Regex::new(pattern).unwrap(), loader, minecraft_first)
+    })
+    .collect()
+});
+
+/// Detect the Minecraft version and the loader version from an installed version id, like
+/// `1.19.4-fabric-0.14.21`, `quilt-loader-0.19.1-1.19.3` or `1.12.2-forge-14.23.5.2860`.
+///
+/// If the id is opaque, all the fields are None, use `InstalledIdInfo::from_version` instead.
+pub fn parse_installed_id(id: &str) -> InstalledIdInfo {
+    for (regex, loader, minecraft_first) in INSTALLED_ID_PATTERNS.iter() {
+        let captures = match regex.captures(id) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let (minecraft_version, loader_version) = if *minecraft_first {
+            (&captures[1], &captures[2])
+        } else {
+            (&captures[2], &captures[1])
+        };
+        // legacy forge ids look like `1.7.10-Forge10.13.4.1614-1.7.10` or
+        // `1.12.2-forge1.12.2-14.23.2.2611`
+        let loader_version = loader_version
+            .strip_prefix(&format!("{minecraft_version}-"))
+            .unwrap_or(loader_version);
+        let loader_version = loader_version
+            .strip_suffix(&format!("-{minecraft_version}"))
+            .unwrap_or(loader_version);
+        return InstalledIdInfo {
+            minecraft_version: Some(minecraft_version.to_string()),
+            loader: Some(*loader),
+            loader_version: Some(loader_version.to_string()),
+        };
+    }
+    InstalledIdInfo {
+        minecraft_version: match MinecraftVersion::from_str(id) {
+            Ok(MinecraftVersion::Unknown(_)) | Err(_) => None,
+            Ok(_) => Some(id.to_string()),
+        },
+        loader: None,
+        loader_version: None,
+    }
+}
+
+impl InstalledIdInfo {
+    /// Detect the loader from the libraries of a version json, for the ids which can't be
+    /// parsed by `parse_installed_id`
+    pub fn from_version(version: &Version) -> Self {
+        let mut info = InstalledIdInfo {
+            minecraft_version: version
+                .inherits_from
+                .clone()
+                .or(version.client_version.clone()),
+            loader: None,
+            loader_version: None,
+        };
+        let libraries = version.libraries.clone().unwrap_or_default();
+        for library in libraries {
+            let name = match library["name"].as_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let split = name.split(':').collect::<Vec<&str>>();
+            if split.len() < 3 {
+                continue;
+            }
+            let loader = match (split[0], split[1]) {
+                ("net.fabricmc", "fabric-loader") => Loader::Fabric,
+                ("org.quiltmc", "quilt-loader") => Loader::Quilt,
+                ("net.minecraftforge", "forge") | ("net.minecraftforge", "fmlloader") => {
+                    Loader::Forge
+                }
+                ("optifine", "OptiFine") => Loader::Optifine,
+                _ => continue,
+            };
+            // forge library versions look like `1.20.1-47.1.0`
+            let loader_version = match &info.minecraft_version {
+                Some(minecraft_version) => split[2]
+                    .strip_prefix(&format!("{minecraft_version}-"))
+                    .unwrap_or(split[2]),
+                None => split[2],
+            };
+            info.loader = Some(loader);
+            info.loader_version = Some(loader_version.to_string());
+            break;
+        }
+        info
+    }
+
+    /// Parse the version id, and read the version json from the versions folder if the id is
+    /// opaque
+    pub async fn from_versions_folder(
+        minecraft: MinecraftLocation,
+        version_id: &str,
+    ) -> Result<Self> {
+        let info = parse_installed_id(version_id);
+        if info.minecraft_version.is_some() {
+            return Ok(info);
+        }
+        Ok(Self::from_version(
+            &Version::from_versions_folder(minecraft, version_id).await?,
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::utils::download::HttpDownloader;
+
+    /// A vanilla version json with a client jar and the asset index `assets`. The `extra` fields
+    /// are added to it, or replace its own.
+    pub(crate) fn vanilla_json(id: &str, assets: &str, extra: Value) -> Value {
+        let mut json = serde_json::json!({
+            "id": id,
+            "type": "release",
+            "mainClass": "net.minecraft.client.main.Main",
+            "assets": assets,
+            "assetIndex": {
+                "id": assets,
+                "size": 1,
+                "url": format!("https://example.com/{assets}.json"),
+                "totalSize": 1
+            },
+            "downloads": {
+                "client": { "sha1": "0", "size": 1, "url": "https://example.com/client.jar" }
+            }
+        });
+        if let (Some(json), Value::Object(extra)) = (json.as_object_mut(), extra) {
+            json.extend(extra);
+        }
+        json
+    }
+
+    pub(crate) fn write_version(minecraft: &MinecraftLocation, id: &str, json: &str) {
+        let folder = minecraft.versions.join(id);
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join(format!("{id}.json")), json).unwrap();
+    }
+
+    #[test]
+    fn test_default_jvm_args() {
+        let flag = Regex::new(r"^-[A-Za-z]").unwrap();
+        let mut args = DEFAULT_JVM_ARGS.iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.trim_matches('"');
+            assert!(flag.is_match(arg), "malformed jvm flag: {arg}");
+            assert!(!arg.starts_with("-Xss:") && !arg.starts_with("-Xmx:"));
+            if arg == "-cp" {
+                assert_eq!(args.next().unwrap(), "${classpath}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_is_log4j_safe() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new("test");
+        let version = Version::from_value(vanilla_json(
+            "1.12.2",
+            "1.12",
+            serde_json::json!({ "complianceLevel": 0 }),
+        ))
+        .unwrap();
+        let resolved = version.parse(&minecraft, &platform).await.unwrap();
+        assert_eq!(resolved.compliance_level, Some(0));
+        assert!(!resolved.is_log4j_safe());
+
+        let mut version = version;
+        version.compliance_level = Some(1);
+        let resolved = version.parse(&minecraft, &platform).await.unwrap();
+        assert!(resolved.is_log4j_safe());
+    }
+
+    #[tokio::test]
+    async fn test_download_artifact() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let url = crate::utils::download::test::serve("200 OK", b"client".to_vec()).await;
+        let version = Version::from_value(vanilla_json(
+            "1.12.2",
+            "1.12",
+            serde_json::json!({
+                "downloads": { "client": {
+                    "sha1": "d2a04d71301a8915217dd5faf81d12cffd6cd958",
+                    "size": 6,
+                    "url": format!("{url}/client.jar")
+                } }
+            }),
+        ))
+        .unwrap();
+        let resolved = version.parse(&minecraft, &platform).await.unwrap();
+
+        let file = resolved
+            .download_artifact(
+                "client",
+                &minecraft,
+                &DownloadSource::Official,
+                &HttpDownloader,
+            )
+            .await
+            .unwrap();
+        assert_eq!(file, Some(minecraft.get_version_jar("1.12.2", None)));
+        assert_eq!(std::fs::read(file.as_ref().unwrap()).unwrap(), b"client");
+
+        // a file of the right size but the wrong sha1 is downloaded again
+        std::fs::write(file.as_ref().unwrap(), b"cl1ent").unwrap();
+        resolved
+            .download_artifact(
+                "client",
+                &minecraft,
+                &DownloadSource::Official,
+                &HttpDownloader,
+            )
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(file.as_ref().unwrap()).unwrap(), b"client");
+
+        // and a download of the right size but the wrong sha1 is removed
+        let corrupt_url = crate::utils::download::test::serve("200 OK", b"cl1ent".to_vec()).await;
+        let mut corrupt = resolved.clone();
+        corrupt
+            .downloads
+            .as_mut()
+            .unwrap()
+            .get_mut("client")
+            .unwrap()
+            .url = format!("{corrupt_url}/client.jar");
+        std::fs::remove_file(file.as_ref().unwrap()).unwrap();
+        let err = corrupt
+            .download_artifact(
+                "client",
+                &minecraft,
+                &DownloadSource::Official,
+                &HttpDownloader,
+            )
+            .await
+            .unwrap_err();
+        assert!(err
+            .downcast_ref::<crate::utils::download::ChecksumMismatch>()
+            .is_some());
+        assert!(!file.unwrap().exists());
+
+        let file = resolved
+            .download_artifact(
+                "windows_server",
+                &minecraft,
+                &DownloadSource::Official,
+                &HttpDownloader,
+            )
+            .await
+            .unwrap();
+        assert_eq!(file, None);
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_minecraft_version() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let vanilla = vanilla_json("1.19.4", "3", Value::Null);
+        write_version(&minecraft, "1.19.4", &vanilla.to_string());
+
+        let fabric = Version::from_str(
+            r#"{
+                "id": "1.19.4-fabric-0.14.21",
+                "inheritsFrom": "1.19.4",
+                "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient"
+            }"#,
+        )
+        .unwrap();
+        let resolved = fabric.parse(&minecraft, &platform).await.unwrap();
+        assert_eq!(resolved.id, "1.19.4-fabric-0.14.21");
+        assert_eq!(resolved.minecraft_version, "1.19.4");
+
+        let resolved = Version::from_value(vanilla.clone())
+            .unwrap()
+            .parse(&minecraft, &platform)
+            .await
+            .unwrap();
+        assert_eq!(resolved.minecraft_version, "1.19.4");
+
+        // a vanilla version installed in a renamed folder
+        let renamed = vanilla_json(
+            "Survival",
+            "3",
+            serde_json::json!({ "client_version": "1.19.4" }),
+        );
+        write_version(&minecraft, "Survival", &renamed.to_string());
+        let forge = Version::from_str(
+            r#"{
+                "id": "Survival-forge",
+                "inheritsFrom": "Survival",
+                "mainClass": "cpw.mods.modlauncher.Launcher"
+            }"#,
+        )
+        .unwrap();
+        let resolved = forge.parse(&minecraft, &platform).await.unwrap();
+        assert_eq!(resolved.minecraft_version, "1.19.4");
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_library_paths() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new("test");
+        let version = Version::from_value(vanilla_json(
+            "1.12.2",
+            "1.12",
+            serde_json::json!({
+                "libraries": [
+                    { "name": "com.google.code.gson:gson:2.8.0" },
+                    { "name": "org.ow2.asm:asm-all:5.2", "url": "https://maven.example.com/" }
+                ]
+            }),
+        ))
+        .unwrap();
+        let resolved = version.parse(&minecraft, &platform).await.unwrap();
+        let paths = resolved.library_paths(&minecraft);
+        assert_eq!(paths.len(), resolved.libraries.len() + 1);
+        assert!(paths[..2]
+            .iter()
+            .all(|p| p.starts_with(&minecraft.libraries)));
+        assert_eq!(
+            paths[0],
+            minecraft.get_library_by_path("com/google/code/gson/gson/2.8.0/gson-2.8.0.jar")
+        );
+        assert_eq!(paths[2], minecraft.get_version_jar("1.12.2", None));
+    }
+
+    #[tokio::test]
+    async fn test_infer_java_version() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new("test");
+        let version = Version::from_value(vanilla_json("1.18.2", "1.18", Value::Null)).unwrap();
+        let resolved = version.parse(&minecraft, &platform).await.unwrap();
+        assert_eq!(resolved.java_version.major_version, 17);
+
+        let major = |id| infer_java_version(id).major_version;
+        assert_eq!(major("1.12.2"), 8);
+        assert_eq!(major("1.17.1"), 16);
+        assert_eq!(major("1.20.4"), 17);
+        assert_eq!(major("1.20.5"), 21);
+        assert_eq!(major("24w14a"), 21);
+        assert_eq!(major("My Modpack"), 8);
+    }
+
+    #[test]
+    fn test_parse_installed_id() {
+        let info = |minecraft: &str, loader: Loader, loader_version: &str| InstalledIdInfo {
+            minecraft_version: Some(minecraft.to_string()),
+            loader: Some(loader),
+            loader_version: Some(loader_version.to_string()),
+        };
+        assert_eq!(
+            parse_installed_id("1.19.4-fabric-0.14.21"),
+            info("1.19.4", Loader::Fabric, "0.14.21")
+        );
+        assert_eq!(
+            parse_installed_id("fabric-loader-0.14.21-1.19.4"),
+            info("1.19.4", Loader::Fabric, "0.14.21")
+        );
+        assert_eq!(
+            parse_installed_id("quilt-loader-0.19.1-1.19.3"),
+            info("1.19.3", Loader::Quilt, "0.19.1")
+        );
+        assert_eq!(
+            parse_installed_id("1.12.2-forge-14.23.5.2860"),
+            info("1.12.2", Loader::Forge, "14.23.5.2860")
+        );
+        assert_eq!(
+            parse_installed_id("1.7.10-Forge10.13.4.1614-1.7.10"),
+            info("1.7.10", Loader::Forge, "10.13.4.1614")
+        );
+        assert_eq!(
+            parse_installed_id("1.20.1"),
+            InstalledIdInfo {
+                minecraft_version: Some("1.20.1".to_string()),
+                loader: None,
+                loader_version: None,
+            }
+        );
+        assert_eq!(parse_installed_id("My Modpack").minecraft_version, None);
+    }
+
+    #[test]
+    fn test_installed_id_from_version() {
+        let version = Version::from_str(
+            r#"{
+                "id": "My Modpack",
+                "inheritsFrom": "1.20.1",
+                "libraries": [
+                    { "name": "cpw.mods:securejarhandler:2.1.10" },
+                    { "name": "net.minecraftforge:fmlloader:1.20.1-47.1.0" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let info = InstalledIdInfo::from_version(&version);
+        assert_eq!(info.minecraft_version, Some("1.20.1".to_string()));
+        assert_eq!(info.loader, Some(Loader::Forge));
+        assert_eq!(info.loader_version, Some("47.1.0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_from_url() {
+        use crate::utils::download::test::serve;
+
+        let body =
+            r#"{"id":"1.20.1","type":"release","mainClass":"net.minecraft.client.main.Main"}"#;
+        let url = serve("200 OK", body.as_bytes().to_vec()).await;
+        let version = Version::from_url(&format!("{url}/1.20.1.json"))
+            .await
+            .unwrap();
+        assert_eq!(version.id, "1.20.1");
+        assert_eq!(version.r#type.as_deref(), Some("release"));
+
+        let url = serve("404 Not Found", vec![]).await;
+        let err = Version::from_url(&url).await.unwrap_err();
+        assert!(err.to_string().contains("404"), "{err}");
+
+        // retried after 0.5s and 1s
+        let url = serve("503 Service Unavailable", vec![]).await;
+        let start = std::time::Instant::now();
+        let err = Version::from_url(&url).await.unwrap_err();
+        assert!(err.to_string().contains("503"), "{err}");
+        assert!(start.elapsed() >= FETCH_RETRY_DELAY * 3);
+
+        let url = serve("200 OK", b"{\"id\":".to_vec()).await;
+        let err = Version::from_url(&url).await.unwrap_err();
+        assert!(err.to_string().contains("Malformed"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_from_url_with() {
+        use futures::future::BoxFuture;
+
+        struct Mirror;
+
+        impl Downloader for Mirror {
+            fn fetch<'a>(&'a self, task: &'a crate::core::Download) -> BoxFuture<'a, Result<()>> {
+                Box::pin(async move {
+                    assert_eq!(task.url, "https://example.com/1.20.1.json");
+                    let body = r#"{"id":"1.20.1","mainClass":"net.minecraft.client.main.Main"}"#;
+                    std::fs::write(&task.file, body)?;
+                    Ok(())
+                })
+            }
+        }
+
+        let version = Version::from_url_with("https://example.com/1.20.1.json", &Mirror)
+            .await
+            .unwrap();
+        assert_eq!(version.id, "1.20.1");
+    }
+
+    #[tokio::test]
+    async fn test_legacy_server_library() {
+        let platform = PlatformInfo::new().await;
+        let libraries = vec![
+            serde_json::json!({ "name": "net.minecraftforge:forge:1.7.10-10.13.4.1614", "url": "https://maven.minecraftforge.net/" }),
+            serde_json::json!({ "name": "com.typesafe:config:1.2.1", "clientreq": true, "serverreq": true }),
+            serde_json::json!({ "name": "org.scala-lang:scala-swing_2.11:1.0.1", "clientreq": false, "serverreq": true }),
+        ];
+        let resolved = resolve_libraries(libraries, &platform, Side::Client).await;
+        let paths: Vec<_> = resolved
+            .iter()
+            .map(|library| library.download_info.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "net/minecraftforge/forge/1.7.10-10.13.4.1614/forge-1.7.10-10.13.4.1614.jar",
+                "com/typesafe/config/1.2.1/config-1.2.1.jar",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_library_url_without_slash() {
+        let platform = PlatformInfo::new().await;
+        let libraries = vec![
+            serde_json::json!({ "name": "net.fabricmc:sponge-mixin:0.12.5+mixin.0.8.5", "url": "https://maven.fabricmc.net" }),
+            serde_json::json!({ "name": "net.fabricmc:intermediary:1.20.1", "url": "https://maven.fabricmc.net/" }),
+        ];
+        let resolved = resolve_libraries(libraries, &platform, Side::Client).await;
+        assert_eq!(
+            resolved[0].download_info.url,
+            "https://maven.fabricmc.net/net/fabricmc/sponge-mixin/0.12.5+mixin.0.8.5/sponge-mixin-0.12.5+mixin.0.8.5.jar"
+        );
+        assert_eq!(
+            resolved[1].download_info.url,
+            "https://maven.fabricmc.net/net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_for_side() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new("test");
+        let classifier = |os: &str| {
+            serde_json::json!({
+                "path": format!("org/lwjgl/lwjgl/lwjgl-platform/2.9.4/lwjgl-platform-2.9.4-natives-{os}.jar"),
+                "url": "https://libraries.minecraft.net/lwjgl-platform.jar",
+                "sha1": "0",
+                "size": 1,
+            })
+        };
+        let version = Version::from_value(vanilla_json(
+            "1.7.10-Forge",
+            "1.7.10",
+            serde_json::json!({
+                "mainClass": "net.minecraft.launchwrapper.Launch",
+                "libraries": [
+                    { "name": "net.minecraft:launchwrapper:1.12" },
+                    { "name": "org.scala-lang:scala-swing_2.11:1.0.1", "clientreq": false, "serverreq": true },
+                    { "name": "net.java.jinput:jinput:2.0.5", "clientreq": true, "serverreq": false },
+                    {
+                        "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+                        "natives": { "linux": "natives-linux", "osx": "natives-osx", "windows": "natives-windows" },
+                        "downloads": { "classifiers": {
+                            "natives-linux": classifier("linux"),
+                            "natives-osx": classifier("osx"),
+                            "natives-windows": classifier("windows"),
+                        } }
+                    }
+                ]
+            }),
+        ))
+        .unwrap();
+        let names = |resolved: &ResolvedVersion| {
+            resolved
+                .libraries
+                .iter()
+                .map(|library| {
+                    library
+                        .download_info
+                        .path
+                        .split('/')
+                        .nth(1)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let client = version.parse(&minecraft, &platform).await.unwrap();
+        let client_names = names(&client);
+        assert!(client_names.contains(&"java".to_string()));
+        assert!(!client_names.contains(&"scala-lang".to_string()));
+        assert!(client
+            .libraries
+            .iter()
+            .any(|library| library.is_native_library));
+        assert!(!client.arguments.as_ref().unwrap().game.is_empty());
+
+        let server = version
+            .parse_for_side(&minecraft, &platform, Side::Server)
+            .await
+            .unwrap();
+        assert_eq!(names(&server), vec!["minecraft", "scala-lang"]);
+        let arguments = server.arguments.unwrap();
+        assert!(arguments.game.is_empty());
+        assert!(!arguments
+            .jvm
+            .iter()
+            .any(|arg| arg.contains("java.library.path")));
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let raw = r#"{
+            "id": "1.20.1-custom",
+            "inheritsFrom": "1.20.1",
+            "minimumLauncherVersion": "21",
+            "complianceLevel": 1,
+            "customLauncher": { "icon": "grass", "tags": ["modded"] }
+        }"#;
+        let version = Version::from_str(raw).unwrap();
+        assert_eq!(version.minimum_launcher_version, None);
+        assert_eq!(version.compliance_level, Some(1));
+        assert_eq!(version.extra["customLauncher"]["icon"], "grass");
+
+        let json: Value = serde_json::from_str(&version.to_version_json().unwrap()).unwrap();
+        assert_eq!(json["customLauncher"]["tags"][0], "modded");
+        assert_eq!(json["minimumLauncherVersion"], "21");
+        assert_eq!(json["inheritsFrom"], "1.20.1");
+        assert!(json.get("jar").is_none());
+        assert_eq!(Version::from_value(json).unwrap(), version);
+
+        // the launch can't go on without the libraries, so a mistyped list is still an error
+        assert!(Version::from_str(r#"{ "id": "broken", "libraries": {} }"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_latest() {
+        use crate::utils::download::test::serve;
+
+        let body = r#"{
+            "latest": { "release": "1.20.6", "snapshot": "24w20a" },
+            "versions": [{ "id": "24w20a", "type": "snapshot" }]
+        }"#;
+        let url = serve("200 OK", body.as_bytes().to_vec()).await;
+        let latest = VersionManifest::latest_from_url(&url).await.unwrap();
+        assert_eq!(latest.release, "1.20.6");
+        assert_eq!(latest.snapshot, "24w20a");
+    }
+
+    #[tokio::test]
+    async fn test_from_mirror() {
+        use crate::utils::download::test::serve;
+
+        let body = r#"{
+            "latest": { "release": "1.20.6", "snapshot": "24w20a" },
+            "versions": [
+                {
+                    "id": "1.20.6",
+                    "type": "release",
+                    "url": "https://piston-meta.mojang.com/v1/packages/1.20.6.json",
+                    "time": "2024-04-29T10:00:00+00:00",
+                    "releaseTime": "2024-04-29T10:00:00+00:00",
+                    "sha1": "ba4f4e7a1e1e4b5ce5f8d9a5a0e3b3e6c1c0a7d2",
+                    "complianceLevel": 1
+                },
+                {
+                    "id": "1.0",
+                    "type": "release",
+                    "url": "https://launchermeta.mojang.com/v1/packages/1.0.json",
+                    "time": "2011-11-17T22:00:00+00:00",
+                    "releaseTime": "2011-11-17T22:00:00+00:00"
+                }
+            ]
+        }"#;
+        let url = serve("200 OK", body.as_bytes().to_vec()).await;
+        let manifest = VersionManifest::from_mirror(&format!("{url}/"))
+            .await
+            .unwrap();
+        assert_eq!(manifest.latest.release, "1.20.6");
+        let v2 = &manifest.versions[0];
+        assert_eq!(
+            v2.sha1.as_deref(),
+            Some("ba4f4e7a1e1e4b5ce5f8d9a5a0e3b3e6c1c0a7d2")
+        );
+        assert_eq!(v2.compliance_level, Some(1));
+        let v1 = &manifest.versions[1];
+        assert_eq!(v1.sha1, None);
+        assert_eq!(v1.compliance_level, None);
+        assert_eq!(
+            version_manifest_url("https://bmclapi2.bangbang93.com/"),
+            "https://bmclapi2.bangbang93.com/mc/game/version_manifest_v2.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tweak_classes() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        write_version(
+            &minecraft,
+            "1.12.2",
+            &vanilla_json(
+                "1.12.2",
+                "1.12",
+                serde_json::json!({
+                    "minecraftArguments": "--username ${auth_player_name} --version ${version_name}"
+                }),
+            )
+            .to_string(),
+        );
+        write_version(
+            &minecraft,
+            "1.12.2-forge-14.23.5.2859",
+            r#"{
+                "id": "1.12.2-forge-14.23.5.2859",
+                "inheritsFrom": "1.12.2",
+                "mainClass": "net.minecraft.launchwrapper.Launch",
+                "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker --tweakClass optifine.OptiFineForgeTweaker"
+            }"#,
+        );
+        let liteloader = Version::from_str(
+            r#"{
+                "id": "1.12.2-forge-LiteLoader1.12.2",
+                "inheritsFrom": "1.12.2-forge-14.23.5.2859",
+                "mainClass": "net.minecraft.launchwrapper.Launch",
+                "arguments": {
+                    "game": [
+                        "--tweakClass", "com.mumfrey.liteloader.launch.LiteLoaderTweaker",
+                        "--tweakClass", "net.minecraftforge.fml.common.launcher.FMLTweaker"
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+        let resolved = liteloader.parse(&minecraft, &platform).await.unwrap();
+        let game = resolved.arguments.unwrap().game;
+        let tweak_classes = game
+            .windows(2)
+            .filter(|pair| pair[0] == "--tweakClass")
+            .map(|pair| pair[1].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tweak_classes,
+            [
+                "com.mumfrey.liteloader.launch.LiteLoaderTweaker",
+                "net.minecraftforge.fml.common.launcher.FMLTweaker",
+                "optifine.OptiFineForgeTweaker"
+            ]
+        );
+        let server = liteloader
+            .parse_for_side(&minecraft, &platform, Side::Server)
+            .await
+            .unwrap();
+        assert!(server.arguments.unwrap().game.is_empty());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_required_java() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        write_version(
+            &minecraft,
+            "1.20.1",
+            r#"{
+                "id": "1.20.1",
+                "mainClass": "net.minecraft.client.main.Main",
+                "javaVersion": { "component": "java-runtime-gamma", "majorVersion": 17 }
+            }"#,
+        );
+        write_version(
+            &minecraft,
+            "1.20.1-forge-47.1.0",
+            r#"{ "id": "1.20.1-forge-47.1.0", "inheritsFrom": "1.20.1" }"#,
+        );
+        assert_eq!(
+            required_java("1.20.1-forge-47.1.0", &minecraft)
+                .await
+                .unwrap(),
+            17
+        );
+
+        // no javaVersion in the chain, inferred from the vanilla version
+        write_version(
+            &minecraft,
+            "Old",
+            r#"{ "id": "Old", "clientVersion": "1.16.5", "mainClass": "net.minecraft.client.main.Main" }"#,
+        );
+        write_version(
+            &minecraft,
+            "Old-fabric",
+            r#"{ "id": "Old-fabric", "inheritsFrom": "Old" }"#,
+        );
+        assert_eq!(required_java("Old-fabric", &minecraft).await.unwrap(), 8);
+        write_version(
+            &minecraft,
+            "1.18.2",
+            r#"{ "id": "1.18.2", "mainClass": "net.minecraft.client.main.Main" }"#,
+        );
+        assert_eq!(required_java("1.18.2", &minecraft).await.unwrap(), 17);
+
+        assert!(required_java("missing", &minecraft).await.is_err());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[cfg(unix)]
+    /// Make `path` a fifo whose json is written only after a while, run `read` meanwhile, and
+    /// check the runtime thread kept running while it waited
+    async fn read_without_blocking<T>(
+        path: &std::path::Path,
+        json: String,
+        read: impl std::future::Future<Output = T>,
+    ) -> T {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mkfifo = std::process::Command::new("mkfifo")
+            .arg(path)
+            .status()
+            .unwrap();
+        assert!(mkfifo.success());
+
+        // the ticker only runs if the read leaves the runtime thread free while it's pending
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker_ticks = ticks.clone();
+        let ticker = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                ticker_ticks.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let writer_ticks = ticks.clone();
+        let fifo = path.to_path_buf();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            let seen = writer_ticks.load(Ordering::SeqCst);
+            let mut fifo = std::fs::OpenOptions::new().write(true).open(&fifo).unwrap();
+            fifo.write_all(json.as_bytes()).unwrap();
+            seen
+        });
+        let result = read.await;
+        let seen = writer.join().unwrap();
+        ticker.abort();
+        assert!(seen >= 10, "the runtime was blocked, {seen} ticks");
+        result
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_parse_on_current_thread() {
+        let platform = PlatformInfo::new().await;
+        let root = crate::utils::download::test::temp_dir();
+        let minecraft = MinecraftLocation::new(root.to_str().unwrap());
+        let version = Version::from_str(
+            r#"{"id":"fabric-1.20.1","inheritsFrom":"1.20.1","mainClass":"net.fabricmc.loader.impl.launch.knot.KnotClient"}"#,
+        )
+        .unwrap();
+        let parent_json = vanilla_json("1.20.1", "5", Value::Null).to_string();
+        let resolved = read_without_blocking(
+            &minecraft.get_version_json("1.20.1"),
+            parent_json.clone(),
+            version.parse(&minecraft, &platform),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resolved.path_chain.len(), 1);
+
+        let version = read_without_blocking(
+            &minecraft.get_version_json("1.20.2"),
+            parent_json,
+            Version::from_versions_folder(minecraft.clone(), "1.20.2"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(version.id, "1.20.1");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_inheritance_chain() {
+        let platform = PlatformInfo::new().await;
+        let root = crate::utils::download::test::temp_dir();
+        let minecraft = MinecraftLocation::new(root.to_str().unwrap());
+        write_version(
+            &minecraft,
+            "1.20.1",
+            &vanilla_json(
+                "1.20.1",
+                "5",
+                serde_json::json!({ "libraries": [{ "name": "com.mojang:brigadier:1.1.8" }] }),
+            )
+            .to_string(),
+        );
+        write_version(
+            &minecraft,
+            "1.20.1-forge-47.2.0",
+            r#"{
+                "id": "1.20.1-forge-47.2.0",
+                "inheritsFrom": "1.20.1",
+                "mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+                "libraries": [{ "name": "net.minecraftforge:fmlloader:1.20.1-47.2.0" }]
+            }"#,
+        );
+        let optifine = Version::from_str(
+            r#"{
+                "id": "1.20.1-forge-OptiFine",
+                "inheritsFrom": "1.20.1-forge-47.2.0",
+                "libraries": [{ "name": "optifine:OptiFine:1.20.1_HD_U_I6" }]
+            }"#,
+        )
+        .unwrap();
+        let resolved = optifine.parse(&minecraft, &platform).await.unwrap();
+        assert_eq!(resolved.inheritances, vec!["1.20.1-forge-47.2.0", "1.20.1"]);
+        assert_eq!(
+            resolved.main_class,
+            "cpw.mods.bootstraplauncher.BootstrapLauncher"
+        );
+        assert_eq!(resolved.minecraft_version, "1.20.1");
+        assert_eq!(resolved.version_type, "release");
+        let paths = resolved
+            .libraries
+            .iter()
+            .map(|lib| lib.download_info.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "optifine/OptiFine/1.20.1_HD_U_I6/OptiFine-1.20.1_HD_U_I6.jar",
+                "net/minecraftforge/fmlloader/1.20.1-47.2.0/fmlloader-1.20.1-47.2.0.jar",
+                "com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar",
+            ]
+        );
+
+        write_version(&minecraft, "a", r#"{ "id": "a", "inheritsFrom": "b" }"#);
+        write_version(&minecraft, "b", r#"{ "id": "b", "inheritsFrom": "a" }"#);
+        let cyclic = Version::from_str(r#"{ "id": "a", "inheritsFrom": "b" }"#).unwrap();
+        let err = cyclic.parse(&minecraft, &platform).await.unwrap_err();
+        assert!(err.to_string().contains("a -> b -> a"), "{err}");
+
+        write_version(&minecraft, "broken", "{ not json");
+        let broken = Version::from_str(r#"{ "id": "c", "inheritsFrom": "broken" }"#).unwrap();
+        let err = broken.parse(&minecraft, &platform).await.unwrap_err();
+        assert!(err.to_string().contains("Malformed"), "{err}");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_windows_version_rule() {
+        use crate::core::{normalize_windows_version, OsType};
+
+        let rules = serde_json::from_str::<Vec<Value>>(
+            r#"[{ "action": "allow", "os": { "name": "windows", "version": "^10\\." } }]"#,
+        )
+        .unwrap();
+        let windows = |version: &str| PlatformInfo {
+            arch: "x64".to_string(),
+            name: "windows".to_string(),
+            os_type: OsType::Windows,
+            version: normalize_windows_version(version),
+        };
+        let windows_10 = windows("Major  Minor  Build  Revision\r\n-----  -----  -----  --------\r\n10     0      19045  0\r\n");
+        assert_eq!(windows_10.version, "10.0.19045");
+        assert!(check_allowed(rules.clone(), &windows_10));
+        let windows_7 = windows("Major  Minor  Build  Revision\r\n-----  -----  -----  --------\r\n6      1      7601   65536\r\n");
+        assert!(!check_allowed(rules, &windows_7));
+    }
+
+    #[tokio::test]
+    async fn test_parse_cached() {
+        let platform = PlatformInfo::new().await;
+        let root = crate::utils::download::test::temp_dir();
+        let minecraft = MinecraftLocation::new(root.to_str().unwrap());
+        let parent = vanilla_json("1.20.1", "5", Value::Null).to_string();
+        write_version(&minecraft, "1.20.1", &parent);
+        let fabric = Version::from_str(r#"{ "id": "fabric", "inheritsFrom": "1.20.1" }"#).unwrap();
+        let first = fabric.parse_cached(&minecraft, &platform).await.unwrap();
+
+        // a cache hit doesn't read the parent json again, even if its content is broken
+        let parent_path = minecraft.get_version_json("1.20.1");
+        let modified = std::fs::metadata(&parent_path).unwrap().modified().unwrap();
+        std::fs::write(&parent_path, "{ not json").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&parent_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let second = fabric.parse_cached(&minecraft, &platform).await.unwrap();
+        assert_eq!(second.main_class, first.main_class);
+
+        // a modified parent json is read again
+        std::fs::File::options()
+            .write(true)
+            .open(&parent_path)
+            .unwrap()
+            .set_modified(modified + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert!(fabric.parse_cached(&minecraft, &platform).await.is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The crash reports the game writes in `crash-reports`
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CrashReportInfo {
+    pub path: PathBuf,
+
+    /// The modified time of the report, when the game crashed
+    pub created_at: SystemTime,
+
+    /// The exception the report starts with, like `java.lang.NullPointerException: ...`
+    pub summary: String,
+}
+
+/// Read the exception of a crash report, the first line after the `Description:` header, or the
+/// first line if there is no such header.
+pub fn read_summary(report: &str) -> String {
+    let mut lines = report.lines().map(str::trim);
+    let after_description = lines
+        .by_ref()
+        .find(|line| line.starts_with("Description:"))
+        .and_then(|_| lines.find(|line| !line.is_empty()));
+    after_description
+        .or_else(|| report.lines().map(str::trim).find(|line| !line.is_empty()))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// List the crash reports of a folder, the newest first. The files that cannot be read are
+/// skipped, and a folder that doesn't exist has no reports.
+pub fn list_crash_reports<P: AsRef<Path>>(folder: P) -> Result<Vec<CrashReportInfo>> {
+    let folder = folder.as_ref();
+    if !folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut reports = Vec::new();
+    for entry in folder.read_dir()? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if path.extension().is_none_or(|extension| extension != "txt") {
+            continue;
+        }
+        let created_at = std::fs::metadata(&path).and_then(|metadata| metadata.modified());
+        let report = std::fs::read(&path);
+        if let (Ok(created_at), Ok(report)) = (created_at, report) {
+            reports.push(CrashReportInfo {
+                summary: read_summary(&String::from_utf8_lossy(&report)),
+                path,
+                created_at,
+            });
+        }
+    }
+    reports.sort_by_key(|report| std::cmp::Reverse(report.created_at));
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_list_crash_reports() {
+        let folder = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&folder).unwrap();
+        let write = |name: &str, content: &str, age: u64| {
+            let path = folder.join(name);
+            std::fs::write(&path, content).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        write(
+            "crash-2023-08-01_12.00.00-client.txt",
+            "---- Minecraft Crash Report ----\n// Who set us up the TNT?\n\n\
+            Time: 2023-08-01 12:00:00\nDescription: Rendering overlay\n\n\
+            java.lang.NullPointerException: Cannot invoke \"Object.toString()\"\n\tat net.minecraft.client.Minecraft.run(Minecraft.java:1)\n",
+            3600,
+        );
+        write(
+            "crash-2023-08-02_12.00.00-client.txt",
+            "---- Minecraft Crash Report ----\nDescription: Initializing game\n\n\
+            java.lang.OutOfMemoryError: Java heap space\n",
+            60,
+        );
+        write("notes.md", "not a report", 0);
+
+        let reports = list_crash_reports(&folder).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0]
+            .path
+            .ends_with("crash-2023-08-02_12.00.00-client.txt"));
+        assert_eq!(
+            reports[0].summary,
+            "java.lang.OutOfMemoryError: Java heap space"
+        );
+        assert_eq!(
+            reports[1].summary,
+            "java.lang.NullPointerException: Cannot invoke \"Object.toString()\""
+        );
+        assert!(list_crash_reports(folder.join("missing"))
+            .unwrap()
+            .is_empty());
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Export and import an instance as a portable zip
+//!
+//! The archive has the same layout as a `.minecraft` folder, so it can be extracted and launched
+//! directly.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::core::{
+    folder::MinecraftLocation,
+    version::{ResolvedVersion, Version},
+    PlatformInfo,
+};
+use crate::utils::unzip::sanitize_entry_name;
+
+/// The folders and files in the game folder never exported
+const EXCLUDED: &[&str] = &["logs", "crash-reports", "cvl-natives", "session.lock"];
+
+/// Export the version folder of an instance, with its mods, configs, resourcepacks and
+/// `options.txt`, into the zip file `dest`.
+///
+/// The parent versions are always exported. With `include_assets`, the libraries and assets
+/// the version depends on are exported too. Logs, natives and session locks are excluded, and
+/// saves are excluded unless `include_saves`, from the parent versions too.
+pub async fn export_instance<P: AsRef<Path>>(
+    minecraft: &MinecraftLocation,
+    version_id: &str,
+    dest: P,
+    include_assets: bool,
+    include_saves: bool,
+) -> Result<PathBuf> {
+    let dest = dest.as_ref().to_path_buf();
+    let version = Version::from_versions_folder(minecraft.clone(), version_id).await?;
+    let resolved = version.parse(minecraft, &PlatformInfo::new().await).await?;
+    let minecraft = minecraft.clone();
+    let version_id = version_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        write_instance(
+            &minecraft,
+            &version_id,
+            &resolved,
+            &dest,
+            include_assets,
+            include_saves,
+        )?;
+        Ok(dest)
+    })
+    .await?
+}
+
+fn write_instance(
+    minecraft: &MinecraftLocation,
+    version_id: &str,
+    resolved: &ResolvedVersion,
+    dest: &Path,
+    include_assets: bool,
+    include_saves: bool,
+) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let excluded = |path: &Path| {
+        let name = path.file_name().unwrap_or_default();
+        EXCLUDED.iter().any(|excluded| name == *excluded) || (!include_saves && name == "saves")
+    };
+    add_dir(
+        &mut zip,
+        &minecraft.get_version_root(version_id),
+        Path::new("versions").join(version_id),
+        &excluded,
+    )?;
+    for parent in &resolved.inheritances {
+        add_dir(
+            &mut zip,
+            &minecraft.get_version_root(parent),
+            Path::new("versions").join(parent),
+            &excluded,
+        )?;
+    }
+
+    if include_assets {
+        for library in &resolved.libraries {
+            let path = Path::new(&library.download_info.path);
+            add_file(
+                &mut zip,
+                &minecraft.get_library_by_path(path),
+                Path::new("libraries").join(path),
+            )?;
+        }
+        let index_path = minecraft.get_assets_index(&resolved.assets);
+        add_file(
+            &mut zip,
+            &index_path,
+            Path::new("assets/indexes").join(format!("{}.json", resolved.assets)),
+        )?;
+        let index: serde_json::Value = serde_json::from_slice(&std::fs::read(&index_path)?)?;
+        if let Some(objects) = index["objects"].as_object() {
+            for hash in objects
+                .values()
+                .filter_map(|object| object["hash"].as_str())
+            {
+                let path = Path::new("objects").join(&hash[0..2]).join(hash);
+                add_file(
+                    &mut zip,
+                    &minecraft.assets.join(&path),
+                    Path::new("assets").join(path),
+                )?;
+            }
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Import an instance archive made by `export_instance` into the `.minecraft` folder, and return
+/// the id of the imported version.
+///
+/// The libraries, assets and parent versions already in the folder are kept. Return an error if
+/// the archive is not an instance, or the version already exists.
+pub async fn import_instance<P: AsRef<Path>>(
+    archive: P,
+    minecraft: &MinecraftLocation,
+) -> Result<String> {
+    let archive = archive.as_ref().to_path_buf();
+    let minecraft = minecraft.clone();
+    tokio::task::spawn_blocking(move || extract_instance(&archive, &minecraft)).await?
+}
+
+fn extract_instance(archive: &Path, minecraft: &MinecraftLocation) -> Result<String> {
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+    let id = find_instance_version(&mut zip)?;
+    if minecraft.get_version_root(&id).exists() {
+        return Err(anyhow!("version {id} already exists"));
+    }
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = sanitize_entry_name(file.name())?;
+        let target = minecraft.root.join(&path);
+        let is_instance = path.starts_with(Path::new("versions").join(&id));
+        if !is_instance && target.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(target)?)?;
+    }
+    Ok(id)
+}
+
+/// Find the version of the instance, which is the only version not inherited by the others
+fn find_instance_version<R: Read + Seek>(zip: &mut ZipArchive<R>) -> Result<String> {
+    let mut versions = HashMap::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let path = sanitize_entry_name(file.name())?;
+        match path.components().next() {
+            Some(Component::Normal(root))
+                if root == "versions" || root == "libraries" || root == "assets" => {}
+            _ => return Err(anyhow!("unexpected entry {} in the archive", file.name())),
+        }
+        let components = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        if let [root, id, json] = components.as_slice() {
+            if root == "versions" && *json == format!("{id}.json") {
+                let mut raw = String::new();
+                file.read_to_string(&mut raw)?;
+                let version: Version = serde_json::from_str(&raw)?;
+                versions.insert(id.clone(), version.inherits_from);
+            }
+        }
+    }
+    let mut instances = versions
+        .keys()
+        .filter(|id| !versions.values().any(|parent| parent.as_ref() == Some(*id)));
+    match (instances.next(), instances.next()) {
+        (Some(id), None) => Ok(id.clone()),
+        _ => Err(anyhow!("the archive is not an instance")),
+    }
+}
+
+fn add_dir(
+    zip: &mut ZipWriter<File>,
+    path: &Path,
+    zip_path: PathBuf,
+    excluded: &dyn Fn(&Path) -> bool,
+) -> Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let path = entry.path();
+        if excluded(&path) {
+            continue;
+        }
+        let zip_path = zip_path.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            add_dir(zip, &path, zip_path, excluded)?;
+        } else {
+            add_file(zip, &path, zip_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add a file to the zip, the missing files are skipped
+fn add_file(zip: &mut ZipWriter<File>, path: &Path, zip_path: PathBuf) -> Result<()> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    // zip entries always use `/`
+    let name = zip_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    zip.start_file(name, FileOptions::default())?;
+    io::copy(&mut file, zip)?;
+    zip.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::version::test::{vanilla_json, write_version};
+
+    #[tokio::test]
+    async fn test_export_instance() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let root = minecraft.get_version_root("1.20.1");
+        let vanilla = vanilla_json("1.20.1", "5", serde_json::Value::Null).to_string();
+        write_version(&minecraft, "1.20.1", &vanilla);
+        for (path, content) in [
+            ("mods/sodium.jar", "mod"),
+            ("config/sodium.json", "{}"),
+            ("options.txt", "lang:en_us"),
+            ("logs/latest.log", "log"),
+            ("saves/World/level.dat", "level"),
+            ("saves/World/session.lock", ""),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let names = |dest: &Path| {
+            let archive = zip::ZipArchive::new(File::open(dest).unwrap()).unwrap();
+            let mut names = archive.file_names().map(String::from).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        let dest = minecraft.root.join("export.zip");
+        let dest = export_instance(&minecraft, "1.20.1", &dest, false, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&dest),
+            vec![
+                "versions/1.20.1/1.20.1.json",
+                "versions/1.20.1/config/sodium.json",
+                "versions/1.20.1/mods/sodium.jar",
+                "versions/1.20.1/options.txt",
+            ]
+        );
+
+        let dest = export_instance(&minecraft, "1.20.1", &dest, false, true)
+            .await
+            .unwrap();
+        let names = names(&dest);
+        assert!(names.contains(&"versions/1.20.1/saves/World/level.dat".to_string()));
+        assert!(!names.iter().any(|name| name.ends_with("session.lock")));
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_instance() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let vanilla = vanilla_json("1.20.1", "5", serde_json::Value::Null).to_string();
+        let fabric = r#"{
+            "id": "fabric-1.20.1",
+            "inheritsFrom": "1.20.1",
+            "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient"
+        }"#;
+        for (id, json) in [("1.20.1", vanilla.as_str()), ("fabric-1.20.1", fabric)] {
+            write_version(&minecraft, id, json);
+            std::fs::create_dir_all(minecraft.get_version_root(id).join("logs")).unwrap();
+            std::fs::write(
+                minecraft.get_version_root(id).join("logs/latest.log"),
+                "log",
+            )
+            .unwrap();
+        }
+        std::fs::create_dir_all(minecraft.get_version_root("fabric-1.20.1").join("mods")).unwrap();
+        std::fs::write(
+            minecraft
+                .get_version_root("fabric-1.20.1")
+                .join("mods/sodium.jar"),
+            "mod",
+        )
+        .unwrap();
+        let archive = export_instance(
+            &minecraft,
+            "fabric-1.20.1",
+            minecraft.root.join("export.zip"),
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+        let names = zip::ZipArchive::new(File::open(&archive).unwrap())
+            .unwrap()
+            .file_names()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"versions/1.20.1/1.20.1.json".to_string()));
+        assert!(!names.iter().any(|name| name.contains("/logs/")));
+
+        let target = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let id = import_instance(&archive, &target).await.unwrap();
+        assert_eq!(id, "fabric-1.20.1");
+        assert!(target
+            .get_version_root(&id)
+            .join("mods/sodium.jar")
+            .is_file());
+        let options = crate::launch::options::LaunchOptions::new(&id, &target)
+            .await
+            .unwrap();
+        let platform = PlatformInfo::new().await;
+        let resolved = options.version.parse(&target, &platform).await.unwrap();
+        assert_eq!(resolved.inheritances, vec!["1.20.1".to_string()]);
+
+        assert!(import_instance(&archive, &target).await.is_err());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+        std::fs::remove_dir_all(&target.root).unwrap();
+    }
+}
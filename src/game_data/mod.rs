@@ -16,6 +16,8 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod crash_report;
 pub mod saves;
 pub mod mods;
-pub mod resourcepack;
\ No newline at end of file
+pub mod resourcepack;
+pub mod export;
\ No newline at end of file
@@ -29,7 +29,7 @@ use serde_json::Value;
 use toml::Table;
 use zip::ZipArchive;
 
-use super::{Parse, ResolvedAuthorInfo, ResolvedDepends, ResolvedMod};
+use super::{parse_people, Parse, ResolvedDependency, ResolvedDepends, ResolvedMod, VersionRange};
 use crate::utils::unzip::filter_entries;
 
 /// Represent the forge `mcmod.info` format.
@@ -66,18 +66,15 @@ impl Parse for ForgeModMcmodInfo {
                 },
             },
             description: self.description,
-            authors: {
-                match self.author_list {
-                    None => vec![],
-                    Some(v) => v
-                        .into_iter()
-                        .map(|v| ResolvedAuthorInfo {
-                            name: v,
-                            contact: None,
-                        })
-                        .collect::<Vec<_>>(),
-                }
-            },
+            authors: self
+                .author_list
+                .map(|v| parse_people(&Value::from(v)))
+                .unwrap_or_default(),
+            contributors: self
+                .credits
+                .map(|v| parse_people(&Value::String(v)))
+                .unwrap_or_default(),
+            mixins: vec![],
             version: self.version,
             icon: self.logo_file,
             license: None,
@@ -87,11 +84,13 @@ impl Parse for ForgeModMcmodInfo {
                         minecraft: Some(Value::String(v)),
                         java: None,
                         mod_loader: None,
+                        dependencies: vec![],
                     },
                     None => ResolvedDepends {
                         minecraft: None,
                         java: None,
                         mod_loader: None,
+                        dependencies: vec![],
                     },
                 }
             },
@@ -154,10 +153,56 @@ impl ForgeModTOMLData {
             }
         }
     }
+
+    /// Read the `[[dependencies.<modid>]]` tables of the described mod.
+    ///
+    /// If the mod id is unknown, the dependencies of every mod in the file are returned.
+    pub fn resolve_dependencies(&self) -> Vec<ResolvedDependency> {
+        let table = match &self.dependencies {
+            Some(v) => v,
+            None => return vec![],
+        };
+        let entries = match self.mod_id.as_ref().and_then(|id| table.get(id)) {
+            Some(v) => vec![v],
+            None => table.values().collect(),
+        };
+        entries
+            .into_iter()
+            .filter_map(|v| v.as_array())
+            .flatten()
+            .filter_map(|v| {
+                let mod_id = v.get("modId")?.as_str()?.to_string();
+                let mandatory = match v.get("mandatory").and_then(|v| v.as_bool()) {
+                    Some(v) => v,
+                    // NeoForge replaced `mandatory` with `type`.
+                    None => v.get("type").and_then(|v| v.as_str()) == Some("required"),
+                };
+                let get_str = |key: &str| v.get(key).and_then(|v| v.as_str()).map(String::from);
+                Some(ResolvedDependency {
+                    mod_id,
+                    mandatory,
+                    version_range: get_str("versionRange")
+                        .and_then(|v| VersionRange::parse(&v).ok()),
+                    ordering: get_str("ordering"),
+                    side: get_str("side"),
+                })
+            })
+            .collect()
+    }
 }
 
 impl Parse for ForgeModTOMLData {
     fn parse(self) -> ResolvedMod {
+        let dependencies = self.resolve_dependencies();
+        let find_range = |ids: &[&str]| {
+            dependencies
+                .iter()
+                .find(|v| ids.contains(&v.mod_id.as_str()))
+                .and_then(|v| v.version_range.as_ref())
+                .map(|v| Value::String(v.raw.clone()))
+        };
+        let minecraft = find_range(&["minecraft"]);
+        let mod_loader = find_range(&["forge", "neoforge"]);
         ResolvedMod {
             name: match self.display_name {
                 Some(v) => v,
@@ -167,23 +212,24 @@ impl Parse for ForgeModTOMLData {
                 },
             },
             description: self.description,
-            authors: {
-                match self.authors {
-                    None => vec![],
-                    Some(v) => vec![ResolvedAuthorInfo {
-                        name: v,
-                        contact: None,
-                    }],
-                }
-            },
+            authors: self
+                .authors
+                .map(|v| parse_people(&Value::String(v)))
+                .unwrap_or_default(),
+            contributors: self
+                .credits
+                .map(|v| parse_people(&Value::String(v)))
+                .unwrap_or_default(),
+            mixins: vec![],
             version: self.version,
             icon: self.logo_file,
             license: None,
             depends: {
                 ResolvedDepends {
-                    minecraft: None,
+                    minecraft,
                     java: None,
-                    mod_loader: None,
+                    mod_loader,
+                    dependencies,
                 }
             },
         }
@@ -280,18 +326,12 @@ impl Parse for ManifestMetadata {
                 },
             },
             description: self.description,
-            authors: {
-                match self.authors {
-                    None => vec![],
-                    Some(v) => v
-                        .into_iter()
-                        .map(|v| ResolvedAuthorInfo {
-                            name: v,
-                            contact: None,
-                        })
-                        .collect::<Vec<_>>(),
-                }
-            },
+            authors: self
+                .authors
+                .map(|v| parse_people(&Value::from(v)))
+                .unwrap_or_default(),
+            contributors: vec![],
+            mixins: vec![],
             version: None,
             icon: None,
             license: None,
@@ -300,6 +340,7 @@ impl Parse for ManifestMetadata {
                     minecraft: None,
                     java: None,
                     mod_loader: None,
+                    dependencies: vec![],
                 }
             },
         }
@@ -386,3 +427,59 @@ pub fn parse_folder<S: AsRef<OsStr> + ?Sized>(folder: &S) -> Result<Vec<Resolved
     }
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mods_toml_dependencies() {
+        let data = ForgeModTOMLData::from_str(
+            r#"
+modLoader = "javafml"
+loaderVersion = "[47,)"
+license = "MIT"
+
+[[mods]]
+modId = "examplemod"
+version = "1.0.0"
+displayName = "Example Mod"
+
+[[dependencies.examplemod]]
+modId = "forge"
+mandatory = true
+versionRange = "[47.1,)"
+ordering = "NONE"
+side = "BOTH"
+
+[[dependencies.examplemod]]
+modId = "jei"
+mandatory = false
+versionRange = "[15.0,16.0)"
+ordering = "AFTER"
+side = "CLIENT"
+"#,
+        )
+        .unwrap();
+        let resolved = data.parse();
+        let depends = resolved.depends;
+        assert_eq!(depends.mod_loader, Some(Value::String("[47.1,)".into())));
+        assert_eq!(depends.dependencies.len(), 2);
+
+        let forge = &depends.dependencies[0];
+        assert_eq!(forge.mod_id, "forge");
+        assert!(forge.mandatory);
+        let range = forge.version_range.as_ref().unwrap();
+        assert!(range.contains("47.2.0"));
+        assert!(!range.contains("46.0.14"));
+
+        let jei = &depends.dependencies[1];
+        assert_eq!(jei.mod_id, "jei");
+        assert!(!jei.mandatory);
+        assert_eq!(jei.ordering.as_deref(), Some("AFTER"));
+        assert_eq!(jei.side.as_deref(), Some("CLIENT"));
+        let range = jei.version_range.as_ref().unwrap();
+        assert!(range.contains("15.2.0.27"));
+        assert!(!range.contains("16.0"));
+    }
+}
@@ -47,11 +47,14 @@
 //! println!("{:#?}", result);
 //! ```
 
-use std::{collections::HashMap, ffi::OsStr, path::Path};
+use std::{cmp::Ordering, collections::HashMap, ffi::OsStr, path::Path};
 
 use anyhow::Result;
+use futures::StreamExt;
 use serde_json::Value;
 
+use crate::core::version::Loader;
+
 pub mod fabric;
 pub mod forge;
 pub mod quilt;
@@ -67,6 +70,12 @@ pub struct ResolvedMod {
     pub version: Option<String>,
     pub depends: ResolvedDepends,
     pub authors: Vec<ResolvedAuthorInfo>,
+    #[serde(default)]
+    pub contributors: Vec<ResolvedAuthorInfo>,
+
+    /// The mixin configs of a fabric or quilt mod
+    #[serde(default)]
+    pub mixins: Vec<MixinConfig>,
     pub license: Option<Vec<String>>,
     pub icon: Option<String>,
 }
@@ -76,6 +85,136 @@ pub struct ResolvedDepends {
     pub minecraft: Option<Value>,
     pub java: Option<Value>,
     pub mod_loader: Option<Value>,
+    /// All declared dependencies, when the mod metadata lists them.
+    #[serde(default)]
+    pub dependencies: Vec<ResolvedDependency>,
+}
+
+/// A dependency on another mod, as declared in the mod metadata.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ResolvedDependency {
+    pub mod_id: String,
+    pub mandatory: bool,
+    pub version_range: Option<VersionRange>,
+    /// `BEFORE`, `AFTER` or `NONE`.
+    pub ordering: Option<String>,
+    /// `BOTH`, `CLIENT` or `SERVER`.
+    pub side: Option<String>,
+}
+
+/// One interval of a [`VersionRange`]. A missing bound is unbounded.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct VersionRestriction {
+    pub lower: Option<String>,
+    pub lower_inclusive: bool,
+    pub upper: Option<String>,
+    pub upper_inclusive: bool,
+}
+
+/// A Maven version range, e.g. `[47,)`, `[1.20,1.21)` or `[1.0],[2.0,)`.
+///
+/// A bare version such as `1.0` is a soft requirement and matches any version, as does `*`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct VersionRange {
+    pub raw: String,
+    pub restrictions: Vec<VersionRestriction>,
+}
+
+impl VersionRange {
+    pub fn parse(range: &str) -> Result<VersionRange> {
+        let raw = range.trim().to_string();
+        let mut restrictions = Vec::new();
+        if raw.is_empty() || raw == "*" || !raw.starts_with(['[', '(']) {
+            return Ok(VersionRange { raw, restrictions });
+        }
+        let mut rest = raw.as_str();
+        while !rest.is_empty() {
+            let end = rest
+                .find([']', ')'])
+                .ok_or(anyhow::anyhow!("Unclosed version range: {raw}"))?;
+            let lower_inclusive = match rest.chars().next() {
+                Some('[') => true,
+                Some('(') => false,
+                _ => return Err(anyhow::anyhow!("Invalid version range: {raw}")),
+            };
+            let upper_inclusive = rest[end..].starts_with(']');
+            let body = &rest[1..end];
+            let bound = |v: &str| match v.trim() {
+                "" => None,
+                v => Some(v.to_string()),
+            };
+            let restriction = match body.split_once(',') {
+                Some((lower, upper)) => VersionRestriction {
+                    lower: bound(lower),
+                    lower_inclusive,
+                    upper: bound(upper),
+                    upper_inclusive,
+                },
+                None => {
+                    if !lower_inclusive || !upper_inclusive {
+                        return Err(anyhow::anyhow!("Invalid version range: {raw}"));
+                    }
+                    VersionRestriction {
+                        lower: bound(body),
+                        lower_inclusive,
+                        upper: bound(body),
+                        upper_inclusive,
+                    }
+                }
+            };
+            restrictions.push(restriction);
+            rest = rest[end + 1..].trim_start_matches([',', ' ']);
+        }
+        Ok(VersionRange { raw, restrictions })
+    }
+
+    /// Whether `version` falls in any interval of the range.
+    pub fn contains(&self, version: &str) -> bool {
+        if self.restrictions.is_empty() {
+            return true;
+        }
+        self.restrictions.iter().any(|r| {
+            let lower_ok = match &r.lower {
+                None => true,
+                Some(lower) => match compare_versions(version, lower) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => r.lower_inclusive,
+                    Ordering::Less => false,
+                },
+            };
+            let upper_ok = match &r.upper {
+                None => true,
+                Some(upper) => match compare_versions(version, upper) {
+                    Ordering::Less => true,
+                    Ordering::Equal => r.upper_inclusive,
+                    Ordering::Greater => false,
+                },
+            };
+            lower_ok && upper_ok
+        })
+    }
+}
+
+/// Compare two dotted versions, numerically where both parts are numbers.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a = a.split(['.', '-']);
+    let mut b = b.split(['.', '-']);
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(x), Some(y)) => {
+                let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+                    (Ok(x), Ok(y)) => x.cmp(&y),
+                    _ => x.cmp(y),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -84,6 +223,69 @@ pub struct ResolvedAuthorInfo {
     pub contact: Option<HashMap<String, String>>,
 }
 
+/// A mixin config file of a mod, and the side it is applied on
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct MixinConfig {
+    pub config: String,
+
+    /// `*`, `client` or `server`
+    pub environment: String,
+}
+
+/// Read the `mixins` of a fabric or quilt mod metadata: a config name, a
+/// `{ "config": ..., "environment": ... }` object, or an array of them. The environment is `*`
+/// if absent.
+pub fn parse_mixins(value: &Value) -> Vec<MixinConfig> {
+    match value {
+        Value::String(config) => vec![MixinConfig {
+            config: config.clone(),
+            environment: "*".to_string(),
+        }],
+        Value::Array(configs) => configs.iter().flat_map(parse_mixins).collect(),
+        Value::Object(object) => match object.get("config").and_then(Value::as_str) {
+            Some(config) => vec![MixinConfig {
+                config: config.to_string(),
+                environment: object
+                    .get("environment")
+                    .and_then(Value::as_str)
+                    .unwrap_or("*")
+                    .to_string(),
+            }],
+            None => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+/// Read the authors or contributors of a mod metadata, in any of the encodings used by the mod
+/// loaders: a name, a `{ "name": ..., "contact": { ... } }` object, a `{ "Name": "Role" }` map
+/// of quilt, or an array of them.
+pub fn parse_people(value: &Value) -> Vec<ResolvedAuthorInfo> {
+    match value {
+        Value::String(name) if !name.trim().is_empty() => vec![ResolvedAuthorInfo {
+            name: name.trim().to_string(),
+            contact: None,
+        }],
+        Value::Array(people) => people.iter().flat_map(parse_people).collect(),
+        Value::Object(person) => match person.get("name").and_then(Value::as_str) {
+            Some(name) => vec![ResolvedAuthorInfo {
+                name: name.to_string(),
+                contact: person
+                    .get("contact")
+                    .and_then(|contact| serde_json::from_value(contact.clone()).ok()),
+            }],
+            None => person
+                .keys()
+                .map(|name| ResolvedAuthorInfo {
+                    name: name.clone(),
+                    contact: None,
+                })
+                .collect(),
+        },
+        _ => vec![],
+    }
+}
+
 /// Mods parser. It support `forge`, `fabric`, `quilt`, `rift`
 ///
 /// It will parse the mod using a parser that is suitable for the mod
@@ -111,3 +313,328 @@ pub fn parse_folder<S: AsRef<OsStr> + ?Sized>(folder: &S) -> Result<Vec<Resolved
     }
     Ok(result)
 }
+
+/// Like [`parse_folder`], but parse up to `concurrency` mods at the same time.
+///
+/// The results are in the same order as [`parse_folder`] reads the mods, and a mod that cannot be
+/// parsed doesn't stop the others.
+pub async fn parse_folder_parallel<S: AsRef<OsStr> + ?Sized>(
+    folder: &S,
+    concurrency: usize,
+) -> Result<Vec<Result<ResolvedMod>>> {
+    let paths = Path::new(folder)
+        .read_dir()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| !path.is_dir())
+        .collect::<Vec<_>>();
+    let results = futures::stream::iter(paths)
+        // zip reading is blocking, keep it off the async workers
+        .map(|path| async move {
+            match tokio::task::spawn_blocking(move || parse_mod(path)).await {
+                Ok(result) => result,
+                Err(err) => Err(err.into()),
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    Ok(results)
+}
+
+/// The number of mods in a mods folder
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ModSummary {
+    pub enabled: usize,
+
+    /// The mods disabled by renaming them to `<name>.jar.disabled`
+    pub disabled: usize,
+
+    /// The number of enabled and disabled mods for each loader
+    pub by_loader: HashMap<Loader, usize>,
+}
+
+/// The metadata file of each loader, in the order they are checked.
+///
+/// Quilt comes first since quilt mods may also ship a fabric.mod.json for compatibility.
+const LOADER_MARKERS: &[(&str, Loader)] = &[
+    ("quilt.mod.json", Loader::Quilt),
+    ("fabric.mod.json", Loader::Fabric),
+    ("META-INF/neoforge.mods.toml", Loader::NeoForge),
+    ("META-INF/mods.toml", Loader::Forge),
+    ("mcmod.info", Loader::Forge),
+];
+
+/// The metadata files of the mods whose loader is not a [`Loader`]
+const OTHER_MOD_MARKERS: &[&str] = &["riftmod.json", "litemod.json"];
+
+/// Tell the loader of a mod by its metadata file, like `fabric.mod.json`, without reading it
+pub fn detect_loader<P: AsRef<Path>>(path: P) -> Loader {
+    let archive = match std::fs::File::open(path).map(zip::ZipArchive::new) {
+        Ok(Ok(archive)) => archive,
+        _ => return Loader::Unknown,
+    };
+    LOADER_MARKERS
+        .iter()
+        .find(|(marker, _)| archive.file_names().any(|name| name == *marker))
+        .map_or(Loader::Unknown, |(_, loader)| *loader)
+}
+
+/// Whether the jar is a mod of any loader, rather than a library jar like a shaded dependency.
+///
+/// The error is for a file that is not a readable jar.
+pub fn is_mod<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let is_marker = |name: &str| {
+        LOADER_MARKERS.iter().any(|(marker, _)| *marker == name)
+            || OTHER_MOD_MARKERS.contains(&name)
+    };
+    if archive.file_names().any(is_marker) {
+        return Ok(true);
+    }
+    // the forge coremods and tweakers before 1.13 only declare themselves in the manifest
+    let mut manifest = String::new();
+    match archive.by_name("META-INF/MANIFEST.MF") {
+        Ok(mut file) => std::io::Read::read_to_string(&mut file, &mut manifest)?,
+        Err(_) => return Ok(false),
+    };
+    Ok(manifest
+        .lines()
+        .any(|line| line.starts_with("TweakClass:") || line.starts_with("FMLCorePlugin:")))
+}
+
+/// Count the enabled and disabled mods of a folder by loader, without parsing their metadata.
+///
+/// A folder that doesn't exist has no mods.
+pub fn summarize_folder<S: AsRef<OsStr> + ?Sized>(folder: &S) -> Result<ModSummary> {
+    let folder = Path::new(folder);
+    let mut summary = ModSummary::default();
+    if !folder.is_dir() {
+        return Ok(summary);
+    }
+    for entry in folder.read_dir()? {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name.ends_with(".jar") {
+            summary.enabled += 1;
+        } else if file_name.ends_with(".jar.disabled") {
+            summary.disabled += 1;
+        } else {
+            continue;
+        }
+        *summary.by_loader.entry(detect_loader(&path)).or_default() += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn write_forge_mod(path: &Path, mod_id: &str) {
+        let mut jar = ZipWriter::new(std::fs::File::create(path).unwrap());
+        jar.start_file("META-INF/mods.toml", FileOptions::default())
+            .unwrap();
+        write!(
+            jar,
+            "modLoader = \"javafml\"\n[[mods]]\nmodId = \"{mod_id}\"\nversion = \"1.0.0\"\n"
+        )
+        .unwrap();
+        jar.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parse_folder_parallel() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::copy(
+            "test-data/fabricMod/fabric-carpet-1.20.jar",
+            root.join("fabric-carpet-1.20.jar"),
+        )
+        .unwrap();
+        for i in 0..8 {
+            write_forge_mod(&root.join(format!("forge-mod-{i}.jar")), &format!("mod{i}"));
+        }
+
+        let serial = parse_folder(&root).unwrap();
+        let parallel = parse_folder_parallel(&root, 4).await.unwrap();
+        assert_eq!(parallel.len(), 9);
+        let parallel = parallel
+            .into_iter()
+            .map(|result| serde_json::to_value(result.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        let serial = serial
+            .into_iter()
+            .map(|resolved| serde_json::to_value(resolved).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parallel, serial);
+
+        std::fs::write(root.join("broken.jar"), "not a zip").unwrap();
+        let parallel = parse_folder_parallel(&root, 4).await.unwrap();
+        assert_eq!(parallel.len(), 10);
+        assert_eq!(parallel.iter().filter(|result| result.is_err()).count(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_version_range() {
+        let range = VersionRange::parse("[1.0,2.0),[3.0]").unwrap();
+        assert!(range.contains("1.5"));
+        assert!(!range.contains("2.0"));
+        assert!(range.contains("3.0"));
+        assert!(VersionRange::parse("*").unwrap().contains("9"));
+        // untrusted mods.toml input must fail, not panic
+        assert!(VersionRange::parse("[1.0]ü]").is_err());
+        assert!(VersionRange::parse("[1.0").is_err());
+    }
+
+    #[test]
+    fn test_is_mod() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let write_jar = |name: &str, files: &[(&str, &str)]| {
+            let path = root.join(name);
+            let mut jar = ZipWriter::new(std::fs::File::create(&path).unwrap());
+            for (file, content) in files {
+                jar.start_file(*file, FileOptions::default()).unwrap();
+                jar.write_all(content.as_bytes()).unwrap();
+            }
+            jar.finish().unwrap();
+            path
+        };
+        let library = write_jar(
+            "gson-2.10.jar",
+            &[
+                ("META-INF/MANIFEST.MF", "Manifest-Version: 1.0\n"),
+                ("com/google/gson/Gson.class", ""),
+            ],
+        );
+        assert!(!is_mod(library).unwrap());
+        let tweaker = write_jar(
+            "optifine.jar",
+            &[(
+                "META-INF/MANIFEST.MF",
+                "Manifest-Version: 1.0\nTweakClass: optifine.OptiFineTweaker\n",
+            )],
+        );
+        assert!(is_mod(tweaker).unwrap());
+        let forge = root.join("jei.jar");
+        write_forge_mod(&forge, "jei");
+        assert!(is_mod(forge).unwrap());
+        assert!(is_mod("test-data/fabricMod/fabric-carpet-1.20.jar").unwrap());
+
+        std::fs::write(root.join("broken.jar"), b"not a zip").unwrap();
+        assert!(is_mod(root.join("broken.jar")).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_summarize_folder() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let write_mod = |name: &str, marker: &str| {
+            let mut jar = ZipWriter::new(std::fs::File::create(root.join(name)).unwrap());
+            jar.start_file(marker, FileOptions::default()).unwrap();
+            jar.write_all(b"{}").unwrap();
+            jar.finish().unwrap();
+        };
+        write_mod("sodium.jar", "fabric.mod.json");
+        write_mod("lithium.jar.disabled", "fabric.mod.json");
+        write_mod("qsl.jar", "quilt.mod.json");
+        write_mod("create.jar", "META-INF/neoforge.mods.toml");
+        write_forge_mod(&root.join("jei.jar.disabled"), "jei");
+        std::fs::write(root.join("broken.jar"), b"not a zip").unwrap();
+        std::fs::write(root.join("README.txt"), b"").unwrap();
+
+        let summary = summarize_folder(&root).unwrap();
+        assert_eq!(summary.enabled, 4);
+        assert_eq!(summary.disabled, 2);
+        assert_eq!(summary.by_loader[&Loader::Fabric], 2);
+        assert_eq!(summary.by_loader[&Loader::Quilt], 1);
+        assert_eq!(summary.by_loader[&Loader::NeoForge], 1);
+        assert_eq!(summary.by_loader[&Loader::Forge], 1);
+        assert_eq!(summary.by_loader[&Loader::Unknown], 1);
+        assert_eq!(
+            summarize_folder(&root.join("missing")).unwrap(),
+            ModSummary::default()
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_people() {
+        let names = |value: Value| {
+            parse_people(&value)
+                .into_iter()
+                .map(|person| person.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(serde_json::json!(" gnembon ")), ["gnembon"]);
+        assert!(names(serde_json::json!("")).is_empty());
+        assert_eq!(names(serde_json::json!(["Alice", "Bob"])), ["Alice", "Bob"]);
+        assert_eq!(
+            names(serde_json::json!({ "Alice": "Owner", "Bob": "Contributor" })),
+            ["Alice", "Bob"]
+        );
+        assert_eq!(
+            names(serde_json::json!(["Alice", { "name": "Bob" }, 1, null])),
+            ["Alice", "Bob"]
+        );
+
+        let people = parse_people(&serde_json::json!({
+            "name": "Alice",
+            "contact": { "homepage": "https://example.com" }
+        }));
+        assert_eq!(people.len(), 1);
+        assert_eq!(
+            people[0].contact.as_ref().unwrap()["homepage"],
+            "https://example.com"
+        );
+        // a contact that is not a string map is dropped, not a panic
+        let people = parse_people(&serde_json::json!({ "name": "Bob", "contact": 1 }));
+        assert!(people[0].contact.is_none());
+    }
+
+    #[test]
+    fn test_parse_mixins() {
+        let mixin = |config: &str, environment: &str| MixinConfig {
+            config: config.to_string(),
+            environment: environment.to_string(),
+        };
+        assert_eq!(
+            parse_mixins(&serde_json::json!("carpet.mixins.json")),
+            [mixin("carpet.mixins.json", "*")]
+        );
+        assert_eq!(
+            parse_mixins(&serde_json::json!(["a.mixins.json", "b.mixins.json"])),
+            [mixin("a.mixins.json", "*"), mixin("b.mixins.json", "*")]
+        );
+        assert_eq!(
+            parse_mixins(&serde_json::json!([
+                "common.mixins.json",
+                { "config": "client.mixins.json", "environment": "client" },
+                { "config": "any.mixins.json" },
+                { "environment": "server" }
+            ])),
+            [
+                mixin("common.mixins.json", "*"),
+                mixin("client.mixins.json", "client"),
+                mixin("any.mixins.json", "*"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forge_contributors() {
+        let data: forge::ForgeModTOMLData =
+            toml::from_str("modLoader = \"javafml\"\nauthors = \"Alice\"\ncredits = \"Bob\"\n")
+                .unwrap();
+        let resolved = data.parse();
+        assert_eq!(resolved.authors[0].name, "Alice");
+        assert_eq!(resolved.contributors[0].name, "Bob");
+    }
+}
@@ -26,7 +26,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use zip::ZipArchive;
 
-use super::{Parse, ResolvedAuthorInfo, ResolvedDepends, ResolvedMod};
+use super::{parse_mixins, parse_people, Parse, ResolvedDepends, ResolvedMod};
 use crate::utils::unzip::filter_entries;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -68,8 +68,8 @@ pub struct QuiltModMetadata {
     pub name: Option<String>,
     pub description: Option<String>,
     pub contact: Option<HashMap<String, Value>>,
-    pub authors: Option<Vec<Value>>,
-    pub contributors: Option<Vec<Value>>,
+    pub authors: Option<Value>,
+    pub contributors: Option<Value>,
     pub license: Option<Value>,
     pub icon: Option<String>,
 
@@ -137,34 +137,6 @@ impl Parse for QuiltModMetadata {
         } else {
             None
         };
-        let mut parsed_authors = None;
-        if let Some(authors) = self.authors.to_owned() {
-            parsed_authors = Some(
-                authors
-                    .iter()
-                    .map(|author_info| {
-                        let author_info = author_info.to_owned();
-                        match author_info {
-                            Value::String(v) => ResolvedAuthorInfo {
-                                name: v,
-                                contact: None,
-                            },
-                            Value::Object(v) => ResolvedAuthorInfo {
-                                name: match v["name"].as_str() {
-                                    Some(v) => v.to_string(),
-                                    None => "".to_string(),
-                                },
-                                contact: serde_json::from_value(v["contact"].clone()).unwrap(),
-                            },
-                            _ => ResolvedAuthorInfo {
-                                name: "".to_string(),
-                                contact: None,
-                            },
-                        }
-                    })
-                    .collect::<Vec<ResolvedAuthorInfo>>(),
-            );
-        }
         ResolvedMod {
             name,
             description: self.description,
@@ -173,11 +145,15 @@ impl Parse for QuiltModMetadata {
                 minecraft: minecraft_depend,
                 mod_loader: loader_depend,
                 java: java_depend,
+                dependencies: vec![],
             },
-            authors: match parsed_authors {
-                Some(v) => v,
-                None => vec![],
-            },
+            authors: self.authors.as_ref().map(parse_people).unwrap_or_default(),
+            contributors: self
+                .contributors
+                .as_ref()
+                .map(parse_people)
+                .unwrap_or_default(),
+            mixins: self.mixins.as_ref().map(parse_mixins).unwrap_or_default(),
             license,
             icon: self.icon,
         }
@@ -21,6 +21,8 @@ use std::{ffi::OsStr, fs, io::Read, path::Path};
 use anyhow::Result;
 use zip::ZipArchive;
 
+use crate::core::folder::GameDataLocation;
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct PackMetadata {
     pub description: String,
@@ -45,3 +47,80 @@ pub fn get_metadata<S: AsRef<OsStr> + ?Sized>(s: &S) -> Result<PackMetadata> {
 }
 
 pub fn parse_resourcespack<P: AsRef<Path>>(_path: P) {}
+
+/// Write the selected resource packs into `options.txt`, in the given order.
+///
+/// `packs` are file names in the `resourcepacks` folder, the last one has the highest priority.
+/// Packs that are no longer selected are also removed from `incompatibleResourcePacks`.
+pub fn set_resource_packs(game_data: &GameDataLocation, packs: &[String]) -> Result<()> {
+    for pack in packs {
+        if !game_data.get_resource_pack(pack).exists() {
+            return Err(anyhow::anyhow!("resource pack {pack} is not found"));
+        }
+    }
+    let mut selected = vec!["vanilla".to_string()];
+    selected.extend(packs.iter().map(|pack| format!("file/{pack}")));
+
+    let options = match fs::read_to_string(&game_data.options) {
+        Ok(options) => options,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines: Vec<String> = options.lines().map(|line| line.to_string()).collect();
+
+    let incompatible: Vec<String> = lines
+        .iter()
+        .find_map(|line| line.strip_prefix("incompatibleResourcePacks:"))
+        .and_then(|value| serde_json::from_str::<Vec<String>>(value).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|pack| selected.contains(pack))
+        .collect();
+
+    for (key, value) in [
+        ("resourcePacks", serde_json::to_string(&selected)?),
+        (
+            "incompatibleResourcePacks",
+            serde_json::to_string(&incompatible)?,
+        ),
+    ] {
+        let prefix = format!("{key}:");
+        let line = format!("{prefix}{value}");
+        match lines.iter_mut().find(|l| l.starts_with(&prefix)) {
+            Some(l) => *l = line,
+            None => lines.push(line),
+        }
+    }
+
+    fs::write(&game_data.options, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_set_resource_packs() {
+        let root = crate::utils::download::test::temp_dir();
+        let game_data = GameDataLocation::new(&root);
+        fs::create_dir_all(&game_data.resourcepacks).unwrap();
+        fs::write(game_data.get_resource_pack("a.zip"), "").unwrap();
+        fs::write(game_data.get_resource_pack("b.zip"), "").unwrap();
+        fs::write(
+            &game_data.options,
+            "lang:en_us\nresourcePacks:[\"vanilla\",\"file/old.zip\"]\nincompatibleResourcePacks:[\"file/old.zip\",\"file/a.zip\"]\n",
+        )
+        .unwrap();
+
+        set_resource_packs(&game_data, &["b.zip".to_string(), "a.zip".to_string()]).unwrap();
+        let options = fs::read_to_string(&game_data.options).unwrap();
+        assert_eq!(
+            options,
+            "lang:en_us\nresourcePacks:[\"vanilla\",\"file/b.zip\",\"file/a.zip\"]\nincompatibleResourcePacks:[\"file/a.zip\"]\n"
+        );
+
+        assert!(set_resource_packs(&game_data, &["missing.zip".to_string()]).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
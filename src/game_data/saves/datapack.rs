@@ -0,0 +1,210 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Datapacks of a world, in `saves/<world>/datapacks`
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::ZipArchive;
+
+use super::level::DataPacksOptions;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Datapack {
+    /// The file or folder name in the `datapacks` folder
+    pub name: String,
+    pub pack_format: u32,
+
+    /// A string or a text component
+    pub description: Value,
+
+    /// `pack.png` as a data url
+    pub icon: Option<String>,
+
+    /// Whether the datapack is enabled in `level.dat`. None if the world has not loaded it yet.
+    pub enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct PackMcmeta {
+    pack: PackSection,
+}
+
+#[derive(Deserialize)]
+struct PackSection {
+    pack_format: u32,
+    #[serde(default)]
+    description: Value,
+}
+
+#[derive(Deserialize)]
+struct LevelDataPacks {
+    #[serde(rename = "Data")]
+    data: LevelDataPacksData,
+}
+
+#[derive(Deserialize)]
+struct LevelDataPacksData {
+    #[serde(rename = "DataPacks")]
+    data_packs: Option<DataPacksOptions>,
+}
+
+/// Read `pack.mcmeta` and `pack.png` of a datapack folder or zip
+pub fn get_datapack<P: AsRef<Path>>(path: P) -> Result<Datapack> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .ok_or(anyhow!("{} is not a datapack", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let (mcmeta, icon) = if path.is_dir() {
+        (
+            fs::read(path.join("pack.mcmeta"))?,
+            fs::read(path.join("pack.png")).ok(),
+        )
+    } else {
+        let mut archive = ZipArchive::new(fs::File::open(path)?)?;
+        let mut read = |name: &str| -> Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut buf)?;
+            Ok(buf)
+        };
+        (read("pack.mcmeta")?, read("pack.png").ok())
+    };
+    let mcmeta: PackMcmeta = serde_json::from_slice(&mcmeta)?;
+    Ok(Datapack {
+        name,
+        pack_format: mcmeta.pack.pack_format,
+        description: mcmeta.pack.description,
+        icon: icon.map(|icon| {
+            format!(
+                "data:image/png;base64,{}",
+                general_purpose::STANDARD.encode(icon)
+            )
+        }),
+        enabled: None,
+    })
+}
+
+/// Get the datapacks of a world, with their enabled state from `level.dat`
+///
+/// The entries that are not valid datapacks are skipped.
+pub fn get_datapacks<P: AsRef<Path>>(world_path: P) -> Result<Vec<Datapack>> {
+    let world_path = world_path.as_ref();
+    let options = fs::File::open(world_path.join("level.dat"))
+        .ok()
+        .and_then(|file| nbt::from_gzip_reader::<_, LevelDataPacks>(file).ok())
+        .and_then(|level| level.data.data_packs);
+    let folder = world_path.join("datapacks");
+    if !folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut datapacks = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let mut datapack = match entry.map(|entry| get_datapack(entry.path())) {
+            Ok(Ok(datapack)) => datapack,
+            _ => continue,
+        };
+        let id = format!("file/{}", datapack.name);
+        datapack.enabled = options.as_ref().and_then(|options| {
+            if options.enabled.contains(&id) {
+                Some(true)
+            } else if options.disabled.contains(&id) {
+                Some(false)
+            } else {
+                None
+            }
+        });
+        datapacks.push(datapack);
+    }
+    datapacks.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(datapacks)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    const MCMETA: &str = r#"{ "pack": { "pack_format": 15, "description": "Custom recipes" } }"#;
+
+    #[test]
+    fn test_get_datapacks() {
+        let world = crate::utils::download::test::temp_dir();
+        let folder_pack = world.join("datapacks").join("recipes");
+        fs::create_dir_all(&folder_pack).unwrap();
+        fs::write(folder_pack.join("pack.mcmeta"), MCMETA).unwrap();
+        fs::write(folder_pack.join("pack.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+        let mut zip =
+            ZipWriter::new(fs::File::create(world.join("datapacks").join("loot.zip")).unwrap());
+        zip.start_file("pack.mcmeta", FileOptions::default())
+            .unwrap();
+        zip.write_all(br#"{ "pack": { "pack_format": 26, "description": { "text": "Loot", "color": "gold" } } }"#)
+            .unwrap();
+        zip.finish().unwrap();
+        fs::write(world.join("datapacks").join("notes.txt"), "").unwrap();
+
+        let list = |names: &[&str]| {
+            nbt::Value::List(
+                names
+                    .iter()
+                    .map(|name| nbt::Value::String(name.to_string()))
+                    .collect(),
+            )
+        };
+        let data_packs = HashMap::from([
+            ("Enabled".to_string(), list(&["vanilla", "file/recipes"])),
+            ("Disabled".to_string(), list(&["file/loot.zip"])),
+        ]);
+        let data = HashMap::from([("DataPacks".to_string(), nbt::Value::Compound(data_packs))]);
+        let mut level = nbt::Blob::new();
+        level.insert("Data", nbt::Value::Compound(data)).unwrap();
+        level
+            .to_gzip_writer(&mut fs::File::create(world.join("level.dat")).unwrap())
+            .unwrap();
+
+        let datapacks = get_datapacks(&world).unwrap();
+        assert_eq!(datapacks.len(), 2);
+        let (loot, recipes) = (&datapacks[0], &datapacks[1]);
+        assert_eq!(loot.name, "loot.zip");
+        assert_eq!(loot.pack_format, 26);
+        assert_eq!(loot.description["text"], "Loot");
+        assert_eq!(loot.icon, None);
+        assert_eq!(loot.enabled, Some(false));
+        assert_eq!(recipes.name, "recipes");
+        assert_eq!(recipes.description, "Custom recipes");
+        assert_eq!(
+            recipes.icon.as_deref(),
+            Some("data:image/png;base64,iVBORw==")
+        );
+        assert_eq!(recipes.enabled, Some(true));
+        fs::remove_dir_all(world).unwrap();
+    }
+}
@@ -16,6 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod datapack;
 pub mod gamerule;
 pub mod player;
 pub mod level;
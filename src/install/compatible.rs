@@ -0,0 +1,180 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Hide the loader versions that cannot run on the installed java
+
+use crate::core::{
+    version::{infer_java_version, MinecraftVersion},
+    JavaExec,
+};
+
+use super::{
+    fabric::{FabricArtifactVersion, FabricLoaderArtifact},
+    forge::version_list::ForgeVersionListItem,
+    optifine::version_list::OptifineVersionListItem,
+};
+
+/// The java major versions a loader version can run on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JavaRequirement {
+    pub min: u32,
+    pub max: Option<u32>,
+}
+
+impl JavaRequirement {
+    /// The requirement of the vanilla game
+    pub fn for_minecraft(minecraft_version: &str) -> Self {
+        Self {
+            min: infer_java_version(minecraft_version).major_version as u32,
+            max: None,
+        }
+    }
+
+    /// The requirement of a loader that starts the game with launchwrapper, which breaks on java 9+
+    /// before 1.13
+    pub fn for_launchwrapper(minecraft_version: &str) -> Self {
+        let max = match minecraft_version.parse() {
+            Ok(MinecraftVersion::Release(1, minor, _)) if minor < 13 => Some(8),
+            _ => None,
+        };
+        Self {
+            max,
+            ..Self::for_minecraft(minecraft_version)
+        }
+    }
+
+    pub fn accepts(&self, java_major: u32) -> bool {
+        java_major >= self.min && self.max.is_none_or(|max| java_major <= max)
+    }
+}
+
+/// A version in a loader version list
+pub trait JavaCompatible {
+    /// The java this version needs, None if it doesn't depend on the game version
+    fn java_requirement(&self) -> Option<JavaRequirement>;
+}
+
+impl JavaCompatible for ForgeVersionListItem {
+    fn java_requirement(&self) -> Option<JavaRequirement> {
+        Some(JavaRequirement::for_launchwrapper(&self.mcversion))
+    }
+}
+
+impl JavaCompatible for OptifineVersionListItem {
+    fn java_requirement(&self) -> Option<JavaRequirement> {
+        Some(JavaRequirement::for_launchwrapper(&self.mcversion))
+    }
+}
+
+impl JavaCompatible for FabricArtifactVersion {
+    fn java_requirement(&self) -> Option<JavaRequirement> {
+        self.game_version
+            .as_deref()
+            .map(JavaRequirement::for_minecraft)
+    }
+}
+
+impl JavaCompatible for FabricLoaderArtifact {
+    fn java_requirement(&self) -> Option<JavaRequirement> {
+        // the intermediary version is the game version
+        Some(JavaRequirement::for_minecraft(&self.intermediary.version))
+    }
+}
+
+/// Keep the versions that can run on one of the java major versions, see [`java_majors`]
+pub fn filter_compatible<T: JavaCompatible>(versions: Vec<T>, java_candidates: &[u32]) -> Vec<T> {
+    versions
+        .into_iter()
+        .filter(|version| match version.java_requirement() {
+            Some(requirement) => java_candidates
+                .iter()
+                .any(|major| requirement.accepts(*major)),
+            None => true,
+        })
+        .collect()
+}
+
+/// Read the major versions of the java candidates, the ones that cannot run are skipped
+pub async fn java_majors(java_candidates: &[JavaExec]) -> Vec<u32> {
+    let mut majors = Vec::new();
+    for java in java_candidates {
+        if let Ok(major) = java.major_version().await {
+            majors.push(major);
+        }
+    }
+    majors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filter_compatible() {
+        let forge = |mcversion: &str| -> ForgeVersionListItem {
+            serde_json::from_value(serde_json::json!({
+                "_id": "id",
+                "build": 1,
+                "__v": 0,
+                "version": "1.0",
+                "modified": "2023-01-01T00:00:00.000Z",
+                "mcversion": mcversion,
+                "files": [],
+                "branch": null,
+            }))
+            .unwrap()
+        };
+        let versions = vec![
+            forge("1.12.2"),
+            forge("1.16.5"),
+            forge("1.18.2"),
+            forge("1.20.1"),
+        ];
+        let mcversions = |versions: Vec<ForgeVersionListItem>| {
+            versions
+                .into_iter()
+                .map(|version| version.mcversion)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            mcversions(filter_compatible(versions.clone(), &[8])),
+            ["1.12.2", "1.16.5"]
+        );
+        assert_eq!(
+            mcversions(filter_compatible(versions.clone(), &[17])),
+            ["1.16.5", "1.18.2", "1.20.1"]
+        );
+        assert_eq!(
+            mcversions(filter_compatible(versions.clone(), &[8, 17])).len(),
+            4
+        );
+        assert!(filter_compatible(versions, &[]).is_empty());
+
+        let yarn = |game_version: Option<&str>| FabricArtifactVersion {
+            game_version: game_version.map(String::from),
+            separator: None,
+            build: None,
+            maven: String::new(),
+            version: String::new(),
+            stable: true,
+        };
+        let versions = vec![yarn(Some("1.16.5")), yarn(Some("1.19.4")), yarn(None)];
+        assert_eq!(filter_compatible(versions, &[8]).len(), 2);
+    }
+}
@@ -23,6 +23,8 @@ use crate::core::folder::MinecraftLocation;
 
 use super::*;
 
+pub(super) const FABRIC_MAVEN: &str = "https://maven.fabricmc.net/";
+
 /// Generate the fabric version JSON file to disk according to yarn and loader.
 ///
 /// ### Arguments
@@ -59,63 +61,19 @@ pub async fn install_fabric_version_json(
         },
         Some(options) => options,
     };
-    let yarn: Option<String>;
+    let yarn = match options.yarn_version {
+        Some(YarnVersion::String(yarn_version)) => Some(yarn_version),
+        Some(YarnVersion::FabricArtifactVersion(yarn_version)) => Some(yarn_version.version),
+        None => None,
+    };
     let side = options.size.unwrap_or(FabricInstallSide::Client);
-    let mut id = options.version_id;
-    let mut minecraft_version = "".to_string();
-
-    match options.yarn_version {
-        Some(yarn_version) => match yarn_version {
-            YarnVersion::String(yarn_version) => {
-                yarn = Some(yarn_version);
-            }
-            YarnVersion::FabricArtifactVersion(yarn_version) => {
-                yarn = Some(yarn_version.version);
-            }
-        },
-        None => {
-            yarn = None;
-            minecraft_version = loader.intermediary.version;
-        }
-    }
-    if let None = id {
-        if let Some(_) = yarn.clone() {
-            id = Some(format!(
-                "{}-loader{}",
-                minecraft_version, loader.loader.version
-            ));
-        } else {
-            id = Some(format!(
-                "{}-fabric{}",
-                minecraft_version, loader.loader.version
-            ))
-        }
-    }
-    let mut libraries = vec![
-        LauncherMetaLibrariesItems {
-            name: Some(loader.loader.maven.clone()),
-            url: Some(String::from("https://maven.fabricmc.net/")),
-        },
-        LauncherMetaLibrariesItems {
-            name: Some(loader.intermediary.maven.clone()),
-            url: Some(String::from("https://maven.fabricmc.net/")),
-        },
-    ];
-    if let Some(yarn) = yarn.clone() {
-        libraries.push(LauncherMetaLibrariesItems {
-            name: Some(format!("net.fabricmc:yarn:{}", yarn)),
-            url: Some(String::from("https://maven.fabricmc.net/")),
-        });
-    }
-    libraries.extend(loader.launcher_meta.libraries.common.iter().cloned());
-    match side {
-        FabricInstallSide::Client => {
-            libraries.extend(loader.launcher_meta.libraries.client.iter().cloned())
-        }
-        FabricInstallSide::Server => {
-            libraries.extend(loader.launcher_meta.libraries.server.iter().cloned())
-        }
-    }
+    let (id, inherits_from) = resolve_version_id(
+        &loader,
+        yarn.as_deref(),
+        options.version_id,
+        options.inherits_from,
+    );
+    let libraries = resolve_libraries(&loader, yarn.as_deref(), &side);
     let main_class = match side {
         FabricInstallSide::Client => loader.launcher_meta.main_class["client"]
             .as_str()
@@ -126,9 +84,8 @@ pub async fn install_fabric_version_json(
             .unwrap_or(loader.launcher_meta.main_class.as_str().unwrap_or(""))
             .to_string(),
     };
-    let inherits_from = options.inherits_from.unwrap_or(minecraft_version);
 
-    let json_file_path = minecraft_location.get_version_json(&id.clone().unwrap());
+    let json_file_path = minecraft_location.get_version_json(&id);
     fs::create_dir_all(json_file_path.parent().unwrap()).await?;
     if let Ok(metadata) = fs::metadata(&json_file_path).await {
         if metadata.is_file() {
@@ -143,7 +100,7 @@ pub async fn install_fabric_version_json(
         id: String,
         inherits_from: String,
         main_class: String,
-        libraries: String,
+        libraries: Vec<LauncherMetaLibrariesItems>,
         arguments: FabricVersionJSONArg,
         release_time: String,
         time: String,
@@ -154,10 +111,10 @@ pub async fn install_fabric_version_json(
         jvm: Vec<i32>,
     }
     let version_json = FabricVersionJSON {
-        id: id.clone().unwrap_or("".to_string()),
+        id: id.clone(),
         inherits_from,
         main_class,
-        libraries: serde_json::to_string(&libraries).unwrap_or("".to_string()),
+        libraries,
         arguments: FabricVersionJSONArg {
             game: vec![],
             jvm: vec![],
@@ -170,12 +127,146 @@ pub async fn install_fabric_version_json(
         .to_string();
     tokio::fs::write(json_file_path, json_data).await?;
 
-    Ok(id.unwrap_or("".to_string()))
+    Ok(id)
 }
 
-// #[tokio::test]
-// async fn test() {
-//     let artifact = FabricLoaderArtifact::new("1.19.4", "0.1.0.48").await;
-//     let location = MinecraftLocation::new("test");
-//     install_fabric(artifact, location, None).await.unwrap();
-// }
+/// Resolve the libraries of the fabric version json.
+///
+/// Every library carries its own maven url, the libraries from launcher meta without url are
+/// resolved from the fabric maven.
+fn resolve_libraries(
+    loader: &FabricLoaderArtifact,
+    yarn: Option<&str>,
+    side: &FabricInstallSide,
+) -> Vec<LauncherMetaLibrariesItems> {
+    let mut libraries = vec![
+        LauncherMetaLibrariesItems {
+            name: Some(loader.loader.maven.clone()),
+            url: Some(FABRIC_MAVEN.to_string()),
+        },
+        LauncherMetaLibrariesItems {
+            name: Some(loader.intermediary.maven.clone()),
+            url: Some(FABRIC_MAVEN.to_string()),
+        },
+    ];
+    if let Some(yarn) = yarn {
+        libraries.push(LauncherMetaLibrariesItems {
+            name: Some(format!("net.fabricmc:yarn:{}", yarn)),
+            url: Some(FABRIC_MAVEN.to_string()),
+        });
+    }
+    let launcher_meta_libraries = &loader.launcher_meta.libraries;
+    let side_libraries = match side {
+        FabricInstallSide::Client => &launcher_meta_libraries.client,
+        FabricInstallSide::Server => &launcher_meta_libraries.server,
+    };
+    libraries.extend(
+        launcher_meta_libraries
+            .common
+            .iter()
+            .chain(side_libraries)
+            .map(|library| LauncherMetaLibrariesItems {
+                name: library.name.clone(),
+                url: Some(library.url.clone().unwrap_or(FABRIC_MAVEN.to_string())),
+            }),
+    );
+    libraries
+}
+
+/// Resolve the version id and `inheritsFrom` of the fabric version json.
+///
+/// Without yarn, the id looks like `1.19.4-fabric-0.14.21`, otherwise `1.19.4-loader0.14.21`.
+/// The `inheritsFrom` is the vanilla version unless it is overridden.
+fn resolve_version_id(
+    loader: &FabricLoaderArtifact,
+    yarn: Option<&str>,
+    version_id: Option<String>,
+    inherits_from: Option<String>,
+) -> (String, String) {
+    let minecraft_version = match yarn {
+        // yarn version looks like `1.19.4+build.1`
+        Some(yarn) => yarn.split('+').next().unwrap_or(yarn).to_string(),
+        None => loader.intermediary.version.clone(),
+    };
+    let id = version_id.unwrap_or(match yarn {
+        Some(_) => format!("{minecraft_version}-loader{}", loader.loader.version),
+        None => format!("{minecraft_version}-fabric-{}", loader.loader.version),
+    });
+    (id, inherits_from.unwrap_or(minecraft_version))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::install::DownloadSource;
+
+    fn loader_artifact() -> FabricLoaderArtifact {
+        serde_json::from_str(
+            r#"{
+                "loader": { "separator": ".", "build": 21, "maven": "net.fabricmc:fabric-loader:0.14.21", "version": "0.14.21", "stable": true },
+                "intermediary": { "maven": "net.fabricmc:intermediary:1.19.4", "version": "1.19.4", "stable": true },
+                "launcherMeta": {
+                    "version": 1,
+                    "libraries": { "client": [], "common": [], "server": [] },
+                    "mainClass": { "client": "net.fabricmc.loader.impl.launch.knot.KnotClient", "server": "net.fabricmc.loader.impl.launch.knot.KnotServer" }
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_libraries() {
+        let mut loader = loader_artifact();
+        loader.launcher_meta.libraries.common = vec![
+            LauncherMetaLibrariesItems {
+                name: Some("org.ow2.asm:asm:9.5".to_string()),
+                url: Some("https://maven.example.com/".to_string()),
+            },
+            LauncherMetaLibrariesItems {
+                name: Some("net.fabricmc:sponge-mixin:0.12.5+mixin.0.8.5".to_string()),
+                url: None,
+            },
+        ];
+        let libraries = resolve_libraries(&loader, None, &FabricInstallSide::Client);
+        let urls = libraries
+            .iter()
+            .map(|library| library.url.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                FABRIC_MAVEN,
+                FABRIC_MAVEN,
+                "https://maven.example.com/",
+                FABRIC_MAVEN
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_version_id() {
+        let loader = loader_artifact();
+        assert_eq!(
+            resolve_version_id(&loader, None, None, None),
+            ("1.19.4-fabric-0.14.21".to_string(), "1.19.4".to_string())
+        );
+        assert_eq!(
+            resolve_version_id(&loader, Some("1.19.4+build.1"), None, None),
+            ("1.19.4-loader0.14.21".to_string(), "1.19.4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_installer_url() {
+        let loader = loader_artifact();
+        assert_eq!(
+            loader.installer_url(&DownloadSource::Official),
+            "https://meta.fabricmc.net/v2/versions/loader/1.19.4/0.14.21/profile/json"
+        );
+        assert_eq!(
+            loader.installer_url(&DownloadSource::BmclApi),
+            "https://bmclapi2.bangbang93.com/fabric-meta/v2/versions/loader/1.19.4/0.14.21/profile/json"
+        );
+    }
+}
@@ -21,6 +21,7 @@ use serde_json::Value;
 
 pub mod install;
 pub mod version_list;
+pub mod yarn;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
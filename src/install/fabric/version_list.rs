@@ -19,66 +19,73 @@
 use anyhow::Result;
 
 use super::*;
+use crate::install::DownloadSource;
+use crate::utils::cache::fetch_json;
+
+/// The fabric meta base url of the download source, mirrors keep the same paths
+fn meta_base(source: &DownloadSource) -> &str {
+    match source {
+        DownloadSource::BmclApi => "https://bmclapi2.bangbang93.com/fabric-meta",
+        DownloadSource::Mcbbs => "https://download.mcbbs.net/fabric-meta",
+        DownloadSource::Official | DownloadSource::Custom { .. } => "https://meta.fabricmc.net",
+    }
+}
 
 impl FabricArtifacts {
     /// get fabric artifacts
     pub async fn new() -> Result<Self> {
-        Ok(reqwest::get("https://meta.fabricmc.net/v2/versions")
-            .await?
-            .json()
-            .await?)
+        fetch_json("https://meta.fabricmc.net/v2/versions").await
     }
 }
 
 impl YarnArtifactList {
     /// get yarn artifacts
     pub async fn new() -> Result<Self> {
-        Ok(reqwest::get("https://meta.fabricmc.net/v2/versions/yarn")
-            .await?
-            .json()
-            .await?)
+        fetch_json("https://meta.fabricmc.net/v2/versions/yarn").await
     }
     /// get the yarn of the specified minecraft version
     pub async fn from_mcversion(mcversion: &str) -> Result<Self> {
-        Ok(reqwest::get(format!(
+        fetch_json(&format!(
             "https://meta.fabricmc.net/v2/versions/yarn/{}",
             mcversion
         ))
-        .await?
-        .json()
-        .await?)
+        .await
     }
 }
 
 impl LoaderArtifactList {
     /// get loader artifacts
     pub async fn new() -> Result<Self> {
-        Ok(reqwest::get("https://meta.fabricmc.net/v2/versions/loader")
-            .await?
-            .json()
-            .await?)
+        fetch_json("https://meta.fabricmc.net/v2/versions/loader").await
     }
     /// get the loader of the specified minecraft version
     pub async fn from_mcversion(mcversion: &str) -> Result<Self> {
-        Ok(reqwest::get(format!(
+        fetch_json(&format!(
             "https://meta.fabricmc.net/v2/versions/loader/{}",
             mcversion
         ))
-        .await?
-        .json()
-        .await?)
+        .await
     }
 }
 
 impl FabricLoaderArtifact {
     /// get fabric loader artifact
     pub async fn new(mcversion: &str, loader: &str) -> Result<Self> {
-        Ok(reqwest::get(format!(
+        fetch_json(&format!(
             "https://meta.fabricmc.net/v2/versions/loader/{}/{}",
             mcversion, loader
         ))
-        .await?
-        .json()
-        .await?)
+        .await
+    }
+
+    /// The url of the launcher profile json of this loader, which is what the fabric installer
+    /// writes to the versions folder
+    pub fn installer_url(&self, source: &DownloadSource) -> String {
+        format!(
+            "{}/v2/versions/loader/{}/{}/profile/json",
+            meta_base(source),
+            self.intermediary.version,
+            self.loader.version
+        )
     }
 }
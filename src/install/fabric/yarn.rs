@@ -0,0 +1,129 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Download the yarn mappings of a Minecraft version, for decompiling the game
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use super::{install::FABRIC_MAVEN, FabricArtifactVersion, YarnArtifactList};
+use crate::{
+    core::{
+        version::{join_url, LibraryInfo},
+        Download,
+    },
+    install::DownloadSource,
+    utils::download::Downloader,
+};
+
+/// The url of the mappings jar of a yarn build, the `v2` one with the tiny v2 mappings, from the
+/// fabric maven or the maven of `source`
+pub fn yarn_url(yarn: &FabricArtifactVersion, source: &DownloadSource) -> String {
+    let library = LibraryInfo::from_value(&serde_json::json!({
+        "name": format!("{}:v2", yarn.maven)
+    }));
+    join_url(
+        source.libraries_base().unwrap_or(FABRIC_MAVEN),
+        &library.path,
+    )
+}
+
+/// The newest yarn build in the list
+pub fn latest_yarn(list: YarnArtifactList) -> Option<FabricArtifactVersion> {
+    list.0.into_iter().max_by_key(|yarn| yarn.build)
+}
+
+/// Download the mappings jar of the newest yarn build of the Minecraft version into `dest`, and
+/// return its path with the downloader. A jar already downloaded there is reused.
+pub async fn download_yarn<P: AsRef<Path>>(
+    mcversion: &str,
+    dest: P,
+    source: &DownloadSource,
+    downloader: &dyn Downloader,
+) -> Result<PathBuf> {
+    let yarn = latest_yarn(YarnArtifactList::from_mcversion(mcversion).await?)
+        .ok_or(anyhow!("No yarn mappings for {mcversion}"))?;
+    let file = dest.as_ref().join(format!("yarn-{}-v2.jar", yarn.version));
+    let cached = std::fs::File::open(&file)
+        .map_err(anyhow::Error::from)
+        .and_then(|jar| Ok(zip::ZipArchive::new(jar)?));
+    if cached.is_err() {
+        downloader
+            .fetch(&Download {
+                url: yarn_url(&yarn, source),
+                file: file.clone(),
+                sha1: None,
+                size: None,
+            })
+            .await?;
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn yarn(build: usize) -> FabricArtifactVersion {
+        FabricArtifactVersion {
+            game_version: Some("1.20.1".to_string()),
+            separator: Some("+build.".to_string()),
+            build: Some(build),
+            maven: format!("net.fabricmc:yarn:1.20.1+build.{build}"),
+            version: format!("1.20.1+build.{build}"),
+            stable: true,
+        }
+    }
+
+    #[test]
+    fn test_yarn_url() {
+        assert_eq!(
+            yarn_url(&yarn(10), &DownloadSource::Official),
+            "https://maven.fabricmc.net/net/fabricmc/yarn/1.20.1+build.10/yarn-1.20.1+build.10-v2.jar"
+        );
+        assert_eq!(
+            yarn_url(&yarn(10), &DownloadSource::BmclApi),
+            "https://bmclapi2.bangbang93.com/maven/net/fabricmc/yarn/1.20.1+build.10/yarn-1.20.1+build.10-v2.jar"
+        );
+        let latest = latest_yarn(YarnArtifactList(vec![yarn(9), yarn(10), yarn(2)])).unwrap();
+        assert_eq!(latest.version, "1.20.1+build.10");
+    }
+
+    #[tokio::test]
+    #[ignore = "downloads from the fabric maven"]
+    async fn test_download_yarn() {
+        use crate::utils::download::HttpDownloader;
+
+        let dest = crate::utils::download::test::temp_dir();
+        let jar = download_yarn("1.20.1", &dest, &DownloadSource::Official, &HttpDownloader)
+            .await
+            .unwrap();
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&jar).unwrap()).unwrap();
+        assert!(archive.by_name("mappings/mappings.tiny").is_ok());
+        let modified = std::fs::metadata(&jar).unwrap().modified().unwrap();
+        let cached = download_yarn("1.20.1", &dest, &DownloadSource::Official, &HttpDownloader)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::metadata(cached).unwrap().modified().unwrap(),
+            modified
+        );
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+}
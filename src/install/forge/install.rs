@@ -21,140 +21,214 @@ use std::{
     io::{self, Read},
     path::{Path, PathBuf},
     str::FromStr,
-    thread,
-    time::Duration,
+    sync::Arc,
 };
 
 use anyhow::Result;
 use regex::Regex;
-use reqwest::Response;
-use tokio::io::AsyncWriteExt;
 use zip::ZipArchive;
 
 use crate::{
     core::{
         folder::MinecraftLocation,
-        version::{LibraryDownload, MinecraftVersion},
-        HTTP_CLIENT,
+        http_client,
+        task::TaskEventListeners,
+        version::{resolve_libraries, MinecraftVersion, Side},
+        Download, PlatformInfo,
     },
     install::forge::{
         install_profile::{InstallProfile, InstallProfileLegacy},
         legacy_install::install_legacy_forge_from_zip,
         new_install::unpack_forge_installer,
     },
-    utils::unzip::filter_entries,
+    utils::{
+        disk::{check_disk_space, download_size},
+        download::{
+            download_files_with, fetch_verified, ChecksumMismatch, Downloader, DEFAULT_CONCURRENCY,
+        },
+        unzip::filter_entries,
+    },
 };
 
 use super::*;
 
 // const DEFAULT_FORGE_MAVEN: &str = "https://files.minecraftforge.net/maven";
 
+/// The downloaded forge installer doesn't match the sha1 of the required version
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorruptInstaller {
+    pub version: String,
+    pub installer: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for CorruptInstaller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The forge {} installer {} is corrupt, its sha1 is {} but {} is expected",
+            self.version,
+            self.installer.display(),
+            self.actual,
+            self.expected
+        )
+    }
+}
+
+impl std::error::Error for CorruptInstaller {}
+
 async fn download_forge_installer(
     required_version: RequiredVersion,
     minecraft: &MinecraftLocation,
-    _options: &Option<InstallForgeOptions>,
-) -> Result<(String, Response)> {
-    let url = find_download_link(&required_version.version, &required_version.mcversion).await?;
-    let path = url.replace("https://maven.minecraftforge.net/", "");
-    // let forge_maven_path = path.replace("/maven", "").replace("maven", "");
-    let sha1 = match &required_version.installer {
-        Some(installer) => match &installer.sha1 {
-            Some(sha1) => Some(String::from(sha1)),
-            None => None,
-        },
-        None => None,
+    options: &Option<InstallForgeOptions>,
+    downloader: &dyn Downloader,
+) -> Result<String> {
+    let (url, path, sha1) = match required_version.installer {
+        // the installer path is known, download it from the maven host
+        Some(RequiredVersionInstaller { sha1, path }) => {
+            let maven = options
+                .as_ref()
+                .and_then(|options| options.maven_host.as_ref())
+                .and_then(|hosts| hosts.first())
+                .map(String::as_str)
+                .unwrap_or(version_list::FORGE_MAVEN);
+            let path = path.trim_start_matches('/').to_string();
+            let url = format!("{}/{path}", maven.trim_end_matches('/'));
+            (url, path, sha1)
+        }
+        None => {
+            let url =
+                find_download_link(&required_version.version, &required_version.mcversion).await?;
+            let path = url.replace("https://maven.minecraftforge.net/", "");
+            (url, path, None)
+        }
     };
-    let library = LibraryDownload {
+    let download = Download {
+        file: minecraft.get_library_by_path(path),
         url,
-        path,
-        size: None,
         sha1,
+        size: None,
     };
-    let file_path = minecraft
-        .get_library_by_path(&library.path)
-        .to_str()
-        .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))
-        .unwrap()
-        .to_string();
-    // let response = download(Download {
-    //     url: library.url,
-    //     file: file_path.clone(),
-    //     sha1: None,
-    // })
-    // .await;
-    let mut response = HTTP_CLIENT.get(library.url).send().await?;
-    let mut file = tokio::fs::File::create(&file_path).await?;
-    while let Some(chunk) = response.chunk().await.unwrap() {
-        file.write_all(&chunk).await.unwrap();
-    }
-    Ok((file_path, response))
+    // a corrupt installer is removed by fetch_verified
+    fetch_verified(downloader, &download, true)
+        .await
+        .map_err(|err| match err.downcast::<ChecksumMismatch>() {
+            Ok(mismatch) => CorruptInstaller {
+                version: required_version.version,
+                installer: mismatch.file,
+                expected: mismatch.expected,
+                actual: mismatch.actual,
+            }
+            .into(),
+            Err(err) => err,
+        })?;
+    Ok(download.file.to_string_lossy().to_string())
 }
 
+/// The names of the argument files in the forge installer, by the field they fill. Forge
+/// versions name them differently, the first one found is used.
+const UNIX_ARGS: [&str; 4] = [
+    "data/unix_args.txt",
+    "data/unix_args",
+    "data/unix_jvm_args.txt",
+    "data/unix_jvm_args",
+];
+const USER_JVM_ARGS: [&str; 2] = ["data/user_jvm_args.txt", "data/user_jvm_args"];
+const WIN_ARGS: [&str; 4] = [
+    "data/win_args.txt",
+    "data/win_args",
+    "data/win_jvm_args.txt",
+    "data/win_jvm_args",
+];
+
 async fn walk_forge_installer_entries<R: Read + io::Seek>(
     mut zip: ZipArchive<R>,
     forge_version: &str,
 ) -> ForgeInstallerEntries {
-    let entries = vec![
-        format!(
-            "maven/net/minecraftforge/forge/{}/forge-{}.jar",
-            forge_version, forge_version
-        ),
-        format!(
-            "maven/net/minecraftforge/forge/{}/forge-{}-universal.jar",
-            forge_version, forge_version
-        ),
+    let forge_jar = format!(
+        "maven/net/minecraftforge/forge/{}/forge-{}.jar",
+        forge_version, forge_version
+    );
+    let forge_universal_jar = format!(
+        "maven/net/minecraftforge/forge/{}/forge-{}-universal.jar",
+        forge_version, forge_version
+    );
+    let legacy_universal_jar = format!("forge-{}-universal.jar", forge_version);
+    let mut entries = vec![
+        forge_jar.clone(),
+        forge_universal_jar.clone(),
         "data/client.lzma".to_string(),
         "data/server.lzma".to_string(),
         "install_profile.json".to_string(),
         "version.json".to_string(),
-        format!("forge-{}-universal.jar", forge_version),
+        legacy_universal_jar.clone(),
         "data/run.sh".to_string(),
         "data/run.bat".to_string(),
-        "data/unix_args.txt".to_string(),
-        "data/unix_jvm_args".to_string(),
-        "data/win_args".to_string(),
     ];
+    entries.extend(
+        UNIX_ARGS
+            .iter()
+            .chain(&USER_JVM_ARGS)
+            .chain(&WIN_ARGS)
+            .map(|name| name.to_string()),
+    );
     let filted_entries = filter_entries(&mut zip, &entries);
-    let get_content = move |index: usize| -> Option<Entry> {
-        match filted_entries.get(entries.clone().get(index).unwrap()) {
-            None => None,
-            Some(value) => Some(value.clone()),
-        }
+    let get_content = |names: &[&str]| -> Option<Entry> {
+        names
+            .iter()
+            .find_map(|name| filted_entries.get(*name).cloned())
     };
     ForgeInstallerEntries {
-        forge_jar: get_content(0),
-        forge_universal_jar: get_content(1),
-        client_lzma: get_content(2),
-        server_lzma: get_content(3),
-        install_profile_json: get_content(4),
-        version_json: get_content(5),
-        legacy_universal_jar: get_content(6),
-        run_sh: get_content(7),
-        run_bat: get_content(8),
-        unix_args: get_content(9),
-        user_jvm_args: get_content(10),
-        win_args: get_content(11),
+        forge_jar: get_content(&[&forge_jar]),
+        forge_universal_jar: get_content(&[&forge_universal_jar]),
+        client_lzma: get_content(&["data/client.lzma"]),
+        server_lzma: get_content(&["data/server.lzma"]),
+        install_profile_json: get_content(&["install_profile.json"]),
+        version_json: get_content(&["version.json"]),
+        legacy_universal_jar: get_content(&[&legacy_universal_jar]),
+        run_sh: get_content(&["data/run.sh"]),
+        run_bat: get_content(&["data/run.bat"]),
+        unix_args: get_content(&UNIX_ARGS),
+        user_jvm_args: get_content(&USER_JVM_ARGS),
+        win_args: get_content(&WIN_ARGS),
     }
 }
 
+/// Read the minecraft version a forge installer jar is for from its `install_profile.json`
+///
+/// Both the new profiles and the legacy ones with an `install` section are supported.
+pub fn forge_installer_target<P: AsRef<Path>>(jar_path: P) -> Result<String> {
+    let mut jar = ZipArchive::new(File::open(jar_path)?)?;
+    let mut profile = String::new();
+    jar.by_name("install_profile.json")?
+        .read_to_string(&mut profile)?;
+    let profile: serde_json::Value = serde_json::from_str(&profile)?;
+    profile["minecraft"]
+        .as_str()
+        .or(profile["install"]["minecraft"].as_str())
+        .map(str::to_string)
+        .ok_or(anyhow::anyhow!(
+            "No minecraft version in the forge install profile"
+        ))
+}
+
+/// Download the forge installer and install forge from it.
+///
+/// For the new installers, the libraries of the install profile are downloaded with the
+/// downloader, reported to the listeners. See [`install_forge_libraries`] for resuming and
+/// cancelling it.
 pub async fn install_forge(
     version: RequiredVersion,
     minecraft: MinecraftLocation,
     options: Option<InstallForgeOptions>,
+    downloader: Arc<dyn Downloader>,
+    listeners: &TaskEventListeners,
 ) -> Result<()> {
-    let mcversion: Vec<_> = version.mcversion.split(".").collect();
-    let minor = *mcversion.get(1).unwrap();
-    let minor_version = minor.parse::<u8>().unwrap();
-    let patch = match mcversion.get(2) {
-        Some(patch) => Some(*patch),
-        None => None,
-    };
-    let forge_version = get_forge_version(minor_version, patch, &version);
+    let forge_version = resolve_forge_version(&version)?;
 
-    let (installer_jar_path, _installer_jar) =
-        download_forge_installer(version, &minecraft, &options)
-            .await
-            .unwrap();
+    let installer_jar_path =
+        download_forge_installer(version, &minecraft, &options, downloader.as_ref()).await?;
     println!("{}", installer_jar_path);
 
     let file = Path::new(&installer_jar_path);
@@ -169,7 +243,6 @@ pub async fn install_forge(
         return Err(anyhow::anyhow!(""));
     }
 
-    thread::sleep(Duration::from_secs(1));
     let installer_jar = ZipArchive::new(File::open(&installer_jar_path).unwrap()).unwrap();
 
     let entries = walk_forge_installer_entries(installer_jar, &forge_version).await;
@@ -194,7 +267,12 @@ pub async fn install_forge(
     match forge_type {
         ForgeType::New => {
             let profile: InstallProfile = serde_json::from_str(&install_profile_json).unwrap();
-            let _version_id = unpack_forge_installer(
+            let platform = PlatformInfo::new().await;
+            install_forge_libraries(
+                &profile, &minecraft, &platform, &options, downloader, listeners,
+            )
+            .await?;
+            unpack_forge_installer(
                 &mut installer_jar,
                 entries,
                 &forge_version,
@@ -203,7 +281,7 @@ pub async fn install_forge(
                 profile,
                 options,
             )
-            .await;
+            .await?;
         }
         ForgeType::Legacy => {
             let profile: InstallProfileLegacy =
@@ -228,6 +306,69 @@ pub async fn install_forge(
     Ok(())
 }
 
+/// Download the libraries required by the forge install profile
+///
+/// The libraries that already match their sha1 will be skipped, so an interrupted install can be
+/// resumed by calling this again. Drop the returned future to cancel the download.
+pub async fn install_forge_libraries(
+    profile: &InstallProfile,
+    minecraft: &MinecraftLocation,
+    platform: &PlatformInfo,
+    options: &Option<InstallForgeOptions>,
+    downloader: Arc<dyn Downloader>,
+    listeners: &TaskEventListeners,
+) -> Result<()> {
+    let concurrency = match options
+        .as_ref()
+        .and_then(|options| options.libraries_download_concurrency)
+    {
+        Some(concurrency) => usize::try_from(concurrency)?,
+        None => DEFAULT_CONCURRENCY,
+    };
+    let libraries = match profile.libraries.as_array() {
+        Some(libraries) => libraries.clone(),
+        None => vec![],
+    };
+    let downloads = resolve_libraries(libraries, platform, Side::Client)
+        .await
+        .into_iter()
+        // the forge jar itself has no url, it is unpacked from the installer
+        .filter(|library| !library.download_info.url.is_empty())
+        .map(|library| Download {
+            url: library.download_info.url,
+            file: minecraft.get_library_by_path(library.download_info.path),
+            sha1: library.download_info.sha1,
+            size: library.download_info.size,
+        })
+        .collect::<Vec<_>>();
+    check_disk_space(&minecraft.root, download_size(&downloads)).await?;
+    download_files_with(downloads, listeners, downloader, concurrency, true).await
+}
+
+/// Get the forge version name, like `1.12.2-14.23.5.2859`, from the required version
+///
+/// Return an error if the minecraft version has no minor version, like `1` or a snapshot
+/// `23w31a`, which forge never supports.
+pub(crate) fn resolve_forge_version(version: &RequiredVersion) -> Result<String> {
+    let mcversion: Vec<_> = version.mcversion.split(".").collect();
+    // the minor version of a pre-release is like `20-pre1`
+    let minor_version = mcversion
+        .get(1)
+        .map(|minor| {
+            minor
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+        })
+        .and_then(|minor| minor.parse::<u8>().ok())
+        .ok_or(anyhow::anyhow!(
+            "minecraft version {} is not supported by forge",
+            version.mcversion
+        ))?;
+    let patch = mcversion.get(2).copied();
+    Ok(get_forge_version(minor_version, patch, version))
+}
+
 fn get_forge_version(minor_version: u8, patch: Option<&str>, version: &RequiredVersion) -> String {
     if (minor_version >= 7 && minor_version <= 9) || (minor_version == 10 && patch.is_none()) {
         match patch {
@@ -267,7 +408,7 @@ pub async fn find_download_link(forge_version: &str, minecraft_version: &str) ->
     let document_url = format!(
         "https://files.minecraftforge.net/net/minecraftforge/forge/index_{minecraft_version}.html"
     );
-    let document = HTTP_CLIENT
+    let document = http_client()
         .get(document_url)
         .send()
         .await?
@@ -322,6 +463,260 @@ pub async fn find_download_link(forge_version: &str, minecraft_version: &str) ->
     Ok(link.to_string())
 }
 
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{
+        core::OsType,
+        utils::download::{
+            test::{serve, temp_dir},
+            HttpDownloader,
+        },
+    };
+
+    #[test]
+    fn test_resolve_forge_version() {
+        let required = |mcversion: &str, version: &str| RequiredVersion {
+            installer: None,
+            mcversion: mcversion.to_string(),
+            version: version.to_string(),
+        };
+        assert_eq!(
+            resolve_forge_version(&required("1.12.2", "14.23.5.2859")).unwrap(),
+            "1.12.2-14.23.5.2859"
+        );
+        assert_eq!(
+            resolve_forge_version(&required("1.7.10", "10.13.4.1614")).unwrap(),
+            "1.7.10-10.13.4.1614-1.7.10"
+        );
+        assert!(resolve_forge_version(&required("1", "1.0.0")).is_err());
+        assert!(resolve_forge_version(&required("23w31a", "1.0.0")).is_err());
+    }
+
+    fn install_profile(url: &str) -> InstallProfile {
+        serde_json::from_value(serde_json::json!({
+            "path": null,
+            "minecraft": "1.20.1",
+            "libraries": [
+                {
+                    "name": "net.minecraftforge:forge:1.20.1-47.1.0",
+                    "downloads": { "artifact": { "path": "net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0.jar", "url": "" } }
+                },
+                {
+                    "name": "net.minecraftforge:installertools:1.3.0",
+                    "downloads": { "artifact": { "path": "net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar", "url": format!("{url}/installertools-1.3.0.jar") } }
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_forge_libraries() {
+        let platform = PlatformInfo {
+            arch: "x64".to_string(),
+            name: "linux".to_string(),
+            os_type: OsType::Linux,
+            version: "6.0.0".to_string(),
+        };
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        let library = minecraft.get_library_by_path(
+            "net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar",
+        );
+
+        // a server that never responds, the download is cancelled by the timeout
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let profile = install_profile(&url);
+        let listeners = TaskEventListeners::default();
+        let install = install_forge_libraries(
+            &profile,
+            &minecraft,
+            &platform,
+            &None,
+            Arc::new(HttpDownloader),
+            &listeners,
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(200), install)
+            .await
+            .is_err());
+        assert!(!library.exists());
+
+        // resume
+        let url = serve("200 OK", b"jar".to_vec()).await;
+        let profile = install_profile(&url);
+        install_forge_libraries(
+            &profile,
+            &minecraft,
+            &platform,
+            &None,
+            Arc::new(HttpDownloader),
+            &listeners,
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read(&library).unwrap(), b"jar");
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_forge_library() {
+        let platform = PlatformInfo::new().await;
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        let path = "net/minecraftforge/installertools/1.3.0/installertools-1.3.0.jar";
+        let library = minecraft.get_library_by_path(path);
+        std::fs::create_dir_all(library.parent().unwrap()).unwrap();
+        std::fs::write(&library, b"truncated").unwrap();
+
+        // the library exists, but doesn't match the sha1 in the profile
+        let url = serve("200 OK", b"jar".to_vec()).await;
+        let profile: InstallProfile = serde_json::from_value(serde_json::json!({
+            "path": null,
+            "minecraft": "1.20.1",
+            "libraries": [{
+                "name": "net.minecraftforge:installertools:1.3.0",
+                "downloads": { "artifact": {
+                    "path": path,
+                    "sha1": sha1_of(b"jar"),
+                    "url": format!("{url}/installertools-1.3.0.jar")
+                } }
+            }]
+        }))
+        .unwrap();
+        install_forge_libraries(
+            &profile,
+            &minecraft,
+            &platform,
+            &None,
+            Arc::new(HttpDownloader),
+            &TaskEventListeners::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read(&library).unwrap(), b"jar");
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_installer() {
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        let path = "net/minecraftforge/forge/1.20.1-47.1.0/forge-1.20.1-47.1.0-installer.jar";
+        let sha1 = sha1_of(b"installer");
+        let install = |maven: String| {
+            let version = RequiredVersion {
+                installer: Some(RequiredVersionInstaller {
+                    sha1: Some(sha1.clone()),
+                    path: path.to_string(),
+                }),
+                mcversion: "1.20.1".to_string(),
+                version: "47.1.0".to_string(),
+            };
+            let options = Some(InstallForgeOptions {
+                maven_host: Some(vec![maven]),
+                libraries_download_concurrency: None,
+                inherits_from: None,
+                version_id: None,
+                java: None,
+            });
+            let minecraft = minecraft.clone();
+            async move { download_forge_installer(version, &minecraft, &options, &HttpDownloader).await }
+        };
+
+        let err = install(serve("200 OK", b"tampered".to_vec()).await)
+            .await
+            .unwrap_err();
+        let corrupt = err.downcast_ref::<CorruptInstaller>().unwrap();
+        assert_eq!(corrupt.version, "47.1.0");
+        assert_eq!(corrupt.installer, minecraft.get_library_by_path(path));
+        assert_eq!(corrupt.expected, sha1);
+        assert_eq!(corrupt.actual, sha1_of(b"tampered"));
+        assert!(!corrupt.installer.exists());
+
+        let installer = install(serve("200 OK", b"installer".to_vec()).await)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(installer).unwrap(), b"installer");
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_installer_arg_files() {
+        use std::io::Write;
+
+        let installer = |names: &[&str]| {
+            let mut jar = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+            for name in names {
+                jar.start_file(*name, Default::default()).unwrap();
+                jar.write_all(name.as_bytes()).unwrap();
+            }
+            ZipArchive::new(io::Cursor::new(jar.finish().unwrap().into_inner())).unwrap()
+        };
+        let content = |entry: Option<Entry>| String::from_utf8(entry.unwrap().content).unwrap();
+
+        let entries = walk_forge_installer_entries(
+            installer(&[
+                "data/unix_args.txt",
+                "data/user_jvm_args.txt",
+                "data/win_args.txt",
+            ]),
+            "1.20.1-47.1.0",
+        )
+        .await;
+        assert_eq!(content(entries.unix_args), "data/unix_args.txt");
+        assert_eq!(content(entries.user_jvm_args), "data/user_jvm_args.txt");
+        assert_eq!(content(entries.win_args), "data/win_args.txt");
+
+        let entries = walk_forge_installer_entries(
+            installer(&["data/unix_jvm_args", "data/win_args"]),
+            "1.17.1-37.0.0",
+        )
+        .await;
+        assert_eq!(content(entries.unix_args), "data/unix_jvm_args");
+        assert!(entries.user_jvm_args.is_none());
+        assert_eq!(content(entries.win_args), "data/win_args");
+    }
+
+    #[test]
+    fn test_forge_installer_target() {
+        use std::io::Write;
+
+        let root = temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let installer = |name: &str, profile: &str| {
+            let path = root.join(name);
+            let mut jar = zip::ZipWriter::new(File::create(&path).unwrap());
+            jar.start_file("install_profile.json", Default::default())
+                .unwrap();
+            jar.write_all(profile.as_bytes()).unwrap();
+            jar.finish().unwrap();
+            path
+        };
+        let new = installer(
+            "forge-1.20.1-47.1.0-installer.jar",
+            r#"{ "spec": 1, "minecraft": "1.20.1", "path": null, "libraries": [] }"#,
+        );
+        assert_eq!(forge_installer_target(new).unwrap(), "1.20.1");
+        let legacy = installer(
+            "forge-1.7.10-10.13.4.1614-1.7.10-installer.jar",
+            r#"{ "install": { "minecraft": "1.7.10" }, "versionInfo": {} }"#,
+        );
+        assert_eq!(forge_installer_target(legacy).unwrap(), "1.7.10");
+        let broken = installer("broken-installer.jar", "{}");
+        assert!(forge_installer_target(broken).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    fn sha1_of(content: &[u8]) -> String {
+        use sha1::{Digest, Sha1};
+
+        format!("{:x}", Sha1::digest(content))
+    }
+}
+
 // #[tokio::test]
 // async fn test() {
 //     let a = find_download_link("44.0.1", "1.19.3");
@@ -89,13 +89,13 @@ pub struct ForgeInstallerEntries {
     /// data/run.bat
     pub run_bat: Option<Entry>,
 
-    /// data/unix_args.txt
+    /// data/unix_args.txt, or data/unix_jvm_args.txt, with or without `.txt`
     pub unix_args: Option<Entry>,
 
-    /// data/user_jvm_args.txt
+    /// data/user_jvm_args.txt, with or without `.txt`
     pub user_jvm_args: Option<Entry>,
 
-    /// data/win_args.txt
+    /// data/win_args.txt, or data/win_jvm_args.txt, with or without `.txt`
     pub win_args: Option<Entry>,
 }
 
@@ -126,13 +126,13 @@ pub struct ForgeInstallerEntriesPatten {
     /// data/run.bat
     pub run_bat: Option<Entry>,
 
-    /// data/unix_args.txt
+    /// data/unix_args.txt, or data/unix_jvm_args.txt, with or without `.txt`
     pub unix_args: Option<Entry>,
 
-    /// data/user_jvm_args.txt
+    /// data/user_jvm_args.txt, with or without `.txt`
     pub user_jvm_args: Option<Entry>,
 
-    /// data/win_args.txt
+    /// data/win_args.txt, or data/win_jvm_args.txt, with or without `.txt`
     pub win_args: Option<Entry>,
 }
 
@@ -162,9 +162,7 @@ pub struct InstallForgeOptions {
     pub maven_host: Option<Vec<String>>,
 
     /// Control how many libraries download task should run at the same time.
-    /// It will override the `maxConcurrencyOption` if this is presented.
-    ///
-    /// This will be ignored if you have your own downloader assigned.
+    /// It will override the `DEFAULT_CONCURRENCY` of `install_forge_libraries` if this is presented.
     pub libraries_download_concurrency: Option<i32>,
 
     /// When you want to install a version over another one.
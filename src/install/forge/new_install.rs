@@ -186,7 +186,7 @@ pub(super) async fn unpack_forge_installer<R: Read + io::Seek>(
     )
     .await?;
 
-    decompression_files(zip, decompression_tasks).await;
+    decompression_files(zip, decompression_tasks).await?;
 
     Ok(Version::from_value(version_json)?.id)
 }
@@ -20,6 +20,12 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::{install::resolve_forge_version, RequiredVersion};
+use crate::install::DownloadSource;
+use crate::utils::cache::fetch_json;
+
+pub(super) const FORGE_MAVEN: &str = "https://maven.minecraftforge.net";
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct ForgeVersionListItem {
     pub _id: String,
@@ -32,6 +38,21 @@ pub struct ForgeVersionListItem {
     pub branch: Option<Value>,
 }
 
+impl ForgeVersionListItem {
+    /// The url of the installer jar of this version, from the forge maven or the maven of `source`
+    pub fn installer_url(&self, source: &DownloadSource) -> Result<String> {
+        let version = resolve_forge_version(&RequiredVersion {
+            installer: None,
+            mcversion: self.mcversion.clone(),
+            version: self.version.clone(),
+        })?;
+        let base = source.libraries_base().unwrap_or(FORGE_MAVEN);
+        Ok(format!(
+            "{base}/net/minecraftforge/forge/{version}/forge-{version}-installer.jar"
+        ))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct ForgeInstallerFile {
     pub format: String,
@@ -44,19 +65,14 @@ pub struct ForgeVersionList(Vec<ForgeVersionListItem>);
 
 impl ForgeVersionList {
     pub async fn new() -> Result<Self> {
-        Ok(reqwest::get("https://bmclapi2.bangbang93.com/forge/list/0")
-            .await?
-            .json::<Self>()
-            .await?)
+        fetch_json("https://bmclapi2.bangbang93.com/forge/list/0").await
     }
 
     pub async fn from_mcversion(mcversion: &str) -> Result<Self> {
-        Ok(reqwest::get(format!(
+        fetch_json(&format!(
             "https://bmclapi2.bangbang93.com/forge/minecraft/{mcversion}"
         ))
-        .await?
-        .json::<Self>()
-        .await?)
+        .await
     }
 }
 
@@ -68,3 +84,37 @@ impl ForgeVersionList {
 //     tokio::fs::write("1.json", a).await.unwrap();
 //     println!("{:#?}", forge_version_list);
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(mcversion: &str, version: &str) -> ForgeVersionListItem {
+        ForgeVersionListItem {
+            _id: String::new(),
+            build: 0,
+            __v: 0,
+            version: version.to_string(),
+            modified: String::new(),
+            mcversion: mcversion.to_string(),
+            files: vec![],
+            branch: None,
+        }
+    }
+
+    #[test]
+    fn test_installer_url() {
+        assert_eq!(
+            item("1.20.1", "47.2.0")
+                .installer_url(&DownloadSource::Official)
+                .unwrap(),
+            "https://maven.minecraftforge.net/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar"
+        );
+        assert_eq!(
+            item("1.7.10", "10.13.4.1614")
+                .installer_url(&DownloadSource::BmclApi)
+                .unwrap(),
+            "https://bmclapi2.bangbang93.com/maven/net/minecraftforge/forge/1.7.10-10.13.4.1614-1.7.10/forge-1.7.10-10.13.4.1614-1.7.10-installer.jar"
+        );
+    }
+}
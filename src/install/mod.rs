@@ -16,24 +16,29 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::path::Path;
 use std::str::FromStr;
 
 use anyhow::{anyhow, Result};
 use reqwest::Url;
 use serde_json::Value;
-use tokio::io::AsyncWriteExt;
 
-use crate::core::version::ResolvedLibrary;
+use crate::core::version::{LibraryInfo, ResolvedLibrary};
 use crate::core::Download;
 use crate::core::{
     folder::MinecraftLocation,
     version::{self, AssetIndex, AssetIndexObject, ResolvedVersion, VersionManifest},
     PlatformInfo,
 };
+use crate::utils::download::{file_sha1, Downloader};
+use crate::utils::gzip::fetch;
+use crate::utils::link::{link_or_copy, LinkMode};
+use crate::utils::unzip::sanitize_entry_name;
 
+pub mod compatible;
 pub mod fabric;
 pub mod forge;
-// pub mod optifine;
+pub mod optifine;
 pub mod quilt;
 
 /// todo
@@ -46,67 +51,473 @@ pub struct NetworkOptions {
     pub quilt_remote: String,
 }
 
-pub(crate) fn generate_libraries_downloads(
-    libraries: &Vec<ResolvedLibrary>,
+/// The source to download game files from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum DownloadSource {
+    /// Download from Mojang, the urls in the version json are used as is
+    #[default]
+    Official,
+    BmclApi,
+    Mcbbs,
+    Custom {
+        /// The maven base url, like `https://download.mcbbs.net/maven`
+        libraries: String,
+
+        /// The assets base url, like `https://download.mcbbs.net/assets`
+        assets: String,
+
+        /// The version base url, like `https://download.mcbbs.net/version`. Its parent serves the
+        /// version manifest, version jsons and asset indexes at Mojang's paths
+        version: String,
+    },
+}
+
+impl DownloadSource {
+    /// The maven base url, None if the original urls should be used
+    pub fn libraries_base(&self) -> Option<&str> {
+        match self {
+            DownloadSource::Official => None,
+            DownloadSource::BmclApi => Some("https://bmclapi2.bangbang93.com/maven"),
+            DownloadSource::Mcbbs => Some("https://download.mcbbs.net/maven"),
+            DownloadSource::Custom { libraries, .. } => Some(libraries),
+        }
+    }
+
+    /// The version base url, the artifacts are at `{base}/{id}/{name}`. None if the original
+    /// urls should be used
+    pub fn version_base(&self) -> Option<&str> {
+        match self {
+            DownloadSource::Official => None,
+            DownloadSource::BmclApi => Some("https://bmclapi2.bangbang93.com/version"),
+            DownloadSource::Mcbbs => Some("https://download.mcbbs.net/version"),
+            DownloadSource::Custom { version, .. } => Some(version),
+        }
+    }
+
+    /// The host serving the version manifest, version jsons and asset indexes at Mojang's paths.
+    /// None if the original urls should be used
+    pub fn meta_base(&self) -> Option<&str> {
+        match self {
+            DownloadSource::Official => None,
+            DownloadSource::BmclApi => Some("https://bmclapi2.bangbang93.com"),
+            DownloadSource::Mcbbs => Some("https://download.mcbbs.net"),
+            DownloadSource::Custom { version, .. } => Some(
+                version
+                    .trim_end_matches('/')
+                    .rsplit_once('/')
+                    .map_or(version.as_str(), |(parent, _)| parent),
+            ),
+        }
+    }
+
+    /// The url of a Mojang metadata file, like a version json or an asset index, from the source
+    pub fn meta_url(&self, url: &str) -> String {
+        match (self.meta_base(), Url::parse(url)) {
+            (Some(base), Ok(url)) => version::join_url(base, url.path()),
+            _ => url.to_string(),
+        }
+    }
+
+    /// The assets base url, the objects are at `{base}/{first 2 chars of hash}/{hash}`
+    pub fn assets_base(&self) -> &str {
+        match self {
+            DownloadSource::Official => "https://resources.download.minecraft.net",
+            DownloadSource::BmclApi => "https://bmclapi2.bangbang93.com/assets",
+            DownloadSource::Mcbbs => "https://download.mcbbs.net/assets",
+            DownloadSource::Custom { assets, .. } => assets,
+        }
+    }
+}
+
+/// The url of an asset object by its hash from the source
+pub fn asset_object_url(hash: &str, source: &DownloadSource) -> String {
+    version::join_url(source.assets_base(), &format!("{}/{hash}", &hash[0..2]))
+}
+
+/// How to install a version id that may already be installed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallMode {
+    /// Remove the version folder, with the user content in it like `mods` and `options.txt`,
+    /// then download every file
+    Fresh,
+    /// Keep the user content, refresh the version json and download only the files that are
+    /// missing or changed
+    Update,
+    /// Keep the user content and the version json, download only the files that are missing or
+    /// corrupt
+    Repair,
+}
+
+impl InstallMode {
+    /// Prepare the folder of `version_id` before installing it
+    pub async fn clean(
+        &self,
+        version_id: &str,
+        minecraft_location: &MinecraftLocation,
+    ) -> Result<()> {
+        let version_root = minecraft_location.get_version_root(version_id);
+        if *self == InstallMode::Fresh && version_root.is_dir() {
+            tokio::fs::remove_dir_all(version_root).await?;
+        }
+        Ok(())
+    }
+
+    /// Keep the downloads to do in this mode
+    pub async fn filter_downloads(&self, downloads: Vec<Download>) -> Result<Vec<Download>> {
+        match self {
+            InstallMode::Fresh => Ok(downloads),
+            InstallMode::Update | InstallMode::Repair => missing_downloads(downloads).await,
+        }
+    }
+}
+
+/// Keep the downloads whose file is missing or corrupt. Hashing every library and asset takes
+/// seconds, so it runs off the async runtime.
+pub(crate) async fn missing_downloads(downloads: Vec<Download>) -> Result<Vec<Download>> {
+    Ok(
+        tokio::task::spawn_blocking(move || downloads.into_iter().filter(needs_download).collect())
+            .await?,
+    )
+}
+
+/// Whether the file is missing, or doesn't match its size or sha1 if they're known. The size is
+/// checked first, so a truncated file is found without hashing it.
+fn needs_download(download: &Download) -> bool {
+    if let Some(size) = download.size {
+        match std::fs::metadata(&download.file) {
+            Ok(metadata) if metadata.len() == size => (),
+            _ => return true,
+        }
+    }
+    match &download.sha1 {
+        Some(sha1) => !file_sha1(&download.file)
+            .map(|actual| actual.eq_ignore_ascii_case(sha1))
+            .unwrap_or(false),
+        None => !download.file.is_file(),
+    }
+}
+
+/// Generate the download list of libraries from the given source
+///
+/// The native libraries are always downloaded from their original urls.
+pub fn library_downloads(
+    libraries: &[ResolvedLibrary],
     minecraft_location: &MinecraftLocation,
+    source: &DownloadSource,
 ) -> Vec<Download> {
     libraries
-        .clone()
-        .into_iter()
+        .iter()
         .map(|library| Download {
-            url: if library.is_native_library {
-                println!("find native library url: {}", &library.download_info.url);
-                library.download_info.url
-            } else {
-                format!(
-                    "https://download.mcbbs.net/maven/{}",
+            url: match source.libraries_base() {
+                Some(base) if !library.is_native_library => format!(
+                    "{}/{}",
+                    base.trim_end_matches('/'),
                     library.download_info.path
-                )
+                ),
+                _ => library.download_info.url.clone(),
             },
             file: minecraft_location
                 .libraries
-                .join(library.download_info.path),
-            sha1: library.download_info.sha1,
+                .join(&library.download_info.path),
+            sha1: library.download_info.sha1.clone(),
+            size: library.download_info.size,
         })
         .collect()
 }
 
+/// Download a single library by its maven coordinate, like `com.google.code.gson:gson:2.10`
+///
+/// The library is downloaded from the maven of the source, or `libraries.minecraft.net` for
+/// the official source, and replaces the file in the libraries folder. Jar files are checked to
+/// be valid archives, the broken file is removed if not.
+pub async fn repair_library(
+    coord: &str,
+    minecraft: &MinecraftLocation,
+    source: &DownloadSource,
+    downloader: &dyn Downloader,
+) -> Result<()> {
+    if coord
+        .split('@')
+        .next()
+        .unwrap_or_default()
+        .split(':')
+        .count()
+        < 3
+    {
+        return Err(anyhow!("invalid library coordinate: {coord}"));
+    }
+    let library = LibraryInfo::from_value(&serde_json::json!({ "name": coord }));
+    let base = source
+        .libraries_base()
+        .unwrap_or("https://libraries.minecraft.net");
+    let download = Download {
+        url: format!("{}/{}", base.trim_end_matches('/'), library.path),
+        file: minecraft.get_library_by_path(&library.path),
+        sha1: None,
+        size: None,
+    };
+    downloader.fetch(&download).await?;
+    if library.r#type == "jar" {
+        let verified = std::fs::File::open(&download.file)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(zip::ZipArchive::new(file)?));
+        if let Err(err) = verified {
+            tokio::fs::remove_file(&download.file).await?;
+            return Err(anyhow!("library {coord} is broken: {err}"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn generate_libraries_downloads(
+    libraries: &[ResolvedLibrary],
+    minecraft_location: &MinecraftLocation,
+    source: &DownloadSource,
+) -> Vec<Download> {
+    library_downloads(libraries, minecraft_location, source)
+}
+
+/// Generate the downloads of the asset objects and the asset index, all with their sizes so a
+/// byte progress total includes the assets
 pub(crate) async fn generate_assets_downloads(
     asset_index: AssetIndex,
     minecraft_location: &MinecraftLocation,
+    source: &DownloadSource,
 ) -> Result<Vec<Download>> {
-    let asset_index_url = Url::parse((&asset_index.url).as_ref())?;
-    let asset_index_raw = reqwest::get(asset_index_url).await?.text().await?;
+    let asset_index_url = source.meta_url(&asset_index.url);
+    let asset_index_raw = String::from_utf8(fetch(&asset_index_url).await?)?;
     let asset_index_json: Value = serde_json::from_str((&asset_index_raw).as_ref())?;
     let asset_index_object: AssetIndexObject =
         serde_json::from_value(asset_index_json["objects"].clone())?;
     let mut assets: Vec<_> = asset_index_object
         .into_iter()
         .map(|obj| Download {
-            url: format!(
-                "https://download.mcbbs.net/assets/{}/{}",
-                &obj.1.hash[0..2],
-                obj.1.hash
-            ),
+            url: asset_object_url(&obj.1.hash, source),
             file: minecraft_location
                 .assets
                 .join("objects")
                 .join(&obj.1.hash[0..2])
                 .join(&obj.1.hash),
             sha1: Some(obj.1.hash),
+            size: Some(obj.1.size as u64),
         })
         .collect();
     assets.push(Download {
-        url: asset_index.url,
+        url: asset_index_url,
         file: minecraft_location
             .assets
             .join("indexes")
             .join(format!("{}.json", asset_index.id)),
         sha1: None,
+        size: Some(asset_index.size),
     });
     Ok(assets)
 }
 
+/// Place the assets of an asset index from a shared store into another game folder
+///
+/// With `LinkMode::Hardlink`, the asset objects are hardlinked so the instances share the disk
+/// space. The objects missing in the shared store are skipped.
+pub fn share_assets(
+    shared: &MinecraftLocation,
+    minecraft_location: &MinecraftLocation,
+    version_assets: &str,
+    mode: LinkMode,
+) -> Result<()> {
+    let index_path = shared.get_assets_index(version_assets);
+    let index: Value = serde_json::from_slice(&std::fs::read(&index_path)?)?;
+    let objects: AssetIndexObject = serde_json::from_value(index["objects"].clone())?;
+    for object in objects.values() {
+        let path = Path::new("objects")
+            .join(&object.hash[0..2])
+            .join(&object.hash);
+        let from = shared.assets.join(&path);
+        if from.is_file() {
+            link_or_copy(from, minecraft_location.assets.join(&path), mode)?;
+        }
+    }
+    link_or_copy(
+        index_path,
+        minecraft_location.get_assets_index(version_assets),
+        LinkMode::Copy,
+    )?;
+    Ok(())
+}
+
+/// Copy the downloaded asset objects to the virtual assets folder by their names, if the asset
+/// index is a legacy one with `"virtual": true`. Versions before 1.7.3 read assets from there.
+/// Nothing is done if the asset index is not downloaded.
+pub fn install_virtual_assets(
+    minecraft_location: &MinecraftLocation,
+    version_assets: &str,
+) -> Result<()> {
+    let index_path = minecraft_location.get_assets_index(version_assets);
+    if !index_path.is_file() {
+        return Ok(());
+    }
+    let index: Value = serde_json::from_slice(&std::fs::read(&index_path)?)?;
+    if !index["virtual"].as_bool().unwrap_or(false) {
+        return Ok(());
+    }
+    let objects: AssetIndexObject = serde_json::from_value(index["objects"].clone())?;
+    let virtual_root = minecraft_location.get_assets_virtual(version_assets);
+    for (name, object) in objects {
+        let from = minecraft_location
+            .assets
+            .join("objects")
+            .join(&object.hash[0..2])
+            .join(&object.hash);
+        link_or_copy(
+            from,
+            virtual_root.join(sanitize_entry_name(&name)?),
+            LinkMode::Copy,
+        )?;
+    }
+    Ok(())
+}
+
+/// [`install_virtual_assets`] off the async runtime, once the assets are downloaded
+pub(crate) async fn install_virtual_assets_async(
+    minecraft_location: &MinecraftLocation,
+    version_assets: &str,
+) -> Result<()> {
+    let minecraft_location = minecraft_location.clone();
+    let version_assets = version_assets.to_string();
+    tokio::task::spawn_blocking(move || {
+        install_virtual_assets(&minecraft_location, &version_assets)
+    })
+    .await?
+}
+
+/// The download of the client jar of the version from the source
+fn client_download(
+    version: &ResolvedVersion,
+    minecraft_location: &MinecraftLocation,
+    source: &DownloadSource,
+) -> Option<Download> {
+    let client = version.downloads.as_ref()?.get("client")?;
+    let url = match source.version_base() {
+        Some(base) => format!(
+            "{}/{}/client",
+            base.trim_end_matches('/'),
+            version.minecraft_version
+        ),
+        None => client.url.clone(),
+    };
+    Some(Download {
+        url,
+        file: minecraft_location.get_version_jar(&version.id, None),
+        sha1: Some(client.sha1.clone()),
+        size: Some(client.size),
+    })
+}
+
+/// Find the libraries, client jar and assets of the version that are missing or corrupt
+///
+/// The files with a known sha1 are verified by it, the others are only checked to exist. A
+/// missing asset index is downloaded first, so the assets are always checked. Return the
+/// downloads to repair them, from `source`. The asset index is fetched with the downloader.
+pub async fn check_game_integrity(
+    version: &ResolvedVersion,
+    minecraft_location: &MinecraftLocation,
+    source: &DownloadSource,
+    downloader: &dyn Downloader,
+) -> Result<Vec<Download>> {
+    let mut downloads = library_downloads(&version.libraries, minecraft_location, source);
+    downloads.extend(client_download(version, minecraft_location, source));
+    let index_path = minecraft_location.get_assets_index(&version.assets);
+    if let (false, Some(asset_index)) = (index_path.is_file(), &version.asset_index) {
+        downloader
+            .fetch(&Download {
+                url: source.meta_url(&asset_index.url),
+                file: index_path.clone(),
+                sha1: None,
+                size: Some(asset_index.size),
+            })
+            .await?;
+    }
+    if index_path.is_file() {
+        let index: Value = serde_json::from_slice(&tokio::fs::read(index_path).await?)?;
+        let objects: AssetIndexObject = serde_json::from_value(index["objects"].clone())?;
+        downloads.extend(objects.into_values().map(|object| {
+            Download {
+                url: asset_object_url(&object.hash, source),
+                file: minecraft_location
+                    .assets
+                    .join("objects")
+                    .join(&object.hash[0..2])
+                    .join(&object.hash),
+                sha1: Some(object.hash),
+                size: Some(object.size as u64),
+            }
+        }));
+    }
+    missing_downloads(downloads).await
+}
+
+/// The bytes the libraries, client jar and assets of the version that are not downloaded yet
+/// take, by the sizes in the version json
+///
+/// All the assets are counted if the asset index is not downloaded yet. The files are looked up
+/// off the async runtime.
+pub async fn missing_bytes(
+    version: &ResolvedVersion,
+    minecraft_location: &MinecraftLocation,
+) -> Result<u64> {
+    let version = version.clone();
+    let minecraft_location = minecraft_location.clone();
+    Ok(
+        tokio::task::spawn_blocking(move || count_missing_bytes(&version, &minecraft_location))
+            .await?,
+    )
+}
+
+fn count_missing_bytes(version: &ResolvedVersion, minecraft_location: &MinecraftLocation) -> u64 {
+    let mut bytes = version
+        .libraries
+        .iter()
+        .filter(|library| {
+            !minecraft_location
+                .get_library_by_path(&library.download_info.path)
+                .is_file()
+        })
+        .filter_map(|library| library.download_info.size)
+        .sum();
+    if let Some(client) = version.downloads.as_ref().and_then(|d| d.get("client")) {
+        if !minecraft_location
+            .get_version_jar(&version.id, None)
+            .is_file()
+        {
+            bytes += client.size;
+        }
+    }
+    let index = std::fs::read(minecraft_location.get_assets_index(&version.assets))
+        .ok()
+        .and_then(|index| serde_json::from_slice::<Value>(&index).ok())
+        .and_then(|index| {
+            serde_json::from_value::<AssetIndexObject>(index["objects"].clone()).ok()
+        });
+    match (index, &version.asset_index) {
+        (Some(objects), _) => {
+            bytes += objects
+                .values()
+                .filter(|object| {
+                    !minecraft_location
+                        .assets
+                        .join("objects")
+                        .join(&object.hash[0..2])
+                        .join(&object.hash)
+                        .is_file()
+                })
+                .map(|object| object.size as u64)
+                .sum::<u64>();
+        }
+        (None, Some(asset_index)) => bytes += asset_index.size + asset_index.total_size,
+        (None, None) => {}
+    }
+    bytes
+}
+
 /// check game integrity and try to repair files
 ///
 /// This is usually done in situations where the integrity of the game is uncertain,
@@ -114,16 +525,22 @@ pub(crate) async fn generate_assets_downloads(
 pub async fn generate_dependencies_downloads(
     version: ResolvedVersion,
     minecraft_location: MinecraftLocation,
+    source: &DownloadSource,
 ) -> Result<()> {
     let mut download_list = Vec::new();
 
     download_list.extend(generate_libraries_downloads(
         &version.libraries,
         &minecraft_location,
+        source,
     ));
     download_list.extend(
-        generate_assets_downloads(version.asset_index.clone().unwrap(), &minecraft_location)
-            .await?,
+        generate_assets_downloads(
+            version.asset_index.clone().unwrap(),
+            &minecraft_location,
+            source,
+        )
+        .await?,
     );
     let log4j2 = generate_log4j2_configuration_download(&version, &minecraft_location);
     if let Ok(log4j2) = log4j2 {
@@ -148,28 +565,30 @@ pub fn generate_log4j2_configuration_download(
             .get_version_root(version.id.clone())
             .join("log4j2.xml"),
         sha1: Some(logging_client.file.sha1),
+        size: Some(logging_client.file.size),
     })
 }
 
+/// Save the version json of `version_id` and generate the downloads of its client jar,
+/// libraries, assets and log4j2 configuration from `source`
 pub async fn generate_download_info(
     version_id: &str,
     minecraft_location: MinecraftLocation,
     platform: &PlatformInfo,
+    source: &DownloadSource,
 ) -> Result<Vec<Download>> {
-    let versions = VersionManifest::new().await?.versions;
-    let version_metadata: Vec<_> = versions
-        .into_iter()
-        .filter(|v| v.id == version_id)
-        .collect();
-    if version_metadata.len() != 1 {
-        panic!("Bad version manifest!!!")
+    let manifest = match source.meta_base() {
+        Some(base) => VersionManifest::from_mirror(base).await?,
+        None => VersionManifest::new().await?,
     };
-    let version_metadata = version_metadata.get(0).unwrap();
+    let version_metadata = manifest
+        .versions
+        .iter()
+        .find(|v| v.id == version_id)
+        .ok_or_else(|| anyhow!("Version {version_id} is not in the version manifest"))?;
 
-    let version_json_raw = reqwest::get(version_metadata.url.clone())
-        .await?
-        .text()
-        .await?;
+    let version_json_raw =
+        String::from_utf8(fetch(&source.meta_url(&version_metadata.url)).await?)?;
     let version = version::Version::from_str(&version_json_raw)?
         .parse(&minecraft_location, &platform)
         .await?;
@@ -177,19 +596,18 @@ pub async fn generate_download_info(
 
     let version_json_path = minecraft_location.versions.join(format!("{id}/{id}.json"));
     tokio::fs::create_dir_all(version_json_path.parent().unwrap()).await?;
-    let mut file = tokio::fs::File::create(&version_json_path).await?;
-    file.write_all(version_json_raw.as_bytes()).await?;
+    tokio::fs::write(&version_json_path, &version_json_raw).await?;
 
     let mut download_list = vec![];
-    download_list.push(Download {
-        url: format!("https://download.mcbbs.net/version/{version_id}/client"),
-        file: minecraft_location.versions.join(format!("{id}/{id}.jar")),
-        sha1: None,
-    });
+    download_list.push(
+        client_download(&version, &minecraft_location, source)
+            .ok_or_else(|| anyhow!("No client download found"))?,
+    );
 
     download_list.extend(generate_libraries_downloads(
         &version.libraries,
         &minecraft_location,
+        source,
     ));
     download_list.extend(
         generate_assets_downloads(
@@ -198,6 +616,7 @@ pub async fn generate_download_info(
                 .clone()
                 .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
             &minecraft_location,
+            source,
         )
         .await?,
     );
@@ -210,18 +629,110 @@ pub async fn generate_download_info(
 
 #[cfg(test)]
 mod test {
+    use tokio::io::AsyncWriteExt;
+
     use super::*;
     use crate::core::folder::MinecraftLocation;
-    use crate::core::HTTP_CLIENT;
+    use crate::core::http_client;
+    use crate::core::version::test::vanilla_json;
+    use crate::core::version::LibraryDownload;
+    use crate::utils::download::{test::serve, HttpDownloader};
+
+    #[test]
+    fn test_library_downloads() {
+        let minecraft = MinecraftLocation::new("test");
+        let path = "com/google/code/gson/gson/2.10/gson-2.10.jar";
+        let libraries = vec![ResolvedLibrary {
+            download_info: LibraryDownload {
+                sha1: None,
+                size: None,
+                url: format!("https://libraries.minecraft.net/{path}"),
+                path: path.to_string(),
+            },
+            is_native_library: false,
+        }];
+        let url = |source| {
+            library_downloads(&libraries, &minecraft, &source)[0]
+                .url
+                .clone()
+        };
+        assert_eq!(
+            url(DownloadSource::Official),
+            format!("https://libraries.minecraft.net/{path}")
+        );
+        assert_eq!(
+            url(DownloadSource::BmclApi),
+            format!("https://bmclapi2.bangbang93.com/maven/{path}")
+        );
+        assert_eq!(
+            url(DownloadSource::Custom {
+                libraries: "https://mirror.example.com/maven/".to_string(),
+                assets: "https://mirror.example.com/assets".to_string(),
+                version: "https://mirror.example.com/version".to_string(),
+            }),
+            format!("https://mirror.example.com/maven/{path}")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repair_library() {
+        use std::io::Write;
+
+        let mut jar = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        jar.start_file("META-INF/MANIFEST.MF", Default::default())
+            .unwrap();
+        jar.write_all(b"Manifest-Version: 1.0\n").unwrap();
+        let jar = jar.finish().unwrap().into_inner();
+
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let path = minecraft
+            .get_library_by_path("net/fabricmc/tiny-remapper/0.8.2/tiny-remapper-0.8.2-fat.jar");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"broken").unwrap();
+
+        let source = |libraries| DownloadSource::Custom {
+            libraries,
+            assets: String::new(),
+            version: String::new(),
+        };
+        let url = crate::utils::download::test::serve("200 OK", jar.clone()).await;
+        repair_library(
+            "net.fabricmc:tiny-remapper:0.8.2:fat",
+            &minecraft,
+            &source(url),
+            &HttpDownloader,
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), jar);
+
+        let url = crate::utils::download::test::serve("200 OK", b"not a jar".to_vec()).await;
+        assert!(repair_library(
+            "net.fabricmc:tiny-remapper:0.8.2:fat",
+            &minecraft,
+            &source(url),
+            &HttpDownloader,
+        )
+        .await
+        .is_err());
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
     #[tokio::test]
     async fn test() {
         let platform = PlatformInfo::new().await;
-        let downloads = generate_download_info("1.19.3", MinecraftLocation::new("test"), &platform)
-            .await
-            .unwrap();
+        let downloads = generate_download_info(
+            "1.19.3",
+            MinecraftLocation::new("test"),
+            &platform,
+            &DownloadSource::default(),
+        )
+        .await
+        .unwrap();
         for (index, download) in downloads.into_iter().enumerate() {
             println!("{}", index);
-            let mut response = HTTP_CLIENT.get(download.url).send().await.unwrap();
+            let mut response = http_client().get(download.url).send().await.unwrap();
             tokio::fs::create_dir_all(download.file.parent().unwrap())
                 .await
                 .unwrap();
@@ -231,4 +742,216 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_install_virtual_assets() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let hash = "bdf48ef6b5d0d23bbb02e17d04865216179f510a";
+        let object = minecraft
+            .assets
+            .join("objects")
+            .join(&hash[0..2])
+            .join(hash);
+        std::fs::create_dir_all(object.parent().unwrap()).unwrap();
+        std::fs::write(&object, "sound").unwrap();
+        // not downloaded yet
+        install_virtual_assets(&minecraft, "legacy").unwrap();
+        let index = minecraft.get_assets_index("legacy");
+        std::fs::create_dir_all(index.parent().unwrap()).unwrap();
+        std::fs::write(
+            &index,
+            format!(r#"{{ "virtual": true, "objects": {{ "sounds/step/grass1.ogg": {{ "hash": "{hash}", "size": 5 }} }} }}"#),
+        )
+        .unwrap();
+        install_virtual_assets(&minecraft, "legacy").unwrap();
+        let file = minecraft
+            .get_assets_virtual("legacy")
+            .join("sounds/step/grass1.ogg");
+        assert_eq!(std::fs::read(file).unwrap(), b"sound");
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_install_mode() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let version_root = minecraft.get_version_root("1.20.1");
+        let mod_jar = version_root.join("mods").join("sodium.jar");
+        std::fs::create_dir_all(mod_jar.parent().unwrap()).unwrap();
+        std::fs::write(&mod_jar, b"sodium").unwrap();
+        let client = minecraft.get_version_jar("1.20.1", None);
+        std::fs::write(&client, b"client").unwrap();
+        let download = |file: &Path, sha1: Option<&str>| Download {
+            url: "https://example.com/file".to_string(),
+            file: file.to_path_buf(),
+            sha1: sha1.map(String::from),
+            size: None,
+        };
+        let downloads = vec![
+            download(&client, Some(&file_sha1(&client).unwrap())),
+            download(&version_root.join("log4j2.xml"), None),
+        ];
+
+        InstallMode::Update
+            .clean("1.20.1", &minecraft)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&mod_jar).unwrap(), b"sodium");
+        let filtered = InstallMode::Update
+            .filter_downloads(downloads.clone())
+            .await
+            .unwrap();
+        assert_eq!(filtered, vec![downloads[1].clone()]);
+
+        assert_eq!(
+            InstallMode::Fresh
+                .filter_downloads(downloads.clone())
+                .await
+                .unwrap(),
+            downloads
+        );
+        InstallMode::Fresh
+            .clean("1.20.1", &minecraft)
+            .await
+            .unwrap();
+        assert!(!mod_jar.exists());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_bytes() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let path = "com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar";
+        let version = version::Version::from_value(vanilla_json(
+            "1.20.1",
+            "5",
+            serde_json::json!({
+                "assetIndex": { "id": "5", "size": 100, "url": "https://example.com/5.json", "totalSize": 1000 },
+                "downloads": { "client": { "sha1": "0", "size": 10000, "url": "https://example.com/client.jar" } },
+                "libraries": [{
+                    "name": "com.mojang:brigadier:1.1.8",
+                    "downloads": { "artifact": { "path": path, "sha1": "0", "size": 10, "url": format!("https://example.com/{path}") } }
+                }]
+            }),
+        ))
+        .unwrap()
+        .parse(&minecraft, &PlatformInfo::new().await)
+        .await
+        .unwrap();
+        assert_eq!(missing_bytes(&version, &minecraft).await.unwrap(), 11110);
+
+        let library = minecraft.get_library_by_path(path);
+        std::fs::create_dir_all(library.parent().unwrap()).unwrap();
+        std::fs::write(library, b"").unwrap();
+        let index = minecraft.get_assets_index("5");
+        std::fs::create_dir_all(index.parent().unwrap()).unwrap();
+        std::fs::write(
+            index,
+            r#"{ "objects": { "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665 } } }"#,
+        )
+        .unwrap();
+        assert_eq!(missing_bytes(&version, &minecraft).await.unwrap(), 13665);
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[test]
+    fn test_asset_object_url() {
+        let hash = "bdf48ef6b5d0d23bbb02e17d04865216179f510a";
+        assert_eq!(
+            asset_object_url(hash, &DownloadSource::Official),
+            format!("https://resources.download.minecraft.net/bd/{hash}")
+        );
+        assert_eq!(
+            asset_object_url(hash, &DownloadSource::Mcbbs),
+            format!("https://download.mcbbs.net/assets/bd/{hash}")
+        );
+        let custom = DownloadSource::Custom {
+            libraries: String::new(),
+            assets: "https://mirror.example.com/assets/".to_string(),
+            version: String::new(),
+        };
+        assert_eq!(
+            asset_object_url(hash, &custom),
+            format!("https://mirror.example.com/assets/bd/{hash}")
+        );
+    }
+
+    #[test]
+    fn test_meta_url() {
+        let url = "https://piston-meta.mojang.com/v1/packages/9d58fdd/5.json";
+        assert_eq!(DownloadSource::Official.meta_url(url), url);
+        assert_eq!(
+            DownloadSource::BmclApi.meta_url(url),
+            "https://bmclapi2.bangbang93.com/v1/packages/9d58fdd/5.json"
+        );
+        let custom = DownloadSource::Custom {
+            libraries: String::new(),
+            assets: String::new(),
+            version: "https://mirror.example.com/version/".to_string(),
+        };
+        assert_eq!(
+            custom.meta_url(url),
+            "https://mirror.example.com/v1/packages/9d58fdd/5.json"
+        );
+    }
+
+    #[test]
+    fn test_needs_download_size() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("client.jar");
+        std::fs::write(&file, b"abc").unwrap();
+        let sha1 = file_sha1(&file).unwrap();
+        let download = |size| Download {
+            url: "https://example.com/client.jar".to_string(),
+            file: file.clone(),
+            sha1: Some(sha1.clone()),
+            size,
+        };
+        // the sha1 matches, only the size can flag it
+        assert!(needs_download(&download(Some(4))));
+        assert!(!needs_download(&download(Some(3))));
+        assert!(!needs_download(&download(None)));
+        std::fs::remove_file(&file).unwrap();
+        assert!(needs_download(&download(Some(3))));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_assets_total_size() {
+        let index = br#"{
+            "objects": {
+                "icons/icon_16x16.png": { "hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665 },
+                "minecraft/sounds/ambient/cave/cave1.ogg": { "hash": "6ba9f1b5a1b5a57e5c4e7b8bc2f1a36a9e0c2b79", "size": 75236 }
+            }
+        }"#;
+        let url = serve("200 OK", index.to_vec()).await;
+        let asset_index = AssetIndex {
+            size: index.len() as u64,
+            url: "https://piston-meta.mojang.com/v1/packages/1.19.json".to_string(),
+            id: "1.19".to_string(),
+            total_size: 3665 + 75236,
+        };
+        let minecraft = MinecraftLocation::new("test");
+        let source = DownloadSource::Custom {
+            libraries: String::new(),
+            assets: "https://mirror.example.com/assets/".to_string(),
+            version: format!("{url}/version"),
+        };
+        let assets = generate_assets_downloads(asset_index, &minecraft, &source)
+            .await
+            .unwrap();
+        assert_eq!(
+            crate::utils::disk::download_size(&assets),
+            3665 + 75236 + index.len() as u64
+        );
+        assert_eq!(assets.len(), 3);
+        let hash = "bdf48ef6b5d0d23bbb02e17d04865216179f510a";
+        let icon = assets
+            .iter()
+            .find(|asset| asset.sha1.as_deref() == Some(hash));
+        assert_eq!(
+            icon.unwrap().url,
+            format!("https://mirror.example.com/assets/bd/{hash}")
+        );
+    }
 }
@@ -16,18 +16,20 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{ffi::OsStr, fmt::Display, path::Path};
+use std::{ffi::OsStr, fmt::Display, io::Read, path::Path};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde_json::json;
 use tokio::{fs, io::AsyncWriteExt};
+use zip::ZipArchive;
 
+use crate::core::DELIMITER;
 use crate::{
-    core::folder::MinecraftLocation,
-    utils::download::{download, Download},
+    core::{folder::MinecraftLocation, version::Version, Download},
+    utils::download::download,
 };
-use crate::core::DELIMITER;
 
-use super::{InstallOptifineOptions, DEFAULT_META_URL};
+use super::{patcher::patch_jar, InstallOptifineOptions, DEFAULT_META_URL};
 
 const OPTIFINE_INSTALL_HELPER: &[u8] = include_bytes!("../../../vendor/optifine-installer.jar");
 
@@ -39,20 +41,21 @@ pub async fn download_optifine_installer<P, D>(
     dest_path: P,
     remote: Option<D>,
 ) -> Result<()>
-    where
-        P: AsRef<Path> + AsRef<OsStr>,
-        D: Display,
+where
+    P: AsRef<Path> + AsRef<OsStr>,
+    D: Display,
 {
     let url = match remote {
         None => format!("{DEFAULT_META_URL}/{minecraft_version}/{optifine_type}/{optifine_patch}"),
         Some(remote) => format!("{remote}/{minecraft_version}/{optifine_type}/{optifine_patch}"),
     };
-    download(Download {
+    download(&Download {
         url,
-        file: dest_path,
+        file: Path::new(&dest_path).to_path_buf(),
         sha1: None,
+        size: None,
     })
-        .await?;
+    .await?;
 
     Ok(())
 }
@@ -83,32 +86,29 @@ pub async fn install_optifine(
         Some(options) => options,
     };
     let full_path = minecraft.get_library_by_path(format!("net/optifine/{minecraft_version}-{optifine_type}-{optifine_patch}/Optifine-{minecraft_version}-{optifine_type}-{optifine_patch}.jar"));
-    let full_path = full_path.to_str().unwrap();
 
     download_optifine_installer(
         minecraft_version,
         optifine_type,
         optifine_patch,
-        full_path,
+        &full_path,
         options.remote,
     )
-        .await?;
+    .await?;
 
     let installer_path = minecraft
         .get_library_by_path("net/stevexmh/optifine-installer/0.0.0/optifine-installer.jar");
-    let installer_path = installer_path.to_str().unwrap();
 
-    fs::create_dir_all(Path::new(&installer_path).parent().unwrap())
-        .await
-        ?;
+    if let Some(parent) = installer_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
 
     let mut file = fs::OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(installer_path)
-        .await
-        ?;
+        .open(&installer_path)
+        .await?;
     file.write_all(OPTIFINE_INSTALL_HELPER).await?;
     file.flush().await?;
     file.sync_all().await?;
@@ -124,15 +124,274 @@ pub async fn install_optifine(
     //     command
     // };
 
-    command.args(&[
-        "-cp",
-        &format!("{installer_path}{}{full_path}", DELIMITER),
-        "net.stevexmh.OptifineInstaller",
-        minecraft.root.to_str().unwrap(),
-        version_name,
-    ]);
+    let mut classpath = installer_path.into_os_string();
+    classpath.push(DELIMITER);
+    classpath.push(&full_path);
+    command
+        .arg("-cp")
+        .arg(classpath)
+        .arg("net.stevexmh.OptifineInstaller")
+        .arg(&minecraft.root)
+        .arg(version_name);
 
-    command.status().await?;
+    let status = command.status().await?;
+    if !status.success() {
+        return Err(anyhow!("The OptiFine installer exited with {status}"));
+    }
 
     Ok(())
 }
+
+/// Where the installers of the different OptiFine versions put the `Config` class
+const CONFIG_CLASSES: &[&str] = &[
+    "net/optifine/Config.class",
+    "notch/net/optifine/Config.class",
+    "Config.class",
+];
+
+/// Read the version of an OptiFine installer, like `OptiFine_1.20.1_HD_U_I6`, from the
+/// constants of its `Config` class
+pub fn read_optifine_version<R: Read + std::io::Seek>(
+    installer: &mut ZipArchive<R>,
+) -> Result<String> {
+    for name in CONFIG_CLASSES {
+        let mut class = Vec::new();
+        match installer.by_name(name) {
+            Ok(mut file) => file.read_to_end(&mut class)?,
+            Err(_) => continue,
+        };
+        let start = class
+            .windows(9)
+            .position(|window| window == b"OptiFine_")
+            .ok_or(anyhow!("No OptiFine version in {name}"))?;
+        let version = class[start..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_' || **c == b'.')
+            .map(|c| *c as char)
+            .collect();
+        return Ok(version);
+    }
+    Err(anyhow!(
+        "The Config class is not found, not an OptiFine installer"
+    ))
+}
+
+/// Install OptiFine from its installer jar without running java, return the installed version
+/// id, like `1.20.1-OptiFine_HD_U_I6`.
+///
+/// This does what the installer does: patch the vanilla jar into the OptiFine library, copy the
+/// bundled launchwrapper and write the version json. The vanilla version must be installed.
+pub async fn install_optifine_without_java<P: AsRef<Path>>(
+    minecraft: &MinecraftLocation,
+    installer_path: P,
+    options: Option<InstallOptifineOptions>,
+) -> Result<String> {
+    let options = options.unwrap_or_default();
+    let installer_path = installer_path.as_ref().to_path_buf();
+    let patch_minecraft = minecraft.clone();
+    let PatchedOptifine {
+        minecraft_version,
+        edition,
+        library_version,
+        launchwrapper_library,
+    } = tokio::task::spawn_blocking(move || patch_optifine(&patch_minecraft, &installer_path))
+        .await??;
+
+    let inherits_from = options.inherits_from.unwrap_or(minecraft_version);
+    let id = options
+        .version_id
+        .unwrap_or_else(|| format!("{inherits_from}-OptiFine_{edition}"));
+    let tweak_class = match options.use_forge_tweaker {
+        Some(true) => "optifine.OptiFineForgeTweaker",
+        _ => "optifine.OptiFineTweaker",
+    };
+    let parent = Version::from_versions_folder(minecraft.clone(), &inherits_from).await?;
+    let mut version_json = json!({
+        "id": id,
+        "inheritsFrom": inherits_from,
+        "type": "release",
+        "time": parent.time,
+        "releaseTime": parent.release_time,
+        "mainClass": "net.minecraft.launchwrapper.Launch",
+        "minimumLauncherVersion": 21,
+        "libraries": [
+            { "name": format!("optifine:OptiFine:{library_version}") },
+            { "name": launchwrapper_library },
+        ],
+    });
+    match parent.minecraft_arguments {
+        Some(arguments) => {
+            version_json["minecraftArguments"] =
+                format!("{arguments} --tweakClass {tweak_class}").into();
+        }
+        None => {
+            version_json["arguments"] = json!({ "game": ["--tweakClass", tweak_class] });
+        }
+    }
+    let json_path = minecraft.get_version_json(&id);
+    fs::create_dir_all(json_path.parent().unwrap()).await?;
+    fs::write(json_path, serde_json::to_string_pretty(&version_json)?).await?;
+    Ok(id)
+}
+
+/// The OptiFine library and launchwrapper written from an installer
+struct PatchedOptifine {
+    minecraft_version: String,
+    edition: String,
+    library_version: String,
+    launchwrapper_library: String,
+}
+
+/// Patch the vanilla jar into the OptiFine library and copy the bundled launchwrapper. This reads
+/// and rewrites the whole vanilla jar, so it's run off the async runtime.
+fn patch_optifine(minecraft: &MinecraftLocation, installer_path: &Path) -> Result<PatchedOptifine> {
+    let mut installer = ZipArchive::new(std::fs::File::open(installer_path)?)?;
+    let optifine_version = read_optifine_version(&mut installer)?;
+    let (minecraft_version, edition) = optifine_version
+        .trim_start_matches("OptiFine_")
+        .split_once('_')
+        .ok_or(anyhow!("Malformed OptiFine version {optifine_version}"))?;
+    let library_version = format!("{minecraft_version}_{edition}");
+
+    let vanilla_jar = minecraft.get_version_jar(minecraft_version, None);
+    let mut vanilla = ZipArchive::new(std::fs::File::open(&vanilla_jar).map_err(|err| {
+        anyhow!("Cannot open the Minecraft {minecraft_version} jar, install it first: {err}")
+    })?)?;
+    let library = minecraft.get_library_by_path(format!(
+        "optifine/OptiFine/{library_version}/OptiFine-{library_version}.jar"
+    ));
+    std::fs::create_dir_all(library.parent().unwrap())?;
+    patch_jar(
+        &mut vanilla,
+        &mut installer,
+        std::fs::File::create(&library)?,
+    )?;
+
+    let mut launchwrapper = String::new();
+    let launchwrapper = match installer.by_name("launchwrapper-of.txt") {
+        Ok(mut file) => {
+            file.read_to_string(&mut launchwrapper)?;
+            Some(launchwrapper.trim().to_string())
+        }
+        Err(_) => None,
+    };
+    let launchwrapper_library = match launchwrapper {
+        Some(launchwrapper) => {
+            let mut jar = Vec::new();
+            installer
+                .by_name(&format!("launchwrapper-of-{launchwrapper}.jar"))?
+                .read_to_end(&mut jar)?;
+            let path = minecraft.get_library_by_path(format!(
+                "optifine/launchwrapper-of/{launchwrapper}/launchwrapper-of-{launchwrapper}.jar"
+            ));
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, jar)?;
+            format!("optifine:launchwrapper-of:{launchwrapper}")
+        }
+        None => "net.minecraft:launchwrapper:1.12".to_string(),
+    };
+    Ok(PatchedOptifine {
+        minecraft_version: minecraft_version.to_string(),
+        edition: edition.to_string(),
+        library_version,
+        launchwrapper_library,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn jar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, Default::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_install_optifine_without_java() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        std::fs::create_dir_all(minecraft.get_version_root("1.20.1")).unwrap();
+        std::fs::write(
+            minecraft.get_version_json("1.20.1"),
+            r#"{ "id": "1.20.1", "time": "2023-06-12T12:00:00+00:00", "releaseTime": "2023-06-12T12:00:00+00:00" }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            minecraft.get_version_jar("1.20.1", None),
+            jar(&[("ehe.class", b"vanilla class")]),
+        )
+        .unwrap();
+        // copy "vanilla " from the base class, then add "optifine"
+        let mut delta = vec![0xd1, 0xff, 0xd1, 0xff, 4, 249, 0, 0, 8, 8];
+        delta.extend_from_slice(b"optifine");
+        delta.push(0);
+        let installer = crate::utils::download::test::temp_dir().with_extension("jar");
+        std::fs::write(
+            &installer,
+            jar(&[
+                (
+                    "net/optifine/Config.class",
+                    b"\x01\x00\x17OptiFine_1.20.1_HD_U_I6\x01",
+                ),
+                ("patch/patch.cfg", b"# base classes\n(.*)=*\n"),
+                ("patch/ehe.class.xdelta", &delta),
+                ("patch/ehe.class.md5", b"0"),
+                ("launchwrapper-of.txt", b"2.3\n"),
+                ("launchwrapper-of-2.3.jar", b"launchwrapper"),
+            ]),
+        )
+        .unwrap();
+
+        let id = install_optifine_without_java(&minecraft, &installer, None)
+            .await
+            .unwrap();
+        assert_eq!(id, "1.20.1-OptiFine_HD_U_I6");
+        let version_json: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(minecraft.get_version_json(&id)).unwrap())
+                .unwrap();
+        assert_eq!(version_json["inheritsFrom"], "1.20.1");
+        assert_eq!(
+            version_json["mainClass"],
+            "net.minecraft.launchwrapper.Launch"
+        );
+        assert_eq!(
+            version_json["libraries"][0]["name"],
+            "optifine:OptiFine:1.20.1_HD_U_I6"
+        );
+        assert_eq!(
+            version_json["libraries"][1]["name"],
+            "optifine:launchwrapper-of:2.3"
+        );
+        assert_eq!(
+            version_json["arguments"]["game"],
+            json!(["--tweakClass", "optifine.OptiFineTweaker"])
+        );
+
+        let library = minecraft
+            .get_library_by_path("optifine/OptiFine/1.20.1_HD_U_I6/OptiFine-1.20.1_HD_U_I6.jar");
+        let mut library = ZipArchive::new(std::fs::File::open(library).unwrap()).unwrap();
+        let mut class = String::new();
+        library
+            .by_name("ehe.class")
+            .unwrap()
+            .read_to_string(&mut class)
+            .unwrap();
+        assert_eq!(class, "vanilla optifine");
+        assert!(library.by_name("ehe.class.md5").is_err());
+        assert!(library.by_name("patch/ehe.class.md5").is_err());
+        assert!(library.by_name("patch/patch.cfg").is_err());
+        assert!(minecraft
+            .get_library_by_path("optifine/launchwrapper-of/2.3/launchwrapper-of-2.3.jar")
+            .is_file());
+        std::fs::remove_file(installer).unwrap();
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+}
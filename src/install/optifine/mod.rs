@@ -17,12 +17,14 @@
  */
 
 pub mod install;
+pub mod patcher;
 pub mod version_list;
 
 const DEFAULT_META_URL: &str = "https://download.mcbbs.net/optifine";
 
 // todo: 支持optifine安装选项
 
+#[derive(Default)]
 pub struct InstallOptifineOptions {
     /// Use "optifine.OptiFineForgeTweaker" instead of "optifine.OptiFineTweaker" for tweakClass.
     ///
@@ -0,0 +1,211 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! The jar patcher of the OptiFine installer, to install OptiFine without java
+//!
+//! The installer ships the modified classes as GDIFF deltas in `patch/<name>.xdelta`, applied to
+//! the classes of the vanilla jar chosen by `patch/patch.cfg`.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+const PATCH_PREFIX: &str = "patch/";
+const DELTA_SUFFIX: &str = ".xdelta";
+const PATCH_CONFIG: &str = "patch/patch.cfg";
+
+/// Apply a GDIFF delta to `base`, see <https://www.w3.org/TR/NOTE-gdiff-19970901>
+pub fn apply_gdiff(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = delta;
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if header != [0xd1, 0xff, 0xd1, 0xff, 4] {
+        return Err(anyhow!("Not a GDIFF delta"));
+    }
+    let mut output = Vec::new();
+    loop {
+        let command = read_uint(&mut reader, 1)?;
+        let (position, length) = match command {
+            0 => return Ok(output),
+            1..=246 => (None, command),
+            247 => (None, read_uint(&mut reader, 2)?),
+            248 => (None, read_uint(&mut reader, 4)?),
+            249 => (Some(read_uint(&mut reader, 2)?), read_uint(&mut reader, 1)?),
+            250 => (Some(read_uint(&mut reader, 2)?), read_uint(&mut reader, 2)?),
+            251 => (Some(read_uint(&mut reader, 2)?), read_uint(&mut reader, 4)?),
+            252 => (Some(read_uint(&mut reader, 4)?), read_uint(&mut reader, 1)?),
+            253 => (Some(read_uint(&mut reader, 4)?), read_uint(&mut reader, 2)?),
+            254 => (Some(read_uint(&mut reader, 4)?), read_uint(&mut reader, 4)?),
+            _ => (Some(read_uint(&mut reader, 8)?), read_uint(&mut reader, 4)?),
+        };
+        // the lengths come from the delta, don't trust them before checking
+        let length = usize::try_from(length)?;
+        match position {
+            None => {
+                if length > reader.len() {
+                    return Err(anyhow!("GDIFF data past the end of the delta"));
+                }
+                let start = output.len();
+                output.resize(start + length, 0);
+                reader.read_exact(&mut output[start..])?;
+            }
+            Some(position) => {
+                let copied = usize::try_from(position)
+                    .ok()
+                    .and_then(|start| Some(start..start.checked_add(length)?))
+                    .and_then(|range| base.get(range))
+                    .ok_or(anyhow!("GDIFF copy out of the base"))?;
+                output.extend_from_slice(copied);
+            }
+        }
+    }
+}
+
+fn read_uint(reader: &mut &[u8], bytes: usize) -> Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf[8 - bytes..])?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// The rules of `patch/patch.cfg`, like `(.*)\.class=$1.class`, mapping a patched entry to the
+/// entry of the vanilla jar it is based on. `*` means the same name.
+fn read_patch_config<R: Read + Seek>(
+    installer: &mut ZipArchive<R>,
+) -> Result<Vec<(Regex, String)>> {
+    let mut config = String::new();
+    match installer.by_name(PATCH_CONFIG) {
+        Ok(mut file) => file.read_to_string(&mut config)?,
+        Err(_) => return Ok(vec![(Regex::new("^.*$")?, "*".to_string())]),
+    };
+    let mut rules = Vec::new();
+    for line in config.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((pattern, base)) = line.split_once('=') {
+            let pattern = Regex::new(&format!("^(?:{})$", pattern.trim()))?;
+            rules.push((pattern, base.trim().to_string()));
+        }
+    }
+    Ok(rules)
+}
+
+fn patch_base(name: &str, rules: &[(Regex, String)]) -> Option<String> {
+    let (pattern, base) = rules.iter().find(|(pattern, _)| pattern.is_match(name))?;
+    if base == "*" {
+        return Some(name.to_string());
+    }
+    let captures = pattern.captures(name)?;
+    let mut base = base.clone();
+    for group in (1..captures.len()).rev() {
+        let value = captures.get(group).map_or("", |m| m.as_str());
+        base = base.replace(&format!("${group}"), value);
+    }
+    Some(base)
+}
+
+/// Write the OptiFine library jar: the entries of the installer, with the deltas in `patch/`
+/// applied to the vanilla jar. The rest of `patch/` is left out, like the config and the `.md5`
+/// checksums of the patched classes, which are not verified.
+pub fn patch_jar<B, I, W>(
+    vanilla: &mut ZipArchive<B>,
+    installer: &mut ZipArchive<I>,
+    output: W,
+) -> Result<()>
+where
+    B: Read + Seek,
+    I: Read + Seek,
+    W: Write + Seek,
+{
+    let rules = read_patch_config(installer)?;
+    let mut vanilla_entries = HashMap::new();
+    let mut writer = ZipWriter::new(output);
+    for i in 0..installer.len() {
+        let mut entry = installer.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        let name = entry.name().to_string();
+        let patched = name
+            .strip_prefix(PATCH_PREFIX)
+            .and_then(|name| name.strip_suffix(DELTA_SUFFIX));
+        if let Some(patched) = patched {
+            let base_name = patch_base(patched, &rules)
+                .ok_or(anyhow!("No base in the vanilla jar for {patched}"))?;
+            if !vanilla_entries.contains_key(&base_name) {
+                let mut base = Vec::new();
+                vanilla.by_name(&base_name)?.read_to_end(&mut base)?;
+                vanilla_entries.insert(base_name.clone(), base);
+            }
+            writer.start_file(patched, FileOptions::default())?;
+            writer.write_all(&apply_gdiff(&vanilla_entries[&base_name], &content)?)?;
+        } else if !name.starts_with(PATCH_PREFIX) {
+            writer.start_file(name, FileOptions::default())?;
+            writer.write_all(&content)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_gdiff() {
+        let base = b"public class Config";
+        // copy "public class " from the base, then add "OptiFine"
+        let mut delta = vec![0xd1, 0xff, 0xd1, 0xff, 4, 249, 0, 0, 13, 8];
+        delta.extend_from_slice(b"OptiFine");
+        delta.push(0);
+        assert_eq!(apply_gdiff(base, &delta).unwrap(), b"public class OptiFine");
+        delta[8] = 100;
+        assert!(apply_gdiff(base, &delta).is_err());
+        assert!(apply_gdiff(base, b"not a delta").is_err());
+
+        // the lengths of the delta are checked against the delta and the base
+        let data_past_end = [0xd1, 0xff, 0xd1, 0xff, 4, 248, 0xff, 0xff, 0xff, 0xff, b'a'];
+        assert!(apply_gdiff(base, &data_past_end).is_err());
+        let mut copy_overflow = vec![0xd1, 0xff, 0xd1, 0xff, 4, 255];
+        copy_overflow.extend_from_slice(&u64::MAX.to_be_bytes());
+        copy_overflow.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        assert!(apply_gdiff(base, &copy_overflow).is_err());
+    }
+
+    #[test]
+    fn test_patch_base() {
+        let rules = vec![
+            (
+                Regex::new(r"^(?:(.*)_patched\.class)$").unwrap(),
+                "$1.class".to_string(),
+            ),
+            (Regex::new(r"^(?:.*)$").unwrap(), "*".to_string()),
+        ];
+        assert_eq!(
+            patch_base("dxo_patched.class", &rules).unwrap(),
+            "dxo.class"
+        );
+        assert_eq!(patch_base("net/a.class", &rules).unwrap(), "net/a.class");
+    }
+}
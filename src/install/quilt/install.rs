@@ -16,24 +16,55 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use anyhow::Result;
 use tokio::fs::{self, create_dir_all};
 
-use crate::core::{folder::MinecraftLocation, version::Version};
+use crate::core::{
+    folder::MinecraftLocation,
+    http_client,
+    version::{ResolvedVersion, Version},
+    PlatformInfo,
+};
+use crate::install::DownloadSource;
 
 use super::DEFAULT_META_URL;
 
+/// The quilt meta base url of the download source. The mirrors don't serve the quilt meta, so
+/// they use the official one too
+fn meta_base(source: &DownloadSource) -> &str {
+    match source {
+        DownloadSource::Official
+        | DownloadSource::BmclApi
+        | DownloadSource::Mcbbs
+        | DownloadSource::Custom { .. } => DEFAULT_META_URL,
+    }
+}
+
+/// The url of the launcher profile json of the quilt loader, which is what the quilt installer
+/// writes to the versions folder
+pub fn installer_url(mcversion: &str, quilt_version: &str, source: &DownloadSource) -> String {
+    profile_url(meta_base(source), mcversion, quilt_version)
+}
+
+fn profile_url(meta_base: &str, mcversion: &str, quilt_version: &str) -> String {
+    format!("{meta_base}/v3/versions/loader/{mcversion}/{quilt_version}/profile/json")
+}
+
+/// Install the quilt version json, and return the resolved version of it
 pub async fn install_quilt_version_json(
     mcversion: &str,
     quilt_version: &str,
     minecraft: MinecraftLocation,
+    platform: &PlatformInfo,
     remote: Option<String>,
-) {
-    let remote = remote.unwrap_or(DEFAULT_META_URL.to_string());
-    let url = format!("{remote}/v3/versions/loader/{mcversion}/{quilt_version}/profile/json");
+) -> Result<ResolvedVersion> {
+    let url = match remote {
+        Some(remote) => profile_url(&remote, mcversion, quilt_version),
+        None => installer_url(mcversion, quilt_version, &DownloadSource::Official),
+    };
+    let response = http_client().get(url).send().await?.error_for_status()?;
 
-    let response = reqwest::get(url).await.unwrap();
-
-    let quilt_version: Version = response.json().await.unwrap();
+    let quilt_version: Version = response.json().await?;
 
     let version_name = quilt_version.id.clone();
 
@@ -44,19 +75,65 @@ pub async fn install_quilt_version_json(
     //     Some(name) => name.starts_with("org.quiltmc:hashed"),
     // });
 
-    create_dir_all(json_path.parent().unwrap()).await.unwrap();
-    fs::write(
-        json_path,
-        serde_json::to_string_pretty(&quilt_version).unwrap(),
-    )
-    .await
-    .unwrap();
+    if let Some(parent) = json_path.parent() {
+        create_dir_all(parent).await?;
+    }
+    fs::write(json_path, serde_json::to_string_pretty(&quilt_version)?).await?;
+    quilt_version.parse(&minecraft, platform).await
 }
 
-// #[tokio::test]
-// async fn test() {
-//     let mcversion = "1.19.3";
-//     let quilt_version = "0.19.1";
-//     let minecraft = MinecraftLocation::new("test");
-//     install_quilt_version(mcversion, quilt_version, minecraft, None).await;
-// }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::version::test::{vanilla_json, write_version};
+    use crate::utils::download::test::{serve, temp_dir};
+
+    #[tokio::test]
+    async fn test_install_quilt_version_json() {
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        write_version(
+            &minecraft,
+            "1.19.3",
+            &vanilla_json("1.19.3", "2", serde_json::Value::Null).to_string(),
+        );
+        let profile = r#"{
+            "id": "quilt-loader-0.19.1-1.19.3",
+            "inheritsFrom": "1.19.3",
+            "type": "release",
+            "mainClass": "org.quiltmc.loader.impl.launch.knot.KnotClient",
+            "libraries": [
+                { "name": "org.quiltmc:quilt-loader:0.19.1", "url": "https://maven.quiltmc.org/repository/release/" }
+            ]
+        }"#;
+        let remote = serve("200 OK", profile.as_bytes().to_vec()).await;
+        let platform = PlatformInfo::new().await;
+
+        let version = install_quilt_version_json(
+            "1.19.3",
+            "0.19.1",
+            minecraft.clone(),
+            &platform,
+            Some(remote),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            version.main_class,
+            "org.quiltmc.loader.impl.launch.knot.KnotClient"
+        );
+        assert!(minecraft
+            .get_version_json("quilt-loader-0.19.1-1.19.3")
+            .is_file());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[test]
+    fn test_installer_url() {
+        for source in [DownloadSource::Official, DownloadSource::BmclApi] {
+            assert_eq!(
+                installer_url("1.20.1", "0.21.0", &source),
+                "https://meta.quiltmc.org/v3/versions/loader/1.20.1/0.21.0/profile/json"
+            );
+        }
+    }
+}
@@ -18,6 +18,8 @@
 
 use anyhow::Result;
 
+use crate::utils::cache::fetch_json;
+
 use super::{QuiltArtifactVersion, QuiltVersion, DEFAULT_META_URL};
 
 pub async fn get_quilt_version_list(remote: Option<String>) -> Result<Vec<QuiltArtifactVersion>> {
@@ -26,8 +28,7 @@ pub async fn get_quilt_version_list(remote: Option<String>) -> Result<Vec<QuiltA
         Some(remote) => remote,
     };
     let url = format!("{remote}/v3/versions/loader");
-    let response = reqwest::get(url).await?;
-    Ok(response.json().await?)
+    fetch_json(&url).await
 }
 
 pub async fn get_quilt_version_list_from_mcversion(
@@ -39,6 +40,5 @@ pub async fn get_quilt_version_list_from_mcversion(
         Some(remote) => remote,
     };
     let url = format!("{remote}/v3/versions/loader/{mcversion}");
-    let response = reqwest::get(url).await?;
-    Ok(response.json().await?)
+    fetch_json(&url).await
 }
@@ -0,0 +1,294 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Install the Java runtimes provided by Mojang
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::core::{
+    folder::{runtime_os_name, MinecraftLocation},
+    http_client,
+    task::TaskEventListeners,
+    Download, JavaExec, PlatformInfo,
+};
+use crate::install::missing_downloads;
+use crate::utils::{
+    disk::{check_disk_space, download_size},
+    download::{download_files_with, Downloader, DEFAULT_CONCURRENCY},
+    unzip::sanitize_entry_name,
+};
+
+/// The index of all Java runtimes provided by Mojang
+pub const JAVA_RUNTIME_MANIFEST: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuntimeDownload {
+    sha1: String,
+    size: u64,
+    url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuntimeManifestEntry {
+    manifest: RuntimeDownload,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuntimeFileDownloads {
+    raw: RuntimeDownload,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RuntimeFile {
+    File {
+        downloads: RuntimeFileDownloads,
+        #[serde(default)]
+        executable: bool,
+    },
+    Directory,
+    Link {
+        target: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuntimeFiles {
+    files: BTreeMap<String, RuntimeFile>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct RuntimeInstallPlan {
+    directories: Vec<PathBuf>,
+    downloads: Vec<Download>,
+    executables: Vec<PathBuf>,
+    links: Vec<(PathBuf, String)>,
+}
+
+/// Resolve the files of a runtime into the folder `root`
+fn plan_runtime_files(files: RuntimeFiles, root: &Path) -> Result<RuntimeInstallPlan> {
+    let mut plan = RuntimeInstallPlan::default();
+    for (name, file) in files.files {
+        let path = root.join(sanitize_entry_name(&name)?);
+        match file {
+            RuntimeFile::Directory => plan.directories.push(path),
+            RuntimeFile::File {
+                downloads,
+                executable,
+            } => {
+                if executable {
+                    plan.executables.push(path.clone());
+                }
+                plan.downloads.push(Download {
+                    url: downloads.raw.url,
+                    file: path,
+                    sha1: Some(downloads.raw.sha1),
+                    size: Some(downloads.raw.size),
+                });
+            }
+            RuntimeFile::Link { target } => {
+                check_link_target(&name, &target)?;
+                plan.links.push((path, target));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Check that the target of the link `name` is relative, and stays inside the runtime folder
+fn check_link_target(name: &str, target: &str) -> Result<()> {
+    let malicious = || anyhow!("malicious link in java runtime: {name} -> {target}");
+    let mut depth = Path::new(name).components().count().saturating_sub(1);
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => depth = depth.checked_sub(1).ok_or_else(malicious)?,
+            Component::RootDir | Component::Prefix(_) => return Err(malicious()),
+        }
+    }
+    Ok(())
+}
+
+/// Download the Java runtime of the component, like `java-runtime-gamma`, into the `runtime`
+/// folder with the downloader, and return the java executable of it.
+///
+/// The files already downloaded are skipped, and all downloaded files are verified by sha1.
+pub async fn install_jre(
+    component: &str,
+    minecraft: &MinecraftLocation,
+    platform: &PlatformInfo,
+    downloader: Arc<dyn Downloader>,
+    listeners: &TaskEventListeners,
+) -> Result<JavaExec> {
+    let os = runtime_os_name(platform);
+    let all: HashMap<String, HashMap<String, Vec<RuntimeManifestEntry>>> = http_client()
+        .get(JAVA_RUNTIME_MANIFEST)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let entry = all
+        .get(os)
+        .and_then(|components| components.get(component))
+        .and_then(|entries| entries.first())
+        .ok_or(anyhow!(
+            "java runtime {component} is not available for {os}"
+        ))?;
+    let files: RuntimeFiles = http_client()
+        .get(&entry.manifest.url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let plan = plan_runtime_files(files, &minecraft.get_runtime_root(component, platform))?;
+    for directory in &plan.directories {
+        tokio::fs::create_dir_all(directory).await?;
+    }
+    // the downloads are verified by sha1 as they finish, the ones left are already checked here
+    let downloads = missing_downloads(plan.downloads).await?;
+    check_disk_space(&minecraft.root, download_size(&downloads)).await?;
+    download_files_with(downloads, listeners, downloader, DEFAULT_CONCURRENCY, false).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        for executable in &plan.executables {
+            std::fs::set_permissions(executable, std::fs::Permissions::from_mode(0o755))?;
+        }
+        for (link, target) in &plan.links {
+            if link.symlink_metadata().is_ok() {
+                std::fs::remove_file(link)?;
+            }
+            if let Some(parent) = link.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::os::unix::fs::symlink(target, link)?;
+        }
+    }
+
+    JavaExec::for_component(component, minecraft, platform).ok_or(anyhow!(
+        "java executable is not found in runtime {component}"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plan_runtime_files() {
+        let files: RuntimeFiles = serde_json::from_str(
+            r#"{
+                "files": {
+                    "bin": { "type": "directory" },
+                    "bin/java": {
+                        "type": "file",
+                        "executable": true,
+                        "downloads": {
+                            "lzma": { "sha1": "1", "size": 1, "url": "https://example.com/java.lzma" },
+                            "raw": { "sha1": "2", "size": 2, "url": "https://example.com/java" }
+                        }
+                    },
+                    "legal/LICENSE": { "type": "link", "target": "../LICENSE" },
+                    "release": {
+                        "type": "file",
+                        "executable": false,
+                        "downloads": { "raw": { "sha1": "3", "size": 3, "url": "https://example.com/release" } }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let root = Path::new("runtime");
+        let plan = plan_runtime_files(files, root).unwrap();
+        assert_eq!(
+            plan,
+            RuntimeInstallPlan {
+                directories: vec![root.join("bin")],
+                downloads: vec![
+                    Download {
+                        url: "https://example.com/java".to_string(),
+                        file: root.join("bin/java"),
+                        sha1: Some("2".to_string()),
+                        size: Some(2),
+                    },
+                    Download {
+                        url: "https://example.com/release".to_string(),
+                        file: root.join("release"),
+                        sha1: Some("3".to_string()),
+                        size: Some(3),
+                    },
+                ],
+                executables: vec![root.join("bin/java")],
+                links: vec![(root.join("legal/LICENSE"), "../LICENSE".to_string())],
+            }
+        );
+
+        let files: RuntimeFiles =
+            serde_json::from_str(r#"{ "files": { "../evil": { "type": "directory" } } }"#).unwrap();
+        assert!(plan_runtime_files(files, root).is_err());
+    }
+
+    #[test]
+    fn test_check_link_target() {
+        assert!(check_link_target("legal/LICENSE", "../LICENSE").is_ok());
+        assert!(check_link_target("bin/java", "./java.real").is_ok());
+        assert!(check_link_target("legal/java.base/LICENSE", "../../release").is_ok());
+        assert!(check_link_target("LICENSE", "../LICENSE").is_err());
+        assert!(check_link_target("legal/LICENSE", "../../../etc/passwd").is_err());
+        assert!(check_link_target("legal/LICENSE", "/etc/passwd").is_err());
+
+        let files: RuntimeFiles = serde_json::from_str(
+            r#"{ "files": { "release": { "type": "link", "target": "/etc/passwd" } } }"#,
+        )
+        .unwrap();
+        assert!(plan_runtime_files(files, Path::new("runtime")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_runtime_files() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let download = Download {
+            url: String::new(),
+            file: root.join("file"),
+            sha1: Some("A9993E364706816ABA3E25717850C26C9CD0D89D".to_string()),
+            size: Some(3),
+        };
+        std::fs::write(&download.file, "abc").unwrap();
+        assert!(missing_downloads(vec![download.clone()])
+            .await
+            .unwrap()
+            .is_empty());
+        std::fs::write(&download.file, "abd").unwrap();
+        assert_eq!(
+            missing_downloads(vec![download.clone()]).await.unwrap(),
+            vec![download]
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
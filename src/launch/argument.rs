@@ -20,22 +20,31 @@ use std::{
     collections::{HashMap, HashSet},
     env,
     path::PathBuf,
+    sync::Arc,
 };
 
 use anyhow::Result;
 use regex::Regex;
+use serde_json::Value;
 use tokio::{fs, process::Command};
 use zip::ZipArchive;
 
 use crate::{
     core::{
-        folder::MinecraftLocation, version::ResolvedVersion, JavaExec, OsType, PlatformInfo,
-        DELIMITER,
+        folder::MinecraftLocation,
+        task::TaskEventListeners,
+        version::{check_allowed_with_features, ResolvedVersion},
+        JavaExec, OsType, PlatformInfo, DELIMITER,
+    },
+    install::{check_game_integrity, install_virtual_assets_async},
+    utils::{
+        disk::{check_disk_space, download_size},
+        download::{download_files_with, Downloader, HttpDownloader, DEFAULT_CONCURRENCY},
+        unzip::decompression_natives,
     },
-    utils::unzip::decompression_all,
 };
 
-use super::options::{LaunchOptions, ProcessPriority, UserType, GC};
+use super::options::{ClasspathPosition, LaunchOptions, ProcessPriority, UserType, GC};
 
 /// launch arguments for launch
 ///
@@ -44,6 +53,13 @@ use super::options::{LaunchOptions, ProcessPriority, UserType, GC};
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct LaunchArguments(Vec<String>);
 
+/// The jndi and log4j hardening flags in the default jvm arguments
+const SECURITY_JVM_FLAGS: &[&str] = &[
+    "-Dcom.sun.jndi.rmi.object.trustURLCodebase=",
+    "-Dcom.sun.jndi.cosnaming.object.trustURLCodebase=",
+    "-Dlog4j2.formatMsgNoLookups=",
+];
+
 const DEFAULT_GAME_ICON: &[u8] = include_bytes!("./assets/minecraft.icns");
 
 impl LaunchArguments {
@@ -52,13 +68,23 @@ impl LaunchArguments {
         version: ResolvedVersion,
         platform: &PlatformInfo,
     ) -> Result<Self> {
-        let minecraft = MinecraftLocation::new(&launch_options.resource_path);
+        if launch_options.access_token.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "access_token is empty, offline accounts can use any placeholder"
+            ));
+        }
+        let mut minecraft = MinecraftLocation::new(&launch_options.resource_path);
+        minecraft.assets = launch_options.minecraft_location.assets.clone();
 
         let game_icon = match launch_options.game_icon.clone() {
             Some(icon_path) => icon_path,
             None => {
                 let icon_path = minecraft.assets.join("minecraft.icns");
-                tokio::fs::write(&icon_path, DEFAULT_GAME_ICON).await?;
+                let icon = launch_options
+                    .default_game_icon
+                    .as_deref()
+                    .unwrap_or(DEFAULT_GAME_ICON);
+                tokio::fs::write(&icon_path, icon).await?;
                 icon_path
             }
         };
@@ -87,6 +113,11 @@ impl LaunchArguments {
         command_arguments.push(format!("-Xms{}M", launch_options.min_memory));
         command_arguments.push(format!("-Xmx{}M", launch_options.max_memory));
 
+        command_arguments.extend(launcher_brand_arguments(
+            &launch_options.launcher_name,
+            &launch_options.launcher_version,
+        ));
+
         if launch_options.ignore_invalid_minecraft_certificates {
             command_arguments.push("-Dfml.ignoreInvalidMinecraftCertificates=true".to_string());
         }
@@ -139,7 +170,25 @@ impl LaunchArguments {
             }
         }
 
-        let mut jvm_options: HashMap<&str, String> = HashMap::new();
+        if let Some(overrides) = launch_options.api_overrides.clone() {
+            let hosts = [
+                ("auth", overrides.auth_host),
+                ("account", overrides.account_host),
+                ("session", overrides.session_host),
+                ("services", overrides.services_host),
+            ];
+            for (api, host) in hosts {
+                if let Some(host) = host {
+                    command_arguments.push(format!("-Dminecraft.api.{api}.host={host}"));
+                }
+            }
+        }
+
+        let template_values = launch_options
+            .template_values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()));
+        let mut jvm_options: HashMap<&str, String> = template_values.clone().collect();
         jvm_options.insert(
             "natives_directory",
             launch_options.native_path.to_string_lossy().to_string(),
@@ -156,32 +205,61 @@ impl LaunchArguments {
             ),
         );
 
-        let mut jvm_arguments = version.arguments.clone().unwrap().jvm;
+        let arguments = version.arguments.clone().ok_or(anyhow::anyhow!(
+            "arguments is not found! version is not resolved correctly"
+        ))?;
+        let mut jvm_arguments = arguments.jvm;
         if let Some(logging) = version.logging {
             if let Some(client) = logging.get("client") {
                 let argument = &client.argument;
                 let file_path = minecraft.get_version_root(&version.id).join("log4j2.xml");
                 if tokio::fs::try_exists(&file_path).await? {
-                    jvm_arguments.push(format!(
-                        "\"{}\"",
-                        argument.replace("${path}", &file_path.to_string_lossy().to_string())
-                    ));
+                    jvm_arguments.push(argument.replace("${path}", &file_path.to_string_lossy()));
                 }
             }
         }
 
         command_arguments.extend(
             jvm_arguments
+                .iter()
+                // the brand flags are always emitted above
+                .filter(|arg| !arg.contains("-Dminecraft.launcher."))
+                .filter(|arg| {
+                    launch_options.security_hardening
+                        || !SECURITY_JVM_FLAGS.iter().any(|flag| arg.contains(flag))
+                })
+                .map(|arg| format(arg, jvm_options.clone())),
+        );
+        for arg in arguments.feature_jvm {
+            let rules = arg["rules"].as_array().cloned().unwrap_or_default();
+            if !check_allowed_with_features(rules, platform, &launch_options.features) {
+                continue;
+            }
+            let values = match &arg["value"] {
+                Value::String(value) => vec![value.clone()],
+                Value::Array(values) => values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(String::from))
+                    .collect(),
+                _ => vec![],
+            };
+            command_arguments.extend(
+                values
+                    .iter()
+                    .map(|value| format(value, jvm_options.clone())),
+            );
+        }
+        command_arguments.extend(
+            split_arguments(launch_options.extra_jvm_args)
                 .iter()
                 .map(|arg| format(arg, jvm_options.clone())),
         );
-        command_arguments.extend(launch_options.extra_jvm_args);
 
         command_arguments.push(version.main_class);
 
-        let mut game_options = HashMap::with_capacity(13);
+        let mut game_options: HashMap<&str, String> = template_values.collect();
 
-        let assets_dir = launch_options.resource_path.join("assets");
+        let assets_dir = minecraft.assets.clone();
         game_options.insert(
             "version_name",
             match launch_options.version_name {
@@ -199,9 +277,8 @@ impl LaunchArguments {
         game_options.insert("assets_root", assets_dir.to_string_lossy().to_string());
         game_options.insert(
             "game_assets",
-            assets_dir
-                .join("virtual")
-                .join(&version.assets)
+            minecraft
+                .get_assets_virtual(&version.assets)
                 .to_string_lossy()
                 .to_string(),
         );
@@ -234,14 +311,16 @@ impl LaunchArguments {
         game_options.insert("resolution_height", launch_options.height.to_string());
 
         command_arguments.extend(
-            version
-                .arguments
-                .unwrap()
+            arguments
                 .game
                 .iter()
                 .map(|arg| format(arg, game_options.clone())),
         );
-        command_arguments.extend(launch_options.extra_mc_args);
+        command_arguments.extend(
+            split_arguments(launch_options.extra_mc_args)
+                .iter()
+                .map(|arg| format(arg, game_options.clone())),
+        );
         if let Some(server) = launch_options.server {
             command_arguments.extend(vec!["--server".to_string(), server.ip]);
             if let Some(port) = server.port {
@@ -251,10 +330,14 @@ impl LaunchArguments {
         if launch_options.fullscreen {
             command_arguments.push("--fullscreen".to_string());
         }
-        let no_width_arguments = None
-            == command_arguments
-                .iter()
-                .find(|v| v == &&"--width".to_string());
+        // the game rejects an option given twice, the last one is kept, like the user's
+        // `extra_mc_args` over the version template
+        for option in ["--width", "--height"] {
+            command_arguments = keep_last_option(command_arguments, option);
+        }
+        let no_width_arguments = !command_arguments
+            .iter()
+            .any(|arg| is_option(arg, "--width"));
         if no_width_arguments && !launch_options.fullscreen {
             command_arguments.extend(vec![
                 "--width".to_string(),
@@ -267,16 +350,68 @@ impl LaunchArguments {
         Ok(LaunchArguments(command_arguments))
     }
 
+    /// The arguments joined with spaces, with the access token and the prefetched yggdrasil
+    /// metadata replaced by `***`, so it can be logged safely
+    pub fn redacted_command_string(&self) -> String {
+        const PREFETCHED: &str = "-Dauthlibinjector.yggdrasil.prefetched=";
+        let mut redacted = Vec::with_capacity(self.0.len());
+        let mut is_secret = false;
+        for argument in &self.0 {
+            if is_secret {
+                redacted.push("***".to_string());
+            } else if argument.starts_with(PREFETCHED) {
+                redacted.push(format!("{PREFETCHED}***"));
+            } else {
+                redacted.push(argument.clone());
+            }
+            is_secret = argument == "--accessToken" || argument == "--session";
+        }
+        redacted.join(" ")
+    }
+
     /// spawn a command instance, you can use this to launch the game
+    ///
+    /// The files repaired by the integrity check are downloaded with the [`HttpDownloader`],
+    /// without reporting the progress. See [`to_async_command_with`](Self::to_async_command_with).
     pub async fn to_async_command(
         &self,
         java_exec: JavaExec,
         launch_options: LaunchOptions,
         platform: &PlatformInfo,
     ) -> Result<std::process::Command> {
+        self.to_async_command_with(
+            java_exec,
+            launch_options,
+            platform,
+            Arc::new(HttpDownloader),
+            &TaskEventListeners::silent(),
+        )
+        .await
+    }
+
+    /// Like [`to_async_command`](Self::to_async_command), with the given downloader and listeners
+    /// for the files repaired by the integrity check
+    pub async fn to_async_command_with(
+        &self,
+        java_exec: JavaExec,
+        launch_options: LaunchOptions,
+        platform: &PlatformInfo,
+        downloader: Arc<dyn Downloader>,
+        listeners: &TaskEventListeners,
+    ) -> Result<std::process::Command> {
+        if launch_options.check_game_integrity {
+            ensure_game_integrity(&launch_options, platform, downloader, listeners).await?;
+        }
+        // skipped if the java can't report its version, it fails on its own then
+        if let Ok(java_major_version) = java_exec.major_version().await {
+            launch_options.gc.check_supported(java_major_version)?;
+        }
         let mut command = format!(
             "cd {}\n",
-            launch_options.version_root.to_string_lossy().to_string()
+            quote_argument(
+                &launch_options.version_root.to_string_lossy(),
+                &platform.os_type
+            )
         );
         match platform.os_type {
             OsType::Windows => {}
@@ -305,19 +440,25 @@ impl LaunchArguments {
             };
         }
         // todo(after java exec): add -Dfile.encoding=encoding.name() and other
-        let mut launch_command = java_exec.binary.to_string_lossy().to_string();
-        launch_command.push_str(" ");
-        launch_command.push_str(&self.0.clone().join(" "));
+        let mut launch_command =
+            quote_argument(&java_exec.binary.to_string_lossy(), &platform.os_type);
+        for argument in &self.0 {
+            launch_command.push(' ');
+            launch_command.push_str(&quote_argument(argument, &platform.os_type));
+        }
         command.push_str(&launch_command);
+        log::debug!(
+            "launch command: {} {}",
+            java_exec.binary.to_string_lossy(),
+            self.redacted_command_string()
+        );
+        let native_path = quote_argument(
+            &launch_options.native_path.to_string_lossy(),
+            &platform.os_type,
+        );
         match platform.os_type {
-            OsType::Windows => command.push_str(&format!(
-                "\ndel /F /Q {}\n",
-                launch_options.native_path.to_string_lossy()
-            )),
-            _ => command.push_str(&format!(
-                "\n rm -rf {}",
-                launch_options.native_path.to_string_lossy()
-            )),
+            OsType::Windows => command.push_str(&format!("\ndel /F /Q {native_path}\n")),
+            _ => command.push_str(&format!("\n rm -rf {native_path}")),
         }
         let script_path = match platform.os_type {
             OsType::Linux => launch_options.version_root.join(".cache").join("launch.sh"),
@@ -362,6 +503,44 @@ impl LaunchArguments {
     }
 }
 
+/// Run the integrity check, and repair the files or fail with the list of them
+async fn ensure_game_integrity(
+    launch_options: &LaunchOptions,
+    platform: &PlatformInfo,
+    downloader: Arc<dyn Downloader>,
+    listeners: &TaskEventListeners,
+) -> Result<()> {
+    let minecraft = &launch_options.minecraft_location;
+    let version = launch_options
+        .version
+        .parse_cached(minecraft, platform)
+        .await?;
+    let issues = check_game_integrity(
+        &version,
+        minecraft,
+        &launch_options.download_source,
+        downloader.as_ref(),
+    )
+    .await?;
+    if issues.is_empty() {
+        return Ok(());
+    }
+    if launch_options.repair_on_launch {
+        check_disk_space(&minecraft.root, download_size(&issues)).await?;
+        // the issues are already checked, download them again without hashing them first
+        download_files_with(issues, listeners, downloader, DEFAULT_CONCURRENCY, false).await?;
+        return install_virtual_assets_async(minecraft, &version.assets).await;
+    }
+    let files = issues
+        .iter()
+        .map(|download| download.file.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    Err(anyhow::anyhow!(
+        "game files are missing or corrupt: {}",
+        files.join(", ")
+    ))
+}
+
 fn resolve_classpath(
     options: &LaunchOptions,
     version: &ResolvedVersion,
@@ -378,7 +557,12 @@ fn resolve_classpath(
                 println!("{:#?},{:#?}", path, native_folder);
                 if let Ok(file) = std::fs::File::open(path) {
                     if let Ok(mut zip_archive) = ZipArchive::new(file) {
-                        decompression_all(&mut zip_archive, &native_folder).unwrap_or(());
+                        if let Ok(skipped) = decompression_natives(&mut zip_archive, &native_folder)
+                        {
+                            for file in skipped {
+                                log::warn!("{} is in use, keep the extracted one", file.display());
+                            }
+                        }
                     }
                 }
             }
@@ -404,11 +588,103 @@ fn resolve_classpath(
     );
 
     if let Some(extra_class_paths) = extra_class_paths {
-        classpath.extend(extra_class_paths);
+        match options.extra_class_paths_position {
+            ClasspathPosition::Prepend => {
+                classpath.splice(0..0, extra_class_paths);
+            }
+            ClasspathPosition::Append => classpath.extend(extra_class_paths),
+        }
     }
     classpath.join(DELIMITER)
 }
 
+/// Split user custom arguments like `"-Xmx4G -XX:+UseG1GC"` into separate arguments.
+///
+/// Whitespace inside quotes does not split, and the quotes are removed like a shell does. The
+/// arguments are quoted again when the launch script is written, see `quote_argument`.
+fn split_arguments(arguments: Vec<String>) -> Vec<String> {
+    let mut result = Vec::new();
+    for argument in arguments {
+        let mut current = String::new();
+        let mut quote = None;
+        let mut quoted = false;
+        for c in argument.chars() {
+            match quote {
+                Some(q) if c == q => {
+                    quote = None;
+                    continue;
+                }
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    quoted = true;
+                    continue;
+                }
+                None if c.is_whitespace() => {
+                    if !current.is_empty() || quoted {
+                        result.push(std::mem::take(&mut current));
+                    }
+                    quoted = false;
+                    continue;
+                }
+                _ => (),
+            }
+            current.push(c);
+        }
+        if !current.is_empty() || quoted {
+            result.push(current);
+        }
+    }
+    result
+}
+
+/// Quote an argument for the launch script, so the shell passes it as one argument as is. The
+/// arguments made of only safe characters are left unquoted to keep the script readable.
+fn quote_argument(argument: &str, os_type: &OsType) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_=+.,:/@".contains(c);
+    if !argument.is_empty() && argument.chars().all(is_safe) {
+        return argument.to_string();
+    }
+    match os_type {
+        OsType::Windows => format!("\"{}\"", argument.replace('"', "\"\"")),
+        _ => format!("'{}'", argument.replace('\'', "'\\''")),
+    }
+}
+
+/// Whether the argument is the option, like `--width` or `--width=854`, in any case
+fn is_option(argument: &str, option: &str) -> bool {
+    let name = argument.split('=').next().unwrap_or_default();
+    name.eq_ignore_ascii_case(option)
+}
+
+/// Remove all but the last occurrence of the option, with their values
+fn keep_last_option(arguments: Vec<String>, option: &str) -> Vec<String> {
+    let last = match arguments.iter().rposition(|arg| is_option(arg, option)) {
+        Some(last) => last,
+        None => return arguments,
+    };
+    let mut result = Vec::with_capacity(arguments.len());
+    let mut arguments = arguments.into_iter().enumerate();
+    while let Some((index, argument)) = arguments.next() {
+        if index < last && is_option(&argument, option) {
+            if !argument.contains('=') {
+                arguments.next();
+            }
+            continue;
+        }
+        result.push(argument);
+    }
+    result
+}
+
+/// The `-Dminecraft.launcher.*` flags, emitted whether or not the version json references
+/// `${launcher_name}` / `${launcher_version}`
+fn launcher_brand_arguments(launcher_name: &str, launcher_version: &str) -> Vec<String> {
+    vec![
+        format!("-Dminecraft.launcher.brand={launcher_name}"),
+        format!("-Dminecraft.launcher.version={launcher_version}"),
+    ]
+}
+
 fn format(template: &str, args: HashMap<&str, String>) -> String {
     let regex = Regex::new(r"\$\{(.*?)}").unwrap();
 
@@ -420,3 +696,567 @@ fn format(template: &str, args: HashMap<&str, String>) -> String {
         })
         .to_string()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::version::test::vanilla_json;
+    use crate::launch::options::ApiOverrides;
+
+    /// The json of the vanilla version `test` with the `extra` fields
+    fn version_json(extra: Value) -> String {
+        vanilla_json("test", "1.19", extra).to_string()
+    }
+
+    async fn setup(version_json: &str) -> (LaunchOptions, ResolvedVersion, PlatformInfo) {
+        let root = env::temp_dir().join(format!("cvl-test-{}", uuid::Uuid::new_v4()));
+        let minecraft = MinecraftLocation::new(&root);
+        fs::create_dir_all(&minecraft.assets).await.unwrap();
+        fs::create_dir_all(minecraft.get_version_root("test"))
+            .await
+            .unwrap();
+        fs::write(minecraft.get_version_json("test"), version_json)
+            .await
+            .unwrap();
+        let platform = PlatformInfo::new().await;
+        let options = LaunchOptions::new("test", &minecraft).await.unwrap();
+        let version = options.version.parse(&minecraft, &platform).await.unwrap();
+        (options, version, platform)
+    }
+
+    #[tokio::test]
+    async fn test_legacy_version() {
+        let legacy_version_json = vanilla_json(
+            "test",
+            "1.12",
+            serde_json::json!({
+                "mainClass": "net.minecraft.launchwrapper.Launch",
+                "minecraftArguments": "--username ${auth_player_name} --version ${version_name}"
+            }),
+        );
+        let (options, version, platform) = setup(&legacy_version_json.to_string()).await;
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert!(arguments
+            .0
+            .contains(&"net.minecraft.launchwrapper.Launch".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_extra_class_paths_position() {
+        let (mut options, version, _) = setup(&version_json(Value::Null)).await;
+        let minecraft = options.minecraft_location.clone();
+        options.extra_class_paths = Some(vec!["/dev/classes".to_string()]);
+        let classpath = |options: &LaunchOptions| {
+            resolve_classpath(
+                options,
+                &version,
+                &minecraft,
+                options.extra_class_paths.clone(),
+            )
+        };
+        assert!(classpath(&options).ends_with(&format!("{DELIMITER}/dev/classes")));
+        let options = options.with_extra_class_paths_position(ClasspathPosition::Prepend);
+        assert!(classpath(&options).starts_with(&format!("/dev/classes{DELIMITER}")));
+    }
+
+    #[tokio::test]
+    async fn test_repair_on_launch() {
+        use sha1::{Digest, Sha1};
+
+        let content = b"library".to_vec();
+        let sha1 = format!("{:x}", Sha1::digest(&content));
+        let url = crate::utils::download::test::serve("200 OK", content.clone()).await;
+        let index = br#"{ "objects": {} }"#.to_vec();
+        let index_size = index.len();
+        let index_url = crate::utils::download::test::serve("200 OK", index).await;
+        let version_json = version_json(serde_json::json!({
+            "assetIndex": {
+                "id": "1.19",
+                "size": index_size,
+                "url": format!("{index_url}/1.19.json"),
+                "totalSize": 1
+            },
+            "downloads": {
+                "client": { "sha1": sha1, "size": 7, "url": format!("{url}/client.jar") }
+            },
+            "libraries": [{
+                "name": "com.example:library:1.0",
+                "downloads": { "artifact": {
+                    "path": "com/example/library/1.0/library-1.0.jar",
+                    "sha1": sha1,
+                    "size": 7,
+                    "url": format!("{url}/library-1.0.jar")
+                } }
+            }]
+        }));
+        let (mut options, _, platform) = setup(&version_json).await;
+        let minecraft = options.minecraft_location.clone();
+        let library = minecraft.get_library_by_path("com/example/library/1.0/library-1.0.jar");
+        let java = JavaExec {
+            binary: PathBuf::from("java"),
+        };
+        let arguments = LaunchArguments(vec![]);
+        options.check_game_integrity = true;
+
+        let err = arguments
+            .to_async_command(
+                java.clone(),
+                options.clone().with_repair_on_launch(false),
+                &platform,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("library-1.0.jar"));
+
+        let repaired = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = repaired.clone();
+        let listeners = TaskEventListeners::silent()
+            .on_file(Box::new(move |_, _| counter.set(counter.get() + 1)));
+        arguments
+            .to_async_command_with(
+                java,
+                options.with_repair_on_launch(true),
+                &platform,
+                Arc::new(HttpDownloader),
+                &listeners,
+            )
+            .await
+            .unwrap();
+        assert_eq!(repaired.get(), 2);
+        assert_eq!(std::fs::read(library).unwrap(), content);
+        assert_eq!(
+            std::fs::read(minecraft.get_version_jar("test", None)).unwrap(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_arguments() {
+        let (options, mut version, platform) = setup(&version_json(Value::Null)).await;
+        version.arguments = None;
+        assert!(
+            LaunchArguments::from_launch_options(options, version, &platform)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_redacted_command_string() {
+        let arguments = LaunchArguments(
+            [
+                "-Dauthlibinjector.yggdrasil.prefetched=eyJtZXRh",
+                "net.minecraft.client.main.Main",
+                "--username",
+                "Steve",
+                "--accessToken",
+                "0123456789abcdef",
+                "--userType",
+                "mojang",
+            ]
+            .map(|argument| argument.to_string())
+            .to_vec(),
+        );
+        assert_eq!(
+            arguments.redacted_command_string(),
+            "-Dauthlibinjector.yggdrasil.prefetched=*** net.minecraft.client.main.Main \
+            --username Steve --accessToken *** --userType mojang"
+        );
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        let arguments = split_arguments(vec![
+            "-Xmx4G  -XX:+UseG1GC -Dsome=val".to_string(),
+            "\"-Dpath=/path/with space\" --name '' -Dtitle='A \"B\"'".to_string(),
+        ]);
+        assert_eq!(
+            arguments,
+            vec![
+                "-Xmx4G",
+                "-XX:+UseG1GC",
+                "-Dsome=val",
+                "-Dpath=/path/with space",
+                "--name",
+                "",
+                "-Dtitle=A \"B\"",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_overrides() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.api_overrides = Some(ApiOverrides {
+            auth_host: Some("https://auth.example.com".to_string()),
+            account_host: Some("https://account.example.com".to_string()),
+            session_host: Some("https://session.example.com".to_string()),
+            services_host: Some("https://services.example.com".to_string()),
+        });
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        for api in ["auth", "account", "session", "services"] {
+            assert!(arguments.0.contains(&format!(
+                "-Dminecraft.api.{api}.host=https://{api}.example.com"
+            )));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_launcher_brand() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.launcher_name = "MyLauncher".to_string();
+        options.launcher_version = "1.2.3".to_string();
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert!(arguments
+            .0
+            .contains(&"-Dminecraft.launcher.brand=MyLauncher".to_string()));
+        assert!(arguments
+            .0
+            .contains(&"-Dminecraft.launcher.version=1.2.3".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_security_hardening() {
+        let (options, version, platform) = setup(&version_json(Value::Null)).await;
+        let is_security_flag =
+            |arg: &String| SECURITY_JVM_FLAGS.iter().any(|flag| arg.contains(flag));
+        let arguments =
+            LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+                .await
+                .unwrap();
+        assert_eq!(
+            arguments.0.iter().filter(|a| is_security_flag(a)).count(),
+            3
+        );
+
+        let options = options.with_security_hardening(false);
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert!(!arguments.0.iter().any(is_security_flag));
+    }
+
+    #[tokio::test]
+    async fn test_version_type() {
+        let (options, version, platform) = setup(&version_json(Value::Null)).await;
+        let version_type = |arguments: &LaunchArguments| {
+            let index = arguments.0.iter().position(|a| a == "--versionType");
+            arguments.0[index.unwrap() + 1].clone()
+        };
+        let arguments =
+            LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+                .await
+                .unwrap();
+        assert_eq!(version_type(&arguments), "release");
+
+        let options = options.with_version_type("Modded");
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert_eq!(version_type(&arguments), "Modded");
+    }
+
+    #[tokio::test]
+    async fn test_feature_jvm_arguments() {
+        let version_json = version_json(serde_json::json!({
+            "arguments": {
+                "game": [],
+                "jvm": [
+                    { "rules": [{ "action": "allow", "features": { "is_profiling": true } }], "value": ["-Dprofiler.enabled=true", "-Dprofiler.name=${launcher_name}"] }
+                ]
+            }
+        }));
+        let (mut options, version, platform) = setup(&version_json).await;
+        let arguments =
+            LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+                .await
+                .unwrap();
+        assert!(!arguments.0.iter().any(|arg| arg.starts_with("-Dprofiler.")));
+
+        options.launcher_name = "MyLauncher".to_string();
+        options
+            .features
+            .insert("is_profiling".to_string(), Value::Bool(true));
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert!(arguments.0.contains(&"-Dprofiler.enabled=true".to_string()));
+        assert!(arguments
+            .0
+            .contains(&"-Dprofiler.name=MyLauncher".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_empty_access_token() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        let arguments =
+            LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+                .await
+                .unwrap();
+        let index = arguments
+            .0
+            .iter()
+            .position(|a| a == "--accessToken")
+            .unwrap();
+        assert_eq!(arguments.0[index + 1], options.access_token);
+
+        options.access_token = " ".to_string();
+        let err = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("access_token"));
+    }
+
+    #[test]
+    fn test_quote_argument() {
+        assert_eq!(quote_argument("-Xmx2048M", &OsType::Linux), "-Xmx2048M");
+        assert_eq!(
+            quote_argument("/home/steve/My Instances", &OsType::Linux),
+            "'/home/steve/My Instances'"
+        );
+        assert_eq!(quote_argument("it's", &OsType::Osx), "'it'\\''s'");
+        assert_eq!(quote_argument("", &OsType::Linux), "''");
+        assert_eq!(
+            quote_argument("C:\\My \"Games\"", &OsType::Windows),
+            "\"C:\\My \"\"Games\"\"\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_launch_script_quoting() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.native_path = options.version_root.join("My Natives");
+        let arguments = LaunchArguments::from_launch_options(options.clone(), version, &platform)
+            .await
+            .unwrap();
+        let java = JavaExec {
+            binary: PathBuf::from("java"),
+        };
+        arguments
+            .to_async_command(java, options.clone(), &platform)
+            .await
+            .unwrap();
+        let script_name = match platform.os_type {
+            OsType::Windows => "launch.bat",
+            _ => "launch.sh",
+        };
+        let script =
+            std::fs::read_to_string(options.version_root.join(".cache").join(script_name)).unwrap();
+        let native_path = options.native_path.to_string_lossy();
+        assert!(script.contains(&quote_argument(&native_path, &platform.os_type)));
+        assert!(!script.contains(&format!(" {native_path}")));
+    }
+
+    #[tokio::test]
+    async fn test_argument_with_space() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.game_path = PathBuf::from("/home/steve/My Instances/1.19");
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        let index = arguments.0.iter().position(|a| a == "--gameDir").unwrap();
+        assert_eq!(arguments.0[index + 1], "/home/steve/My Instances/1.19");
+    }
+
+    #[tokio::test]
+    async fn test_template_values() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.extra_jvm_args = vec!["-Dpack.name=${pack_name}".to_string()];
+        options.extra_mc_args = vec!["--packVersion ${pack_version}".to_string()];
+        let options = options
+            .with_template_value("pack_name", "Skyblock")
+            .with_template_value("pack_version", "2.1")
+            .with_template_value("auth_player_name", "Alex");
+        let player_name = options.game_profile.name.clone();
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert!(arguments.0.contains(&"-Dpack.name=Skyblock".to_string()));
+        let index = arguments
+            .0
+            .iter()
+            .position(|a| a == "--packVersion")
+            .unwrap();
+        assert_eq!(arguments.0[index + 1], "2.1");
+        let index = arguments.0.iter().position(|a| a == "--username").unwrap();
+        assert_eq!(arguments.0[index + 1], player_name);
+    }
+
+    #[tokio::test]
+    async fn test_default_game_icon() {
+        let (options, version, platform) = setup(&version_json(Value::Null)).await;
+        let icon_path = options.minecraft_location.assets.join("minecraft.icns");
+        let options = options.with_default_game_icon(b"branded icon".to_vec());
+        LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&icon_path).unwrap(), b"branded icon");
+
+        let options = LaunchOptions {
+            default_game_icon: None,
+            ..options
+        };
+        LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&icon_path).unwrap(), DEFAULT_GAME_ICON);
+    }
+
+    #[tokio::test]
+    async fn test_inherited_assets_index_name() {
+        let root = env::temp_dir().join(format!("cvl-test-{}", uuid::Uuid::new_v4()));
+        let minecraft = MinecraftLocation::new(&root);
+        for (id, json) in [
+            (
+                "parent",
+                vanilla_json("parent", "1.19", Value::Null).to_string(),
+            ),
+            (
+                "test",
+                r#"{
+                    "id": "test",
+                    "inheritsFrom": "parent",
+                    "assets": "legacy",
+                    "arguments": { "game": ["--assetIndex", "${assets_index_name}"] }
+                }"#
+                .to_string(),
+            ),
+        ] {
+            fs::create_dir_all(minecraft.get_version_root(id))
+                .await
+                .unwrap();
+            fs::write(minecraft.get_version_json(id), json)
+                .await
+                .unwrap();
+        }
+        fs::create_dir_all(&minecraft.assets).await.unwrap();
+        let platform = PlatformInfo::new().await;
+        let options = LaunchOptions::new("test", &minecraft).await.unwrap();
+        let version = options.version.parse(&minecraft, &platform).await.unwrap();
+
+        // the install downloads the index of `asset_index`
+        let installed = version.asset_index.clone().unwrap().id;
+        assert_eq!(installed, "1.19");
+        assert_eq!(version.assets, installed);
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        let index = arguments
+            .0
+            .iter()
+            .position(|a| a == "--assetIndex")
+            .unwrap();
+        assert_eq!(arguments.0[index + 1], installed);
+        fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_isolated_assets() {
+        let root = env::temp_dir().join(format!("cvl-test-{}", uuid::Uuid::new_v4()));
+        let minecraft = MinecraftLocation::new(&root).with_isolated_assets("test");
+        let instance_assets = minecraft.get_version_root("test").join("assets");
+        assert_eq!(minecraft.assets, instance_assets);
+        fs::create_dir_all(minecraft.get_version_root("test"))
+            .await
+            .unwrap();
+        fs::write(
+            minecraft.get_version_json("test"),
+            version_json(serde_json::json!({
+                "arguments": { "game": ["--assetsDir", "${assets_root}", "--assetIndex", "${assets_index_name}"] }
+            })),
+        )
+        .await
+        .unwrap();
+        let hash = "bdf48ef6b5d0d23bbb02e17d04865216179f510a";
+        fs::create_dir_all(minecraft.get_assets_index("1.19").parent().unwrap())
+            .await
+            .unwrap();
+        fs::write(
+            minecraft.get_assets_index("1.19"),
+            format!(r#"{{ "objects": {{ "icons/icon_16x16.png": {{ "hash": "{hash}", "size": 1 }} }} }}"#),
+        )
+        .await
+        .unwrap();
+        let platform = PlatformInfo::new().await;
+        let options = LaunchOptions::new("test", &minecraft).await.unwrap();
+        let version = options.version.parse(&minecraft, &platform).await.unwrap();
+
+        // the install downloads the objects into the instance
+        let downloads = crate::install::check_game_integrity(
+            &version,
+            &minecraft,
+            &crate::install::DownloadSource::Official,
+            &crate::utils::download::HttpDownloader,
+        )
+        .await
+        .unwrap();
+        let object = downloads
+            .iter()
+            .find(|download| download.sha1.as_deref() == Some(hash))
+            .unwrap();
+        assert!(object.file.starts_with(&instance_assets));
+
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        let index = arguments.0.iter().position(|a| a == "--assetsDir").unwrap();
+        assert_eq!(arguments.0[index + 1], instance_assets.to_string_lossy());
+        assert!(!root.join("assets").exists());
+        fs::remove_dir_all(root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_classpath_argument() {
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        let extra = vec![
+            "/opt/My Mods/core.jar".to_string(),
+            "/opt/libs/extra.jar".to_string(),
+        ];
+        options.extra_class_paths = Some(extra.clone());
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        let index = arguments.0.iter().position(|a| a == "-cp").unwrap();
+        let classpath = &arguments.0[index + 1];
+        assert!(classpath.ends_with(&extra.join(DELIMITER)), "{classpath}");
+        assert!(!arguments.0.contains(&"/opt/libs/extra.jar".to_string()));
+        assert!(!arguments.0.iter().any(|arg| arg.contains("${classpath}")));
+    }
+
+    #[tokio::test]
+    async fn test_resolution_arguments() {
+        let count = |arguments: &LaunchArguments, option: &str| {
+            arguments
+                .0
+                .iter()
+                .filter(|arg| is_option(arg, option))
+                .count()
+        };
+        let (mut options, version, platform) = setup(&version_json(Value::Null)).await;
+        options.width = 1280;
+        let arguments =
+            LaunchArguments::from_launch_options(options.clone(), version.clone(), &platform)
+                .await
+                .unwrap();
+        assert_eq!(count(&arguments, "--width"), 1);
+        assert_eq!(count(&arguments, "--height"), 1);
+        let index = arguments.0.iter().position(|a| a == "--width").unwrap();
+        assert_eq!(arguments.0[index + 1], "1280");
+
+        options.extra_mc_args = vec!["--width 1920 --Height=1080".to_string()];
+        let arguments = LaunchArguments::from_launch_options(options, version, &platform)
+            .await
+            .unwrap();
+        assert_eq!(count(&arguments, "--width"), 1);
+        assert_eq!(count(&arguments, "--height"), 1);
+        let index = arguments.0.iter().position(|a| a == "--width").unwrap();
+        assert_eq!(arguments.0[index + 1], "1920");
+        assert!(arguments.0.contains(&"--Height=1080".to_string()));
+    }
+}
@@ -68,3 +68,5 @@
 
 pub mod argument;
 pub mod options;
+pub mod prepare;
+pub mod process;
@@ -16,13 +16,86 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Result};
 use regex::Regex;
 use serde_json::Value;
+use tokio::{process::Command, sync::OnceCell};
 
 use crate::core::{folder::MinecraftLocation, version::Version};
+use crate::install::DownloadSource;
+
+/// The window size of the game when the screen size is unknown
+pub const DEFAULT_RESOLUTION: (u32, u32) = (854, 480);
+
+/// The size of the primary screen, detected once
+static SCREEN_SIZE: OnceCell<Option<(u32, u32)>> = OnceCell::const_new();
+
+/// The default window size for this computer: a 16:9 window half as wide as the primary screen,
+/// or [`DEFAULT_RESOLUTION`] if the screen size cannot be detected. The screen size is read once
+/// by running a system tool.
+pub async fn recommended_resolution() -> (u32, u32) {
+    resolution_for_screen(*SCREEN_SIZE.get_or_init(screen_size).await)
+}
+
+fn resolution_for_screen(screen: Option<(u32, u32)>) -> (u32, u32) {
+    let (default_width, default_height) = DEFAULT_RESOLUTION;
+    match screen {
+        Some((width, height)) => {
+            let window_width = (width / 2).max(default_width);
+            let window_height = (window_width * 9 / 16).min(height).max(default_height);
+            (window_width, window_height)
+        }
+        None => DEFAULT_RESOLUTION,
+    }
+}
+
+async fn screen_size() -> Option<(u32, u32)> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Windows.Forms; $size = [System.Windows.Forms.Screen]::PrimaryScreen.Bounds.Size; \"$($size.Width)x$($size.Height)\"",
+        ]);
+        #[cfg(windows)]
+        command.creation_flags(0x08000000);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("system_profiler");
+        command.arg("SPDisplaysDataType");
+        command
+    } else {
+        let mut command = Command::new("xrandr");
+        command.arg("--current");
+        command
+    };
+    let output = command.output().await.ok()?;
+    parse_screen_size(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Read the primary screen size from the output of `xrandr`, `system_profiler` or the
+/// powershell `WIDTHxHEIGHT`.
+fn parse_screen_size(output: &str) -> Option<(u32, u32)> {
+    let size = Regex::new(r"(\d{3,5}) ?x ?(\d{3,5})").unwrap();
+    // the primary monitor, and the scaled size of retina displays, come first
+    let preferences: [fn(&str) -> bool; 3] = [
+        |line| line.contains(" connected primary ") || line.contains("UI Looks like:"),
+        |line| line.contains(" connected ") || line.contains("Resolution:"),
+        |line| !line.contains("Screen "),
+    ];
+    preferences.iter().find_map(|preferred| {
+        output
+            .lines()
+            .filter(|line| preferred(line))
+            .find_map(|line| size.captures(line))
+            .and_then(|captures| Some((captures[1].parse().ok()?, captures[2].parse().ok()?)))
+    })
+}
 
 #[derive(Debug, Clone)]
 pub struct GameProfile {
@@ -54,6 +127,32 @@ pub struct YggdrasilAgent {
     pub prefetched: Option<String>,
 }
 
+/// Override the Mojang API endpoints with `-Dminecraft.api.*.host`, for the third-party
+/// authentication servers that don't use authlib-injector
+#[derive(Debug, Clone, Default)]
+pub struct ApiOverrides {
+    /// `-Dminecraft.api.auth.host`
+    pub auth_host: Option<String>,
+
+    /// `-Dminecraft.api.account.host`
+    pub account_host: Option<String>,
+
+    /// `-Dminecraft.api.session.host`
+    pub session_host: Option<String>,
+
+    /// `-Dminecraft.api.services.host`
+    pub services_host: Option<String>,
+}
+
+/// Where the extra classpath is placed, relative to the libraries and the client jar
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ClasspathPosition {
+    /// Before the libraries, so the extra classes take precedence
+    Prepend,
+    #[default]
+    Append,
+}
+
 /// Game process priority, invalid on windows
 #[derive(Debug, Clone)]
 pub enum ProcessPriority {
@@ -74,6 +173,27 @@ pub enum GC {
     Z,
 }
 
+impl GC {
+    /// Check that the java of the major version supports the gc, otherwise the game would fail
+    /// to start.
+    ///
+    /// ZGC is production ready since Java 15, and `-XX:+UseParallelOldGC` is removed in it.
+    pub fn check_supported(&self, java_major_version: u32) -> Result<()> {
+        let supported = match self {
+            GC::Z => java_major_version >= 15,
+            GC::ParallelOld => java_major_version < 15,
+            GC::Serial | GC::Parallel | GC::G1 => true,
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{self:?} gc is not supported by Java {java_major_version}"
+            ))
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Launch options for game
 pub struct LaunchOptions {
@@ -82,6 +202,7 @@ pub struct LaunchOptions {
     /// For game display name & uuid
     pub(crate) game_profile: GameProfile,
 
+    /// Must not be empty. Offline accounts can use any placeholder, the default is a random uuid.
     pub(crate) access_token: String,
     pub(crate) user_type: UserType,
     pub(crate) properties: String,
@@ -105,6 +226,11 @@ pub struct LaunchOptions {
     /// Currently, this only supported on MacOS
     pub(crate) game_icon: Option<PathBuf>,
 
+    /// The icon used when `game_icon` is absent, the bundled Minecraft icon if this is also absent
+    ///
+    /// The bytes of an `.icns` file, for launchers with their own branding.
+    pub(crate) default_game_icon: Option<Vec<u8>>,
+
     /// The launched game name
     ///
     /// Currently, this only supported on MacOS.
@@ -139,12 +265,19 @@ pub struct LaunchOptions {
 
     /// User custom additional java virtual machine command line arguments.
     ///
-    /// If this is empty, the `DEFAULT_EXTRA_JVM_ARGS` will be used.
+    /// An element may contain several flags separated by whitespace, like `"-Xss1m -XX:+UseG1GC"`.
     pub(crate) extra_jvm_args: Vec<String>,
 
     /// User custom additional minecraft command line arguments.
+    ///
+    /// An element may contain several arguments separated by whitespace.
     pub(crate) extra_mc_args: Vec<String>,
 
+    /// Custom values substituted for `${key}` in the arguments, including `extra_jvm_args` and
+    /// `extra_mc_args`. They cannot replace the values provided by the launcher, like
+    /// `auth_access_token`.
+    pub(crate) template_values: HashMap<String, String>,
+
     pub(crate) is_demo: bool,
 
     // Todo: yggdrasilAgent
@@ -154,9 +287,19 @@ pub struct LaunchOptions {
     /// Add `-Dfml.ignorePatchDiscrepancies=true` to jvm argument
     pub(crate) ignore_patch_discrepancies: bool,
 
+    /// Add the jndi and log4j hardening flags like `-Dlog4j2.formatMsgNoLookups=true`, default
+    /// is true.
+    ///
+    /// ### WARN: Only disable this for debugging, the game may be vulnerable to remote code
+    /// execution like log4shell (CVE-2021-44228) without it, e.g. by a chat message on a server.
+    pub(crate) security_hardening: bool,
+
     /// Add extra classpath
     pub(crate) extra_class_paths: Option<Vec<String>>,
 
+    /// Where to put the extra classpath, default is after the client jar
+    pub(crate) extra_class_paths_position: ClasspathPosition,
+
     /// The path of parent directory of `<version_id>.jar` and `<version_id>.json`,
     ///
     /// default is `versions/{version_id}`
@@ -167,15 +310,28 @@ pub struct LaunchOptions {
     /// The version of launched Minecraft. Can be either resolved version or version string
     pub(crate) version: Version,
 
-    /// Enable features. Not really in used...
+    /// Enabled features, like `{ "has_custom_resolution": true }`. The jvm arguments of the
+    /// version json gated by `features` rules are included according to them.
     pub(crate) features: HashMap<String, Value>,
 
     /// Game process priority, invalid on windows
     pub(crate) process_priority: ProcessPriority,
 
+    /// Check the libraries, client jar and assets before launch
+    pub(crate) check_game_integrity: bool,
+
+    /// Download the missing or corrupt files found by the integrity check, instead of failing
+    pub(crate) repair_on_launch: bool,
+
+    /// Where the integrity check repairs the files from
+    pub(crate) download_source: DownloadSource,
+
     /// Support yushi's yggdrasil agent <https://github.com/to2mbn/authlib-injector/wiki>
     pub(crate) yggdrasil_agent: Option<YggdrasilAgent>,
 
+    /// Override the Mojang API endpoints
+    pub(crate) api_overrides: Option<ApiOverrides>,
+
     pub(crate) version_id: String,
 
     pub(crate) gc: GC,
@@ -186,11 +342,12 @@ pub struct LaunchOptions {
 }
 
 impl LaunchOptions {
-    /// spawn an instance with default launch options
+    /// spawn an instance with default launch options, in a window of [`recommended_resolution`]
     pub async fn new(version_id: &str, minecraft: &MinecraftLocation) -> Result<Self> {
         let version_json_path = minecraft.get_version_json(version_id);
         let raw_version_json = tokio::fs::read_to_string(version_json_path).await?;
         let version_json: Version = serde_json::from_str((&raw_version_json).as_ref())?;
+        let (width, height) = recommended_resolution().await;
 
         Ok(Self {
             game_profile: GameProfile {
@@ -205,6 +362,7 @@ impl LaunchOptions {
             version_name: None,
             version_type: None,
             game_icon: None,
+            default_game_icon: None,
             game_name: "Minecraft".to_string(),
             game_path: minecraft.get_version_root(version_id),
             version_root: minecraft.get_version_root(version_id),
@@ -212,19 +370,26 @@ impl LaunchOptions {
             min_memory: 128,
             max_memory: 2048,
             server: None,
-            width: 854,
-            height: 480,
+            width,
+            height,
             fullscreen: false,
             extra_jvm_args: vec![],
             extra_mc_args: vec![],
+            template_values: HashMap::new(),
             is_demo: false,
             ignore_invalid_minecraft_certificates: false,
             ignore_patch_discrepancies: false,
+            security_hardening: true,
             extra_class_paths: None,
+            extra_class_paths_position: ClasspathPosition::Append,
             version: version_json,
             features: HashMap::new(),
             yggdrasil_agent: None,
+            api_overrides: None,
             process_priority: ProcessPriority::Normal,
+            check_game_integrity: false,
+            repair_on_launch: true,
+            download_source: DownloadSource::default(),
             version_id: version_id.to_string(),
             gc: GC::G1,
             minecraft_location: minecraft.clone(),
@@ -232,32 +397,74 @@ impl LaunchOptions {
         })
     }
 
+    /// Overwrite the version type passed to `--versionType`, which the game shows on the title
+    /// screen and in the F3 debug screen, e.g. `Modded`.
+    pub fn with_version_type(mut self, version_type: &str) -> Self {
+        self.version_type = Some(version_type.to_string());
+        self
+    }
+
+    /// Set the window size of the game instead of the [`recommended_resolution`]
+    pub fn with_resolution(mut self, (width, height): (u32, u32)) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Read the assets from another folder than the one of the minecraft location, passed to
+    /// `--assetsDir`. See [`MinecraftLocation::with_isolated_assets`].
+    pub fn with_assets_root<P: AsRef<Path>>(mut self, assets_root: P) -> Self {
+        self.minecraft_location.assets = assets_root.as_ref().to_path_buf();
+        self
+    }
+
+    /// Add the jndi and log4j hardening flags, on by default.
+    ///
+    /// ### WARN: Only do this for debugging, see `security_hardening`.
+    pub fn with_security_hardening(mut self, enabled: bool) -> Self {
+        self.security_hardening = enabled;
+        self
+    }
+
+    /// Download the files the integrity check finds missing or corrupt before launch, instead
+    /// of failing. It's on by default.
+    pub fn with_repair_on_launch(mut self, enabled: bool) -> Self {
+        self.repair_on_launch = enabled;
+        self
+    }
+
+    /// Repair the files found by the integrity check from `source`, the official one by default.
+    pub fn with_download_source(mut self, source: DownloadSource) -> Self {
+        self.download_source = source;
+        self
+    }
+
+    /// Set where the extra classpath goes, after the client jar by default.
+    pub fn with_extra_class_paths_position(mut self, position: ClasspathPosition) -> Self {
+        self.extra_class_paths_position = position;
+        self
+    }
+
+    /// Use the `.icns` bytes as the game icon when no `game_icon` is set.
+    pub fn with_default_game_icon(mut self, icon: Vec<u8>) -> Self {
+        self.default_game_icon = Some(icon);
+        self
+    }
+
+    /// Substitute `value` for `${key}` in the arguments, e.g. in the `extra_mc_args`.
+    pub fn with_template_value(mut self, key: &str, value: &str) -> Self {
+        self.template_values
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
     pub async fn new_forge_options(
         version_id: &str,
         minecraft: &MinecraftLocation,
     ) -> Result<Self> {
         let mut default = LaunchOptions::new(version_id, &minecraft).await?;
 
-        let version = minecraft.get_version_json(version_id);
-        let version = tokio::fs::read_to_string(version).await?;
-        let version = serde_json::from_str::<Version>(&version)?;
-
-        if let Some(arguments) = version.minecraft_arguments {
-            let tweak_class_regex = Regex::new(r"--tweakClass\s+.+").unwrap();
-            let forge_arguments = tweak_class_regex
-                .captures(&arguments)
-                .ok_or(anyhow!("tweakClass not found"))?
-                .get(0)
-                .ok_or(anyhow!("tweakClass not found"))?
-                .as_str();
-            default.extra_mc_args.extend(
-                forge_arguments
-                    .split(" ")
-                    .map(|x| x.to_string())
-                    .collect::<Vec<String>>(),
-            );
-        }
-
+        // the `--tweakClass` of forge is in the game arguments of the parsed version
         default.extra_jvm_args.extend(vec![
             "\"-Dfml.ignoreInvalidMinecraftCertificates=true\"".to_string(),
             "\"-Dfml.ignorePatchDiscrepancies=true\"".to_string(),
@@ -266,3 +473,52 @@ impl LaunchOptions {
         Ok(default)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolution_for_screen() {
+        assert_eq!(resolution_for_screen(None), DEFAULT_RESOLUTION);
+        assert_eq!(resolution_for_screen(Some((2560, 1440))), (1280, 720));
+        assert_eq!(resolution_for_screen(Some((1280, 1024))), (854, 480));
+    }
+
+    #[tokio::test]
+    async fn test_default_resolution() {
+        let minecraft = MinecraftLocation::new(&crate::utils::download::test::temp_dir());
+        let version = crate::core::version::test::vanilla_json("1.20.1", "5", Value::Null);
+        crate::core::version::test::write_version(&minecraft, "1.20.1", &version.to_string());
+        let options = LaunchOptions::new("1.20.1", &minecraft).await.unwrap();
+        assert_eq!(
+            (options.width, options.height),
+            recommended_resolution().await
+        );
+        let options = options.with_resolution((1280, 720));
+        assert_eq!((options.width, options.height), (1280, 720));
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_screen_size() {
+        let xrandr = "Screen 0: minimum 8 x 8, current 4480 x 1440, maximum 32767 x 32767\n\
+            HDMI-1 connected 1920x1080+2560+0 (normal left inverted right x axis y axis) 527mm x 296mm\n\
+            DP-1 connected primary 2560x1440+0+0 (normal left inverted right x axis y axis) 597mm x 336mm\n\
+            \x20  2560x1440     59.95*+\n";
+        assert_eq!(parse_screen_size(xrandr), Some((2560, 1440)));
+        let system_profiler = "        Resolution: 3024 x 1964 Retina\n\
+            \x20         UI Looks like: 1512 x 982 @ 120.00Hz\n";
+        assert_eq!(parse_screen_size(system_profiler), Some((1512, 982)));
+        assert_eq!(parse_screen_size("1920x1080\r\n"), Some((1920, 1080)));
+        assert_eq!(parse_screen_size("Can't open display"), None);
+    }
+
+    #[test]
+    fn test_gc_supported() {
+        assert!(GC::Z.check_supported(8).is_err());
+        assert!(GC::Z.check_supported(17).is_ok());
+        assert!(GC::ParallelOld.check_supported(17).is_err());
+        assert!(GC::G1.check_supported(8).is_ok());
+    }
+}
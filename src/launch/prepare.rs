@@ -0,0 +1,263 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! One call from a version id to a launch command, for simple apps
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::{
+    core::{
+        folder::MinecraftLocation,
+        task::{CancellationToken, TaskEventListeners},
+        version::{ResolvedVersion, Version},
+        JavaExec, PlatformInfo,
+    },
+    install::{
+        check_game_integrity, generate_download_info, install_virtual_assets_async, missing_bytes,
+        DownloadSource, InstallMode,
+    },
+    utils::{
+        disk::{check_disk_space, download_size},
+        download::{download_files_with, Downloader, DEFAULT_CONCURRENCY},
+    },
+};
+
+use super::{
+    argument::LaunchArguments,
+    options::LaunchOptions,
+    process::{spawn_game, GameProcess},
+};
+
+/// A version that is installed and checked, with the default launch options for it
+#[derive(Debug, Clone)]
+pub struct PreparedLaunch {
+    pub options: LaunchOptions,
+    pub java: JavaExec,
+    pub version: ResolvedVersion,
+    pub platform: PlatformInfo,
+}
+
+impl PreparedLaunch {
+    /// Build the command to start the game with the current options
+    pub async fn command(&self) -> Result<std::process::Command> {
+        LaunchArguments::from_launch_options(
+            self.options.clone(),
+            self.version.clone(),
+            &self.platform,
+        )
+        .await?
+        .to_async_command(self.java.clone(), self.options.clone(), &self.platform)
+        .await
+    }
+
+    /// Start the game and send each line it prints to `on_line`, see [`spawn_game`]
+    pub async fn launch<F>(&self, on_line: F) -> Result<GameProcess>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        self.launch_cancellable(on_line, &CancellationToken::new())
+            .await
+    }
+
+    /// The same as [`launch`](Self::launch), but fails with a [`Cancelled`] without starting
+    /// the game once `cancel` is cancelled.
+    ///
+    /// [`Cancelled`]: crate::core::task::Cancelled
+    pub async fn launch_cancellable<F>(
+        &self,
+        on_line: F,
+        cancel: &CancellationToken,
+    ) -> Result<GameProcess>
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        let command = cancel.run(self.command()).await?;
+        // the last chance before the process exists
+        cancel.check()?;
+        spawn_game(command, on_line)
+    }
+}
+
+/// Install the vanilla version according to `mode`, repair its missing or corrupt files, and
+/// create the default launch options for it.
+///
+/// Every file is downloaded from `source`, with the version manifest, version json and asset index
+/// too when it is a mirror. The version is installed if its json is not in the versions folder yet.
+/// The install and the repair fail with an [`InsufficientDiskSpace`] before downloading if the
+/// files don't fit on the disk.
+///
+/// [`InsufficientDiskSpace`]: crate::utils::disk::InsufficientDiskSpace
+pub async fn install_and_prepare(
+    version_id: &str,
+    minecraft: &MinecraftLocation,
+    java: JavaExec,
+    mode: InstallMode,
+    source: &DownloadSource,
+    downloader: Arc<dyn Downloader>,
+    listeners: &TaskEventListeners,
+) -> Result<PreparedLaunch> {
+    let platform = PlatformInfo::new().await;
+    mode.clean(version_id, minecraft).await?;
+    if mode != InstallMode::Repair || !minecraft.get_version_json(version_id).is_file() {
+        let downloads =
+            generate_download_info(version_id, minecraft.clone(), &platform, source).await?;
+        let version = Version::from_versions_folder(minecraft.clone(), version_id)
+            .await?
+            .parse(minecraft, &platform)
+            .await?;
+        check_disk_space(&minecraft.root, missing_bytes(&version, minecraft).await?).await?;
+        download_files_with(
+            mode.filter_downloads(downloads).await?,
+            listeners,
+            downloader.clone(),
+            DEFAULT_CONCURRENCY,
+            mode != InstallMode::Fresh,
+        )
+        .await?;
+    }
+    let options = LaunchOptions::new(version_id, minecraft).await?;
+    let version = options.version.parse_cached(minecraft, &platform).await?;
+    let repairs = check_game_integrity(&version, minecraft, source, downloader.as_ref()).await?;
+    if !repairs.is_empty() {
+        check_disk_space(&minecraft.root, download_size(&repairs)).await?;
+        download_files_with(repairs, listeners, downloader, DEFAULT_CONCURRENCY, true).await?;
+    }
+    install_virtual_assets_async(minecraft, &version.assets).await?;
+    Ok(PreparedLaunch {
+        options,
+        java,
+        version,
+        platform,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::core::version::test::{vanilla_json, write_version};
+    use crate::utils::download::{
+        test::{serve, temp_dir},
+        HttpDownloader,
+    };
+
+    #[tokio::test]
+    async fn test_install_and_prepare() {
+        let client = b"client jar".to_vec();
+        let sha1 = format!("{:x}", Sha1::digest(&client));
+        let url = serve("200 OK", client.clone()).await;
+        let index = br#"{ "objects": {} }"#.to_vec();
+        let index_size = index.len();
+        let index_url = serve("200 OK", index).await;
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        let version_json = vanilla_json(
+            "test",
+            "1.19",
+            serde_json::json!({
+                "assetIndex": {
+                    "id": "1.19",
+                    "size": index_size,
+                    "url": format!("{index_url}/1.19.json"),
+                    "totalSize": 1
+                },
+                "downloads": {
+                    "client": { "sha1": sha1, "size": 10, "url": format!("{url}/client.jar") }
+                }
+            }),
+        );
+        write_version(&minecraft, "test", &version_json.to_string());
+
+        let java = JavaExec::new("/usr").await;
+        let prepared = install_and_prepare(
+            "test",
+            &minecraft,
+            java.clone(),
+            InstallMode::Repair,
+            &DownloadSource::Official,
+            Arc::new(HttpDownloader),
+            &TaskEventListeners::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(prepared.options.version.id, "test");
+        assert_eq!(prepared.version.id, "test");
+        assert_eq!(prepared.java, java);
+        assert_eq!(
+            std::fs::read(minecraft.get_version_jar("test", None)).unwrap(),
+            client
+        );
+        // the repair fetches the missing asset index to check the assets
+        assert!(minecraft.get_assets_index("1.19").is_file());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_cancelled() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::core::task::Cancelled;
+
+        let minecraft = MinecraftLocation::new(&temp_dir());
+        std::fs::create_dir_all(&minecraft.assets).unwrap();
+        write_version(
+            &minecraft,
+            "test",
+            &vanilla_json("test", "1.19", serde_json::Value::Null).to_string(),
+        );
+        // a fake java that leaves a marker once it runs
+        let marker = minecraft.root.join("started");
+        let binary = minecraft.root.join("java");
+        std::fs::write(
+            &binary,
+            format!("#!/bin/sh\ntouch {}\n", marker.to_string_lossy()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let platform = PlatformInfo::new().await;
+        let options = LaunchOptions::new("test", &minecraft).await.unwrap();
+        let version = options.version.parse(&minecraft, &platform).await.unwrap();
+        let prepared = PreparedLaunch {
+            options,
+            java: JavaExec { binary },
+            version,
+            platform,
+        };
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = prepared
+            .launch_cancellable(|_| (), &cancel)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+        assert!(!marker.exists());
+
+        let mut process = prepared
+            .launch_cancellable(|_| (), &CancellationToken::new())
+            .await
+            .unwrap();
+        process.wait().await.unwrap();
+        assert!(marker.exists());
+        std::fs::remove_dir_all(&minecraft.root).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Run the game and read its output
+
+use std::{
+    process::{Command, ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Child,
+    task::JoinHandle,
+};
+
+/// How long [`GameProcess::wait`] keeps reading the output after the game exited. A process the
+/// game started may keep the pipes open after it.
+const OUTPUT_GRACE: Duration = Duration::from_secs(2);
+
+/// A running game, with the tasks reading its stdout and stderr
+#[derive(Debug)]
+pub struct GameProcess {
+    id: u32,
+    child: Child,
+    readers: Vec<JoinHandle<()>>,
+}
+
+/// Spawn the command and send every line it prints, on stdout or stderr, to `on_line`.
+///
+/// It must be called in a tokio runtime.
+pub fn spawn_game<F>(command: Command, on_line: F) -> Result<GameProcess>
+where
+    F: Fn(String) + Send + Sync + 'static,
+{
+    let mut child = tokio::process::Command::from(command)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let id = child.id().unwrap_or_default();
+    let on_line = Arc::new(on_line);
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(read_lines(stdout, on_line.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(read_lines(stderr, on_line));
+    }
+    Ok(GameProcess { id, child, readers })
+}
+
+/// Read the pipe until EOF, which is only reached once the game exited and its output is flushed.
+fn read_lines<R, F>(pipe: R, on_line: Arc<F>) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    F: Fn(String) + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while let Ok(length) = reader.read_until(b'\n', &mut line).await {
+            if length == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            on_line(text.trim_end_matches(['\r', '\n']).to_string());
+            line.clear();
+        }
+    })
+}
+
+impl GameProcess {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Start killing the game, [`wait`](Self::wait) returns once it's gone
+    pub fn kill(&mut self) -> Result<()> {
+        Ok(self.child.start_kill()?)
+    }
+
+    /// Wait for the game to exit, then for all of its output to be read, so the last lines
+    /// (often the crash cause) are never lost.
+    ///
+    /// It can be cancelled, like in a `tokio::select!` with a stop button that then calls
+    /// [`kill`](Self::kill).
+    pub async fn wait(&mut self) -> Result<ExitStatus> {
+        let status = self.child.wait().await?;
+        let deadline = tokio::time::Instant::now() + OUTPUT_GRACE;
+        for mut reader in self.readers.drain(..) {
+            if tokio::time::timeout_at(deadline, &mut reader)
+                .await
+                .is_err()
+            {
+                reader.abort();
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_reads_last_line() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "for i in $(seq 1 1000); do echo line $i; done; echo crashed >&2; echo last line",
+        ]);
+        let output = lines.clone();
+        let mut game = spawn_game(command, move |line| output.lock().unwrap().push(line)).unwrap();
+        assert!(game.wait().await.unwrap().success());
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 1002);
+        assert!(lines.contains(&"crashed".to_string()));
+        assert_eq!(lines.iter().filter(|line| *line == "last line").count(), 1);
+        assert_eq!(
+            lines
+                .iter()
+                .rfind(|line| line.starts_with("line "))
+                .unwrap(),
+            "line 1000"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_wait_with_open_pipe() {
+        // the background sleep keeps stdout open after the shell exited
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 10 & echo started"]);
+        let mut game = spawn_game(command, |_| ()).unwrap();
+        let status = tokio::time::timeout(Duration::from_secs(5), game.wait())
+            .await
+            .expect("wait hangs on the pipe of a child process");
+        assert!(status.unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_kill_while_waiting() {
+        let mut command = Command::new("sleep");
+        command.arg("30");
+        let mut game = spawn_game(command, |_| ()).unwrap();
+        tokio::select! {
+            _ = game.wait() => panic!("the game exited by itself"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => game.kill().unwrap(),
+        }
+        assert!(!game.wait().await.unwrap().success());
+    }
+}
@@ -21,3 +21,4 @@ pub mod install;
 pub mod launch;
 pub mod utils;
 pub mod game_data;
+pub mod java;
@@ -0,0 +1,159 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! On-disk cache of the metadata endpoints, like the version manifest and the loader lists
+//!
+//! The cache is disabled by default, use `set_cache_dir` to enable it. The cached responses are
+//! revalidated by `If-None-Match` / `If-Modified-Since`, and reused on `304 Not Modified`.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use reqwest::{
+    header::{ACCEPT_ENCODING, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::core::http_client;
+use crate::utils::gzip::decompress_if_gzip;
+
+static CACHE_DIR: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct CacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Set the folder to cache the metadata responses in, None to disable the cache
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.write().unwrap() = dir;
+}
+
+/// Fetch and deserialize a json metadata endpoint, through the cache if it's enabled
+pub async fn fetch_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let cache_dir = CACHE_DIR.read().unwrap().clone();
+    let body = fetch_with_cache(url, cache_dir.as_deref()).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+pub(crate) async fn fetch_with_cache(url: &str, cache_dir: Option<&Path>) -> Result<Vec<u8>> {
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => cache_dir,
+        None => return crate::utils::gzip::fetch(url).await,
+    };
+    let key = format!("{:x}", Sha1::digest(url.as_bytes()));
+    let body_path = cache_dir.join(&key);
+    let entry_path = cache_dir.join(format!("{key}.json"));
+    let entry: Option<CacheEntry> = match (
+        tokio::fs::metadata(&body_path)
+            .await
+            .map(|meta| meta.is_file()),
+        tokio::fs::read(&entry_path).await,
+    ) {
+        (Ok(true), Ok(entry)) => serde_json::from_slice(&entry).ok(),
+        _ => None,
+    };
+
+    let mut request = http_client().get(url).header(ACCEPT_ENCODING, "gzip");
+    if let Some(entry) = &entry {
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await?;
+    if entry.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(tokio::fs::read(body_path).await?);
+    }
+    let response = response.error_for_status()?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let entry = CacheEntry {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let body = decompress_if_gzip(response.bytes().await?.to_vec())?;
+    if entry.etag.is_some() || entry.last_modified.is_some() {
+        tokio::fs::create_dir_all(cache_dir).await?;
+        tokio::fs::write(&body_path, &body).await?;
+        tokio::fs::write(&entry_path, serde_json::to_vec(&entry)?).await?;
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_not_modified() {
+        // return the body with an etag, and 304 if the request has the etag. A request that
+        // doesn't accept gzip is refused like a mirror that only serves it compressed
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let response = if !request.contains("accept-encoding: gzip") {
+                    "HTTP/1.1 406 Not Acceptable\r\nConnection: close\r\n\r\n".to_string()
+                } else if request.contains("if-none-match: \"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 9\r\nConnection: close\r\n\r\n[\"1.0.0\"]".to_string()
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let url = format!("http://{address}/versions");
+        let root = crate::utils::download::test::temp_dir();
+
+        let body = fetch_with_cache(&url, Some(&root)).await.unwrap();
+        assert_eq!(body, b"[\"1.0.0\"]");
+        let body = fetch_with_cache(&url, Some(&root)).await.unwrap();
+        assert_eq!(body, b"[\"1.0.0\"]");
+
+        // the 304 body is empty, so the cached one must have been used
+        std::fs::write(
+            root.join(format!("{:x}", Sha1::digest(url.as_bytes()))),
+            "[\"cached\"]",
+        )
+        .unwrap();
+        let body = fetch_with_cache(&url, Some(&root)).await.unwrap();
+        assert_eq!(body, b"[\"cached\"]");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
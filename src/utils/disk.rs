@@ -0,0 +1,173 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Free space of the disks, to check it before downloading
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use tokio::process::Command;
+
+use crate::core::Download;
+
+/// The files to download don't fit in the free space of the disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsufficientDiskSpace {
+    /// The bytes to download
+    pub required: u64,
+    /// The free bytes of the disk
+    pub available: u64,
+}
+
+impl std::fmt::Display for InsufficientDiskSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Not enough disk space, {} MB is required but only {} MB is available",
+            self.required / 1024 / 1024,
+            self.available / 1024 / 1024
+        )
+    }
+}
+
+impl std::error::Error for InsufficientDiskSpace {}
+
+/// Get the free bytes of the disk the path is on. The path doesn't need to exist yet.
+pub async fn available_space<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path
+        .as_ref()
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or(anyhow!("No existing folder in {}", path.as_ref().display()))?;
+    #[cfg(windows)]
+    {
+        let mut command = Command::new("powershell");
+        command.creation_flags(0x08000000);
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "(Get-Item -LiteralPath '{}').PSDrive.Free",
+                path.to_string_lossy().replace('\'', "''")
+            ),
+        ]);
+        let output = command.output().await?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Cannot read the free space of {}", path.display()))
+    }
+    #[cfg(not(windows))]
+    {
+        let output = Command::new("df").arg("-Pk").arg(path).output().await?;
+        parse_df_output(&String::from_utf8_lossy(&output.stdout))
+            .ok_or(anyhow!("Cannot read the free space of {}", path.display()))
+    }
+}
+
+/// Read the available bytes from the output of `df -Pk`
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_df_output(output: &str) -> Option<u64> {
+    let kilobytes: u64 = output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Check that `required` bytes fit in the `available` ones
+pub fn check_space(required: u64, available: u64) -> Result<(), InsufficientDiskSpace> {
+    if required > available {
+        return Err(InsufficientDiskSpace {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Check that `required` bytes fit on the disk the path is on.
+///
+/// The error is an [`InsufficientDiskSpace`] when they don't. The check is skipped if the free
+/// space can't be read, like when `df` or PowerShell is missing.
+pub async fn check_disk_space<P: AsRef<Path>>(path: P, required: u64) -> Result<()> {
+    let available = match available_space(&path).await {
+        Ok(available) => available,
+        Err(err) => {
+            log::warn!(
+                "Skip the disk space check of {}: {err}",
+                path.as_ref().display()
+            );
+            return Ok(());
+        }
+    };
+    Ok(check_space(required, available)?)
+}
+
+/// The bytes the downloads take, by their known sizes
+pub fn download_size(downloads: &[Download]) -> u64 {
+    downloads.iter().filter_map(|download| download.size).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_space() {
+        let gigabyte = 1024 * 1024 * 1024;
+        assert!(check_space(gigabyte, 2 * gigabyte).is_ok());
+        let err = check_space(3 * gigabyte, 2 * gigabyte).unwrap_err();
+        assert_eq!(
+            err,
+            InsufficientDiskSpace {
+                required: 3 * gigabyte,
+                available: 2 * gigabyte,
+            }
+        );
+        assert!(err.to_string().contains("3072 MB"));
+    }
+
+    #[test]
+    fn test_parse_df_output() {
+        let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+            /dev/nvme0n1p2   490617784 81234560 384362168      18% /\n";
+        assert_eq!(parse_df_output(output), Some(384362168 * 1024));
+        assert_eq!(parse_df_output("df: /nowhere: No such file"), None);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_space() {
+        // no folder of the path exists, so the free space is unknown
+        let path = Path::new("relative/not/created");
+        assert!(available_space(path).await.is_err());
+        assert!(check_disk_space(path, u64::MAX).await.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_available_space() {
+        let path = crate::utils::download::test::temp_dir()
+            .join("not")
+            .join("created");
+        assert!(available_space(path).await.unwrap() > 0);
+    }
+}
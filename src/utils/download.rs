@@ -0,0 +1,840 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Download files
+//!
+//! # Example
+//!
+//! ```
+//! use aml_core::core::Download;
+//! use aml_core::core::task::TaskEventListeners;
+//! use aml_core::utils::download::download_files;
+//!
+//! async fn fn_name() {
+//!     let listeners = TaskEventListeners::default().on_file(Box::new(|path, success| {
+//!         println!("{path}: {success}")
+//!     }));
+//!     let downloads = vec![Download::new(
+//!         "https://piston-data.mojang.com/v1/objects/0c3ec587af28e5a785c0b4a7b8a30f9a8f78f838/client.jar",
+//!         ".minecraft/versions/1.20.1/1.20.1.jar",
+//!     )
+//!     .with_sha1("0c3ec587af28e5a785c0b4a7b8a30f9a8f78f838")];
+//!     download_files(downloads, &listeners, 16).await.unwrap();
+//! }
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use futures::{future::BoxFuture, StreamExt};
+use tokio::io::AsyncWriteExt;
+
+use crate::core::{
+    http_client,
+    task::{TaskEventListeners, WeightedProgress},
+    Download,
+};
+use crate::utils::path::long_path;
+
+/// A download backend, like aria2 or a rate limited client, passed to the installs instead of the
+/// built-in [`HttpDownloader`]
+pub trait Downloader: Send + Sync {
+    /// Download the file of the task, creating its parent folders if needed
+    fn fetch<'a>(&'a self, task: &'a Download) -> BoxFuture<'a, Result<()>>;
+
+    /// Whether `fetch` already checks the file against the sha1 of the task, and fails with a
+    /// [`ChecksumMismatch`] itself. The file is hashed again after `fetch` otherwise.
+    fn checks_sha1(&self) -> bool {
+        false
+    }
+}
+
+/// The built-in downloader, with the shared [`http_client`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpDownloader;
+
+impl Downloader for HttpDownloader {
+    fn fetch<'a>(&'a self, task: &'a Download) -> BoxFuture<'a, Result<()>> {
+        Box::pin(download(task))
+    }
+
+    fn checks_sha1(&self) -> bool {
+        true
+    }
+}
+
+/// How [`download_with_options`] retries a failed download
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownloadOptions {
+    /// The times to try again after the first failure
+    pub retries: u32,
+
+    /// The time limit of each try, from sending the request to the end of the body. None for no
+    /// limit
+    pub timeout: Option<Duration>,
+
+    /// The wait before the first retry, doubled for each next one
+    pub backoff_base: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            timeout: None,
+            backoff_base: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Download a single file with the default [`DownloadOptions`], the parent folders will be
+/// created if not exists. The file is fully written to the disk and closed when this returns.
+pub async fn download(download: &Download) -> Result<()> {
+    download_with_options(download, &DownloadOptions::default()).await
+}
+
+/// Download a single file, and retry with an exponential backoff if it fails. The client errors
+/// like 404 are not retried. The error of the last try is returned.
+pub async fn download_with_options(download: &Download, options: &DownloadOptions) -> Result<()> {
+    download_with_client(download, &http_client(), options).await
+}
+
+/// Same as [`download_with_options`], but with the given client instead of the shared
+/// [`http_client`]
+pub async fn download_with_client(
+    download: &Download,
+    client: &reqwest::Client,
+    options: &DownloadOptions,
+) -> Result<()> {
+    let mut backoff = options.backoff_base;
+    let mut retries = options.retries;
+    loop {
+        let err = match download_once(download, client, options.timeout).await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let client_error = err
+            .downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status)
+            .is_some_and(|status| status.is_client_error());
+        if retries == 0 || client_error {
+            return Err(err);
+        }
+        log::warn!("retry to download {} in {backoff:?}: {err}", download.url);
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        retries -= 1;
+    }
+}
+
+async fn download_once(
+    download: &Download,
+    client: &reqwest::Client,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let mut request = client.get(&download.url);
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    let response = request.send().await?.error_for_status()?;
+    let path = long_path(&download.file);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // a partial file never has the final name, so the existence checks can't mistake it
+    let part = part_path(&path);
+    let written = match write_part(response, &part, download.size).await {
+        Ok(()) => verify_part(&part, download).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = written {
+        tokio::fs::remove_file(&part).await.ok();
+        return Err(err);
+    }
+    tokio::fs::rename(&part, &path).await?;
+    Ok(())
+}
+
+/// Check the part file against the sha1 of the download if it's known, so a corrupt body never
+/// gets the final name
+async fn verify_part(part: &Path, download: &Download) -> Result<()> {
+    let expected = match &download.sha1 {
+        Some(sha1) if !sha1.is_empty() => sha1,
+        _ => return Ok(()),
+    };
+    let actual = current_sha1(part).await.unwrap_or_default();
+    if actual.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+    Err(ChecksumMismatch {
+        file: download.file.clone(),
+        expected: expected.clone(),
+        actual,
+    }
+    .into())
+}
+
+/// The file a download is written to before it's complete, `<file>.part`
+pub fn part_path(file: &Path) -> PathBuf {
+    let mut part = file.as_os_str().to_os_string();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Write the body to the part file, and check its size if it's known
+async fn write_part(mut response: reqwest::Response, part: &Path, size: Option<u64>) -> Result<()> {
+    let mut file = tokio::fs::File::create(part).await?;
+    let mut written = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    // the file is closed and on the disk once the download returns, so it can be read at once
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+    match size {
+        Some(size) if size != written => Err(anyhow!(
+            "{} is incomplete, {written} of {size} bytes are downloaded",
+            part.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Calculate the sha1 of a file, in lowercase hex
+pub fn file_sha1<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<String> {
+    use sha1::{Digest, Sha1};
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// A downloaded file doesn't match the sha1 of its download task
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumMismatch {
+    pub file: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The sha1 of {} is {} but {} is expected",
+            self.file.display(),
+            self.actual,
+            self.expected
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// The sha1 of a file off the async runtime, None if it can't be read
+async fn current_sha1(file: &Path) -> Option<String> {
+    let file = long_path(file);
+    tokio::task::spawn_blocking(move || file_sha1(file).ok())
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Fetch the task with the downloader and verify it by its sha1 if known.
+///
+/// With `verify_exists`, a file that already matches is not downloaded again. A file of another
+/// size than the task's is downloaded again without hashing it. A mismatched
+/// download is removed with a [`ChecksumMismatch`]. Retrying it is up to the downloader, like the
+/// [`HttpDownloader`] does with its backoff.
+pub(crate) async fn fetch_verified(
+    downloader: &dyn Downloader,
+    task: &Download,
+    verify_exists: bool,
+) -> Result<()> {
+    let expected = match &task.sha1 {
+        Some(sha1) if !sha1.is_empty() => sha1,
+        _ => return downloader.fetch(task).await,
+    };
+    let matches = |actual: &Option<String>| {
+        actual
+            .as_deref()
+            .is_some_and(|actual| actual.eq_ignore_ascii_case(expected))
+    };
+    if verify_exists && size_matches(task).await && matches(&current_sha1(&task.file).await) {
+        return Ok(());
+    }
+    downloader.fetch(task).await?;
+    if downloader.checks_sha1() {
+        return Ok(());
+    }
+    let actual = current_sha1(&task.file).await;
+    if matches(&actual) {
+        return Ok(());
+    }
+    tokio::fs::remove_file(long_path(&task.file)).await.ok();
+    Err(ChecksumMismatch {
+        file: task.file.clone(),
+        expected: expected.clone(),
+        actual: actual.unwrap_or_default(),
+    }
+    .into())
+}
+
+/// Whether the file has the size of the task, a cheap check before hashing it. True if the size
+/// is unknown.
+async fn size_matches(task: &Download) -> bool {
+    match task.size {
+        Some(size) => tokio::fs::metadata(long_path(&task.file))
+            .await
+            .is_ok_and(|metadata| metadata.len() == size),
+        None => true,
+    }
+}
+
+/// The number of files [`download_files`] downloads at the same time if not told otherwise
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Download files concurrently, at most `max_concurrency` at the same time
+///
+/// The `on_file` listener is triggered once for every download task, and the `on_progress`
+/// listener is triggered after it. If any download fails, the first error is returned after all
+/// tasks are finished.
+///
+/// The tasks with a sha1 are verified after downloading, and skipped if their file already
+/// matches it, see [`ChecksumMismatch`].
+pub async fn download_files(
+    downloads: Vec<Download>,
+    listeners: &TaskEventListeners,
+    max_concurrency: usize,
+) -> Result<()> {
+    download_files_with(
+        downloads,
+        listeners,
+        Arc::new(HttpDownloader),
+        max_concurrency,
+        true,
+    )
+    .await
+}
+
+/// Like [`download_files`], with the given downloader instead of the [`HttpDownloader`]. The
+/// files that already match their sha1 are only skipped with `verify_exists`, otherwise they are
+/// downloaded again.
+pub async fn download_files_with(
+    downloads: Vec<Download>,
+    listeners: &TaskEventListeners,
+    downloader: Arc<dyn Downloader>,
+    max_concurrency: usize,
+    verify_exists: bool,
+) -> Result<()> {
+    if max_concurrency == 0 {
+        return Err(anyhow!("The download concurrency must not be zero"));
+    }
+    listeners.start();
+    let mut weighted = WeightedProgress::new(&downloads);
+    let total = downloads.len();
+    let mut completed = 0;
+    let mut error = None;
+    let mut results = futures::stream::iter(downloads)
+        .map(|task| {
+            let downloader = downloader.clone();
+            async move {
+                let result = fetch_verified(downloader.as_ref(), &task, verify_exists).await;
+                (task, result)
+            }
+        })
+        .buffer_unordered(max_concurrency);
+    while let Some((task, result)) = results.next().await {
+        completed += 1;
+        listeners.file(task.file.to_string_lossy().to_string(), result.is_ok());
+        listeners.progress(completed, total, 1);
+        weighted.complete(&task);
+        listeners.weighted_progress(weighted);
+        if let Err(err) = result {
+            error.get_or_insert(err);
+        }
+    }
+    match error {
+        None => {
+            listeners.succeed();
+            Ok(())
+        }
+        Some(err) => {
+            listeners.failed();
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::AsyncReadExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    async fn respond(mut stream: TcpStream, status: &str, body: &[u8]) {
+        let mut buf = [0; 4096];
+        let mut request = Vec::new();
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+        let header = format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).await.ok();
+        stream.write_all(body).await.ok();
+    }
+
+    /// Serve `body` for every request on a local port, return the base url
+    pub(crate) async fn serve(status: &'static str, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let body = body.clone();
+                tokio::spawn(async move { respond(stream, status, &body).await });
+            }
+        });
+        format!("http://{address}")
+    }
+
+    pub(crate) fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("cvl-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_on_file() {
+        let url = serve("200 OK", b"content".to_vec()).await;
+        let root = temp_dir();
+        let downloads = (0..3)
+            .map(|i| Download {
+                url: format!("{url}/{i}"),
+                file: root.join(format!("{i}.txt")),
+                sha1: None,
+                size: None,
+            })
+            .collect::<Vec<_>>();
+        let files = Arc::new(Mutex::new(Vec::new()));
+        let files_clone = files.clone();
+        let listeners = TaskEventListeners::default().on_file(Box::new(move |path, success| {
+            files_clone.lock().unwrap().push((path, success));
+        }));
+        download_files(downloads, &listeners, DEFAULT_CONCURRENCY)
+            .await
+            .unwrap();
+
+        let mut files = files.lock().unwrap().clone();
+        files.sort();
+        let expected = (0..3)
+            .map(|i| {
+                (
+                    root.join(format!("{i}.txt")).to_string_lossy().to_string(),
+                    true,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(files, expected);
+        assert_eq!(std::fs::read(root.join("0.txt")).unwrap(), b"content");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_files_with() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl Downloader for Recorder {
+            fn fetch<'a>(&'a self, task: &'a Download) -> BoxFuture<'a, Result<()>> {
+                Box::pin(async move {
+                    self.0.lock().unwrap().push(task.url.clone());
+                    if task.url.ends_with("broken") {
+                        return Err(anyhow::anyhow!("broken"));
+                    }
+                    Ok(())
+                })
+            }
+        }
+
+        let downloads = ["a", "b", "broken"]
+            .map(|name| Download {
+                url: format!("aria2://{name}"),
+                file: PathBuf::from(name),
+                sha1: None,
+                size: None,
+            })
+            .to_vec();
+        let recorder = Arc::new(Recorder::default());
+        let result = download_files_with(
+            downloads,
+            &TaskEventListeners::default(),
+            recorder.clone(),
+            2,
+            true,
+        )
+        .await;
+        assert!(result.is_err());
+        let mut urls = recorder.0.lock().unwrap().clone();
+        urls.sort();
+        assert_eq!(urls, ["aria2://a", "aria2://b", "aria2://broken"]);
+    }
+
+    #[tokio::test]
+    async fn test_download_with_client() {
+        // echo the request back as the body
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            let header =
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {n}\r\nConnection: close\r\n\r\n");
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&buf[..n]).await.unwrap();
+        });
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-launcher", "amethyst".parse().unwrap());
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let root = temp_dir();
+        let task = Download {
+            url: format!("http://{address}/file"),
+            file: root.join("request.txt"),
+            sha1: None,
+            size: None,
+        };
+        download_with_client(&task, &client, &DownloadOptions::default())
+            .await
+            .unwrap();
+        let request = std::fs::read_to_string(&task.file).unwrap();
+        assert!(request.to_lowercase().contains("x-launcher: amethyst"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_sha1() {
+        use sha1::{Digest, Sha1};
+
+        #[derive(Default)]
+        struct Writer(Mutex<usize>);
+
+        impl Downloader for Writer {
+            fn fetch<'a>(&'a self, task: &'a Download) -> BoxFuture<'a, Result<()>> {
+                *self.0.lock().unwrap() += 1;
+                Box::pin(async move {
+                    std::fs::create_dir_all(task.file.parent().unwrap())?;
+                    std::fs::write(&task.file, b"tampered")?;
+                    Ok(())
+                })
+            }
+        }
+
+        let root = temp_dir();
+        let task = Download {
+            url: "aria2://file".to_string(),
+            file: root.join("file"),
+            sha1: Some(format!("{:x}", Sha1::digest(b"content"))),
+            size: None,
+        };
+        let listeners = TaskEventListeners::default();
+        let writer = Arc::new(Writer::default());
+        let err = download_files_with(vec![task.clone()], &listeners, writer.clone(), 1, true)
+            .await
+            .unwrap_err();
+        let mismatch = err.downcast_ref::<ChecksumMismatch>().unwrap();
+        assert_eq!(mismatch.actual, format!("{:x}", Sha1::digest(b"tampered")));
+        assert_eq!(*writer.0.lock().unwrap(), 1);
+        assert!(!task.file.exists());
+
+        // a file that already matches is kept
+        std::fs::write(&task.file, b"content").unwrap();
+        let writer = Arc::new(Writer::default());
+        download_files_with(vec![task.clone()], &listeners, writer.clone(), 1, true)
+            .await
+            .unwrap();
+        assert_eq!(*writer.0.lock().unwrap(), 0);
+        assert_eq!(std::fs::read(&task.file).unwrap(), b"content");
+
+        // unless it's told not to trust the existing files
+        let writer = Arc::new(Writer::default());
+        assert!(
+            download_files_with(vec![task.clone()], &listeners, writer.clone(), 1, false)
+                .await
+                .is_err()
+        );
+        assert_eq!(*writer.0.lock().unwrap(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_size_first() {
+        use sha1::{Digest, Sha1};
+
+        let root = temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let task = Download {
+            url: String::new(),
+            file: root.join("file"),
+            sha1: Some(format!("{:x}", Sha1::digest(b"content"))),
+            size: Some(7),
+        };
+        std::fs::write(&task.file, b"content").unwrap();
+        assert!(size_matches(&task).await);
+
+        // the sha1 would match, but the size is checked first
+        let task = Download {
+            size: Some(100),
+            ..task
+        };
+        assert!(!size_matches(&task).await);
+        let err = fetch_verified(&NoDownload, &task, true).await.unwrap_err();
+        assert_eq!(err.to_string(), "not downloaded");
+        assert!(
+            !size_matches(&Download {
+                file: root.join("missing"),
+                ..task
+            })
+            .await
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    struct NoDownload;
+
+    impl Downloader for NoDownload {
+        fn fetch<'a>(&'a self, _task: &'a Download) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async { Err(anyhow::anyhow!("not downloaded")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checksum_retries() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(0));
+        let requests_clone = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                *requests_clone.lock().unwrap() += 1;
+                respond(stream, "200 OK", b"tampered").await;
+            }
+        });
+
+        let root = temp_dir();
+        let task = Download {
+            url: format!("http://{address}/file"),
+            file: root.join("file"),
+            sha1: Some("0".repeat(40)),
+            size: None,
+        };
+        let err = fetch_verified(&HttpDownloader, &task, false)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<ChecksumMismatch>().is_some());
+        // only the retries of the downloader, with the default options
+        assert_eq!(*requests.lock().unwrap(), 3);
+        assert!(!task.file.exists());
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_download_retry() {
+        // fail the first two requests, then serve the file
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(0));
+        let requests_clone = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let count = {
+                    let mut requests = requests_clone.lock().unwrap();
+                    *requests += 1;
+                    *requests
+                };
+                match count {
+                    1 | 2 => respond(stream, "503 Service Unavailable", b"").await,
+                    _ => respond(stream, "200 OK", b"content").await,
+                }
+            }
+        });
+
+        let root = temp_dir();
+        let task = Download {
+            url: format!("http://{address}/file"),
+            file: root.join("file"),
+            sha1: None,
+            size: None,
+        };
+        let options = |retries| DownloadOptions {
+            retries,
+            timeout: Some(Duration::from_secs(5)),
+            backoff_base: Duration::from_millis(10),
+        };
+        assert!(download_with_options(&task, &options(1)).await.is_err());
+        assert_eq!(*requests.lock().unwrap(), 2);
+        download_with_options(&task, &options(1)).await.unwrap();
+        assert_eq!(std::fs::read(&task.file).unwrap(), b"content");
+
+        let url = serve("404 Not Found", vec![]).await;
+        let missing = Download {
+            url: format!("{url}/missing"),
+            ..task
+        };
+        // a retry would wait at least a second
+        let options = DownloadOptions {
+            backoff_base: Duration::from_secs(1),
+            ..options(3)
+        };
+        let started = std::time::Instant::now();
+        assert!(download_with_options(&missing, &options).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_persisted() {
+        use sha1::{Digest, Sha1};
+
+        let content = vec![7; 4 * 1024 * 1024];
+        let url = serve("200 OK", content.clone()).await;
+        let root = temp_dir();
+        let task = Download {
+            url: format!("{url}/large"),
+            file: root.join("large"),
+            sha1: None,
+            size: None,
+        };
+        download(&task).await.unwrap();
+        assert_eq!(
+            file_sha1(&task.file).unwrap(),
+            format!("{:x}", Sha1::digest(&content))
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency() {
+        #[derive(Default)]
+        struct Counter(Mutex<(usize, usize)>);
+
+        impl Downloader for Counter {
+            fn fetch<'a>(&'a self, _task: &'a Download) -> BoxFuture<'a, Result<()>> {
+                Box::pin(async move {
+                    {
+                        let mut counter = self.0.lock().unwrap();
+                        counter.0 += 1;
+                        counter.1 = counter.1.max(counter.0);
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    self.0.lock().unwrap().0 -= 1;
+                    Ok(())
+                })
+            }
+        }
+
+        let downloads = (0..12)
+            .map(|i| Download {
+                url: format!("aria2://{i}"),
+                file: PathBuf::from(i.to_string()),
+                sha1: None,
+                size: None,
+            })
+            .collect::<Vec<_>>();
+        let listeners = TaskEventListeners::default();
+        let counter = Arc::new(Counter::default());
+        download_files_with(downloads.clone(), &listeners, counter.clone(), 4, true)
+            .await
+            .unwrap();
+        assert_eq!(counter.0.lock().unwrap().1, 4);
+        assert!(download_files_with(downloads, &listeners, counter, 0, true)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download() {
+        // promise more bytes than sent, then close the connection
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf).await;
+                let header = "HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\n";
+                stream.write_all(header.as_bytes()).await.ok();
+                stream.write_all(b"partial").await.ok();
+            }
+        });
+
+        let root = temp_dir();
+        let task = Download {
+            url: format!("http://{address}/file"),
+            file: root.join("file"),
+            sha1: None,
+            size: None,
+        };
+        let options = DownloadOptions {
+            retries: 0,
+            ..Default::default()
+        };
+        assert!(download_with_options(&task, &options).await.is_err());
+        assert!(!task.file.exists());
+        assert!(!part_path(&task.file).exists());
+
+        // a complete body of a wrong size is not kept either
+        let url = serve("200 OK", b"short".to_vec()).await;
+        let task = Download {
+            url: format!("{url}/file"),
+            size: Some(100),
+            ..task
+        };
+        assert!(download_with_options(&task, &options).await.is_err());
+        assert!(!task.file.exists());
+
+        // nor a body that doesn't match the sha1
+        let task = Download {
+            size: None,
+            sha1: Some("0".repeat(40)),
+            ..task
+        };
+        let err = download_with_options(&task, &options).await.unwrap_err();
+        assert!(err.downcast_ref::<ChecksumMismatch>().is_some());
+        assert!(!task.file.exists());
+        assert!(!part_path(&task.file).exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
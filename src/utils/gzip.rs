@@ -0,0 +1,94 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Transparent gzip decompression for logs and http responses
+
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use reqwest::header::ACCEPT_ENCODING;
+
+use crate::core::http_client;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decompress the data if it starts with the gzip magic number, otherwise return it as is
+pub fn decompress_if_gzip(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data);
+    }
+    let mut buf = Vec::new();
+    GzDecoder::new(data.as_slice()).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read a text file, like `latest.log` or the rotated `2023-08-01-1.log.gz`
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let data = decompress_if_gzip(std::fs::read(path)?)?;
+    String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Fetch the body of the url, accepting gzip compressed response
+pub async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let response = http_client()
+        .get(url)
+        .header(ACCEPT_ENCODING, "gzip")
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(decompress_if_gzip(response.bytes().await?.to_vec())?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_gzipped_log() {
+        let root = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&root).unwrap();
+        let log = "[12:00:00] [main/INFO]: Setting user: Steve\n";
+        std::fs::write(root.join("2023-08-01-1.log.gz"), gzip(log.as_bytes())).unwrap();
+        std::fs::write(root.join("latest.log"), log).unwrap();
+
+        assert_eq!(
+            read_to_string(root.join("2023-08-01-1.log.gz")).unwrap(),
+            log
+        );
+        assert_eq!(read_to_string(root.join("latest.log")).unwrap(), log);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gzip() {
+        let url = crate::utils::download::test::serve("200 OK", gzip(b"{}")).await;
+        assert_eq!(fetch(&url).await.unwrap(), b"{}");
+    }
+}
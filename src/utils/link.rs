@@ -0,0 +1,114 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Share files between game folders without copying them
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How a file is placed into another folder
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// Copy the file
+    #[default]
+    Copy,
+
+    /// Hardlink the file, fall back to copy if the filesystem doesn't support it, or the two
+    /// paths are on different filesystems
+    Hardlink,
+}
+
+/// Place `from` at `to` with the mode, the parent folders of `to` will be created if not exists.
+///
+/// The file at `to` is only replaced once the link or copy is ready, and nothing is done if both
+/// paths are the same file. Return whether the file is linked rather than copied.
+pub fn link_or_copy<P: AsRef<Path>, Q: AsRef<Path>>(
+    from: P,
+    to: Q,
+    mode: LinkMode,
+) -> io::Result<bool> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if to.exists() && from.canonicalize()? == to.canonicalize()? {
+        return Ok(true);
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut pending = to.as_os_str().to_os_string();
+    pending.push(".link");
+    let pending = PathBuf::from(pending);
+    if pending.exists() {
+        std::fs::remove_file(&pending)?;
+    }
+    let linked = mode == LinkMode::Hardlink && std::fs::hard_link(from, &pending).is_ok();
+    if !linked {
+        std::fs::copy(from, &pending)?;
+    }
+    if let Err(err) = std::fs::rename(&pending, to) {
+        std::fs::remove_file(&pending).ok();
+        return Err(err);
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    fn same_file(a: &Path, b: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        std::fs::metadata(a).unwrap().ino() == std::fs::metadata(b).unwrap().ino()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_or_copy() {
+        use std::os::unix::fs::MetadataExt;
+
+        let root = crate::utils::download::test::temp_dir();
+        let from = root.join("store/objects/ab/abcdef");
+        std::fs::create_dir_all(from.parent().unwrap()).unwrap();
+        std::fs::write(&from, "asset").unwrap();
+
+        let to = root.join("instance/objects/ab/abcdef");
+        assert!(link_or_copy(&from, &to, LinkMode::Hardlink).unwrap());
+        assert!(same_file(&from, &to));
+
+        assert!(!link_or_copy(&from, &to, LinkMode::Copy).unwrap());
+        assert!(!same_file(&from, &to));
+        assert_eq!(std::fs::read(&to).unwrap(), b"asset");
+
+        // the same file is kept, even through another path
+        let alias = root.join("store/objects/../objects/ab/abcdef");
+        assert!(link_or_copy(&from, &alias, LinkMode::Copy).unwrap());
+        assert_eq!(std::fs::read(&from).unwrap(), b"asset");
+
+        // /dev/shm is usually a tmpfs, linking into it crosses filesystems
+        let shm = Path::new("/dev/shm");
+        let device = |path: &Path| std::fs::metadata(path).unwrap().dev();
+        if shm.is_dir() && device(shm) != device(&root) {
+            let to = shm.join(format!("cvl-test-{}", uuid::Uuid::new_v4()));
+            assert!(!link_or_copy(&from, &to, LinkMode::Hardlink).unwrap());
+            assert_eq!(std::fs::read(&to).unwrap(), b"asset");
+            std::fs::remove_file(to).unwrap();
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -16,5 +16,11 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod cache;
+pub mod disk;
+pub mod download;
+pub mod gzip;
+pub mod link;
 pub mod nbt;
+pub mod path;
 pub mod unzip;
@@ -0,0 +1,106 @@
+/*
+ * Amethyst Launcher Core
+ * Copyright (C) 2023 Broken-Deer <old_driver__@outlook.com> and contributors
+ *
+ * This program is free software, you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Path helpers for the file operations
+
+use std::path::{Path, PathBuf};
+
+/// Convert an absolute path to the extended-length form like `\\?\C:\...` on Windows, so the
+/// files deeper than `MAX_PATH` (260 characters) can be created.
+///
+/// The `/` separators and `..` are resolved, since Windows doesn't do it for such paths.
+/// Relative and already prefixed paths, and all paths on other platforms, are returned as is.
+pub fn long_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    #[cfg(windows)]
+    {
+        windows_long_path(path.as_ref())
+    }
+    #[cfg(not(windows))]
+    {
+        path.as_ref().to_path_buf()
+    }
+}
+
+#[cfg(windows)]
+fn windows_long_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let prefix = match components.next() {
+        Some(Component::Prefix(prefix)) => prefix,
+        _ => return path.to_path_buf(),
+    };
+    let mut result = match prefix.kind() {
+        Prefix::Disk(disk) => OsString::from(format!(r"\\?\{}:", disk as char)),
+        Prefix::UNC(server, share) => {
+            let mut result = OsString::from(r"\\?\UNC\");
+            result.push(server);
+            result.push(r"\");
+            result.push(share);
+            result
+        }
+        _ => return path.to_path_buf(),
+    };
+    // a drive relative path like `C:foo`
+    if components.next() != Some(Component::RootDir) {
+        return path.to_path_buf();
+    }
+    let mut parts = Vec::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    if parts.is_empty() {
+        result.push(r"\");
+    }
+    for part in parts {
+        result.push(r"\");
+        result.push(part);
+    }
+    PathBuf::from(result)
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_long_path() {
+        assert_eq!(
+            long_path(r"C:\Users\Steve/.minecraft\libraries\..\versions"),
+            PathBuf::from(r"\\?\C:\Users\Steve\.minecraft\versions")
+        );
+
+        let root = crate::utils::download::test::temp_dir();
+        let mut deep = root.clone();
+        while deep.as_os_str().len() <= 300 {
+            deep = deep.join("net.minecraftforge.fmlonlyloader");
+        }
+        let file = deep.join("fmlonly-1.20.1-47.2.0.jar");
+        std::fs::create_dir_all(long_path(&deep)).unwrap();
+        std::fs::write(long_path(&file), "jar").unwrap();
+        assert_eq!(std::fs::read(long_path(&file)).unwrap(), b"jar");
+        std::fs::remove_dir_all(long_path(root)).unwrap();
+    }
+}
@@ -21,13 +21,15 @@ use std::{
     ffi::OsStr,
     fs::File,
     io::{self, Read},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use tokio::fs::create_dir_all;
 use zip::{read::ZipFile, CompressionMethod, DateTime, ZipArchive};
 
+use crate::utils::path::long_path;
+
 #[derive(Debug, Clone)]
 pub struct Entry {
     pub version_name_by: (u8, u8),
@@ -141,48 +143,201 @@ pub async fn decompression_file(zip_archive: &mut ZipArchive<File>, from: String
     tokio::fs::write(to, buf).await.unwrap();
 }
 
+/// The max total uncompressed size of a single extraction, to guard against zip bombs
+pub const MAX_UNCOMPRESSED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Check that the entry name is a relative path inside the target folder
+///
+/// Absolute paths and `..` components are rejected.
+pub fn sanitize_entry_name(name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    for component in path.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            _ => return Err(anyhow!("malicious zip entry: {name}")),
+        }
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Read the whole entry, `remaining` is the size still allowed to be extracted
+fn read_limited<R: Read>(reader: R, name: &str, remaining: &mut u64) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.take(*remaining + 1).read_to_end(&mut buf)?;
+    if buf.len() as u64 > *remaining {
+        return Err(anyhow!(
+            "zip entry {name} exceeds the max uncompressed size"
+        ));
+    }
+    *remaining -= buf.len() as u64;
+    Ok(buf)
+}
+
 pub async fn decompression_files<R: Read + io::Seek>(
     zip_archive: &mut ZipArchive<R>,
     tasks: Vec<(String, PathBuf)>,
-) {
+) -> Result<()> {
+    let mut remaining = MAX_UNCOMPRESSED_SIZE;
     // todo: 在线程池读取，并发写入
     for task in tasks {
-        let mut buf: Vec<u8> = Vec::new();
-        zip_archive
-            .by_name(&task.0)
-            .unwrap()
-            .read_to_end(&mut buf)
-            .unwrap();
-        create_dir_all(task.1.parent().unwrap()).await.unwrap();
-        tokio::fs::write(task.1, buf).await.unwrap();
+        let buf = read_limited(zip_archive.by_name(&task.0)?, &task.0, &mut remaining)?;
+        let path = long_path(&task.1);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, buf).await?;
     }
+    Ok(())
 }
 
 pub fn decompression_all<R: Read + io::Seek, S: AsRef<OsStr> + ?Sized>(
     zip_archive: &mut ZipArchive<R>,
     to: &S,
 ) -> Result<()> {
+    decompression_all_with_limit(zip_archive, to, MAX_UNCOMPRESSED_SIZE)
+}
+
+/// Extract the native libraries of a jar, like [`decompression_all`]
+///
+/// The existing files that cannot be overwritten because another game holds them open, like
+/// `lwjgl.dll` on Windows, are skipped, as the native in use is the same one. Return the
+/// skipped files.
+pub fn decompression_natives<R: Read + io::Seek, S: AsRef<OsStr> + ?Sized>(
+    zip_archive: &mut ZipArchive<R>,
+    to: &S,
+) -> Result<Vec<PathBuf>> {
+    extract(zip_archive, to, MAX_UNCOMPRESSED_SIZE, true)
+}
+
+/// Whether the write failed because another process has the file open, which Windows forbids
+fn is_in_use(err: &io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION, other permission errors are real failures
+    cfg!(windows) && err.raw_os_error() == Some(32)
+}
+
+fn decompression_all_with_limit<R: Read + io::Seek, S: AsRef<OsStr> + ?Sized>(
+    zip_archive: &mut ZipArchive<R>,
+    to: &S,
+    max_size: u64,
+) -> Result<()> {
+    extract(zip_archive, to, max_size, false)?;
+    Ok(())
+}
+
+fn extract<R: Read + io::Seek, S: AsRef<OsStr> + ?Sized>(
+    zip_archive: &mut ZipArchive<R>,
+    to: &S,
+    max_size: u64,
+    skip_in_use: bool,
+) -> Result<Vec<PathBuf>> {
     let to = Path::new(to).to_path_buf();
+    let mut remaining = max_size;
+    let mut skipped = Vec::new();
     for i in 0..zip_archive.len() {
-        let mut zip_file = zip_archive.by_index(i).unwrap();
+        let zip_file = zip_archive.by_index(i)?;
         let name = zip_file.name().to_string();
-        let entry = Entry::from_zip_file(&mut zip_file);
-        let path = to.join(&name);
+        let path = long_path(to.join(sanitize_entry_name(&name)?));
         if zip_file.is_dir() {
-            std::fs::create_dir_all(zip_file.name()).unwrap();
+            std::fs::create_dir_all(path)?;
             continue;
         }
+        let content = read_limited(zip_file, &name, &mut remaining)?;
         std::fs::create_dir_all(
             path.parent()
                 .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
         )?;
-        std::fs::write(path, entry.content).unwrap();
-        // for entry in entries {
-        //     let entry = entry.to_string();
-        //     if name == entry {
-        //         resolved_entries.insert(entry, Entry::from_zip_file(&mut zip_file));
-        //     }
-        // }
+        match std::fs::write(&path, content) {
+            Err(err) if skip_in_use && path.is_file() && is_in_use(&err) => skipped.push(path),
+            result => result?,
+        }
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn zip_with(entries: &[(&str, &[u8])]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer.start_file(*name, Default::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_decompression_natives_skips_locked_files() {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        let target = crate::utils::download::test::temp_dir();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("lwjgl.dll"), b"dll").unwrap();
+        // a running game keeps the dll open without sharing
+        let locked = std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(target.join("lwjgl.dll"))
+            .unwrap();
+        let mut zip = zip_with(&[("lwjgl.dll", b"dll"), ("OpenAL.dll", b"openal")]);
+        let skipped = decompression_natives(&mut zip, &target).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].ends_with("lwjgl.dll"));
+        assert_eq!(std::fs::read(target.join("OpenAL.dll")).unwrap(), b"openal");
+        assert!(decompression_all(&mut zip, &target).is_err());
+        drop(locked);
+        std::fs::remove_dir_all(target).unwrap();
+    }
+
+    #[test]
+    fn test_entries_record() {
+        let mut jar = zip_with(&[
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\n"),
+            ("net/optifine/Config.class", b"\xca\xfe\xba\xbe"),
+        ]);
+        let entries = Entry::from_zip_archive(&mut jar);
+        assert_eq!(entries.len(), 2);
+        let record = Entry::get_entries_record(entries);
+        let manifest = &record["META-INF/MANIFEST.MF"];
+        assert_eq!(manifest.content, b"Manifest-Version: 1.0\n");
+        assert_eq!(manifest.size, 22);
+        assert!(matches!(manifest.r#type, EntryType::File));
+        assert_eq!(
+            record["net/optifine/Config.class"].content,
+            b"\xca\xfe\xba\xbe"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_entry_name() {
+        assert!(sanitize_entry_name("org/lwjgl/liblwjgl.so").is_ok());
+        assert!(sanitize_entry_name("../../etc/passwd").is_err());
+        assert!(sanitize_entry_name("natives/../../evil.so").is_err());
+        assert!(sanitize_entry_name("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_decompression_all_rejects_malicious_entries() {
+        let root = crate::utils::download::test::temp_dir();
+        let target = root.join("natives");
+
+        let mut zip = zip_with(&[("ok.so", b"ok"), ("../evil.so", b"evil")]);
+        assert!(decompression_all(&mut zip, &target).is_err());
+        assert!(!root.join("evil.so").exists());
+
+        let mut zip = zip_with(&[("big.so", &[0; 1024])]);
+        assert!(decompression_all_with_limit(&mut zip, &target, 100).is_err());
+        assert!(!target.join("big.so").exists());
+
+        let mut zip = zip_with(&[("ok.so", b"ok")]);
+        decompression_all(&mut zip, &target).unwrap();
+        assert_eq!(std::fs::read(target.join("ok.so")).unwrap(), b"ok");
+        std::fs::remove_dir_all(root).unwrap();
     }
-    Ok(())
 }